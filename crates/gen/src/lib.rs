@@ -1,12 +1,13 @@
 #![doc = include_str!("../README.md")]
 
 use ctor::ctor;
-use jni::objects::{JMethodID, JObject, JValue};
+use jni::objects::{JMethodID, JObject, JObjectArray, JString, JValue};
 use jni::signature::{Primitive, ReturnType};
 use jni::sys::jvalue;
 use jni::{InitArgsBuilder, JNIEnv, JNIVersion, JavaVM};
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 #[ctor]
 static JVM: JavaVM = {
@@ -20,9 +21,15 @@ static JVM: JavaVM = {
     jvm
 };
 
+///the classes of the jar are defined once for the whole JVM, the next generators reuse them
+///so they must be created from the same jar
+static JAR_LOADED: Mutex<Option<PathBuf>> = Mutex::new(None);
+
 pub struct Generator<'a> {
     generator_java_instance: JObject<'a>,
     get_block_method: JMethodID,
+    get_biome_method: JMethodID,
+    get_biomes_method: JMethodID,
 }
 
 impl<'a> Generator<'a> {
@@ -47,21 +54,44 @@ impl<'a> Generator<'a> {
         Ok(())
     }
 
+    ///create a generator for the current thread, a thread can't use the generator of another one
+    ///the jar is only loaded by the first generator, so the generators of the worker threads are cheap to create
+    ///a generator can't be created from another jar than the first one
     pub fn new(path: impl AsRef<Path>, seed: i64) -> anyhow::Result<Self> {
         JVM.attach_current_thread_as_daemon().unwrap();
 
         let mut env = JVM.get_env()?;
 
-        Self::load_jar(&mut env, path)?;
+        let path = std::fs::canonicalize(path)?;
+        let mut jar_loaded = JAR_LOADED.lock().unwrap();
+        match &*jar_loaded {
+            Some(loaded) if *loaded != path => anyhow::bail!(
+                "the jar {} is already loaded, can't load {}",
+                loaded.display(),
+                path.display()
+            ),
+            Some(_) => {}
+            None => {
+                Self::load_jar(&mut env, &path)?;
+                *jar_loaded = Some(path);
+            }
+        }
+        drop(jar_loaded);
 
         let generator_class = env.find_class("org/archipel/generator/Generator")?;
         let jvalue = JValue::from(seed);
         let generator_java_instance = env.new_object(&generator_class, "(J)V", &[jvalue])?;
-        let get_block_method = env.get_method_id(generator_class, "getBlock", "(III)I")?;
+        let get_block_method = env.get_method_id(&generator_class, "getBlock", "(III)I")?;
+        let get_biome_method =
+            env.get_method_id(&generator_class, "getBiome", "(III)Ljava/lang/String;")?;
+        let get_biomes_method =
+            env.get_method_id(generator_class, "getBiomes", "(III)[Ljava/lang/String;")?;
 
         Ok(Self {
             generator_java_instance,
             get_block_method,
+            get_biome_method,
+            get_biomes_method,
         })
     }
 
@@ -82,4 +112,59 @@ impl<'a> Generator<'a> {
             .unwrap()
         }
     }
+
+    ///the identifier of the biome at the given position, like minecraft:plains
+    pub fn get_biome(&mut self, x: i32, y: i32, z: i32) -> String {
+        let mut env = JVM.get_env().unwrap();
+        let biome = unsafe {
+            let x = jvalue { i: x };
+            let y = jvalue { i: y };
+            let z = jvalue { i: z };
+            env.call_method_unchecked(
+                &self.generator_java_instance,
+                self.get_biome_method,
+                ReturnType::Object,
+                &[x, y, z],
+            )
+            .unwrap()
+            .l()
+            .unwrap()
+        };
+        let biome = JString::from(biome);
+        let name = env.get_string(&biome).unwrap().into();
+        env.delete_local_ref(biome).unwrap();
+        name
+    }
+
+    ///the identifiers of the biomes of the 4x4x4 cells of a chunk in the y, z, x order, sampled at the corner of each cell with the lowest coordinates
+    ///a single call to the JVM for the whole chunk, get_biome costs a call per cell
+    pub fn get_biomes(&mut self, chunk_x: i32, chunk_y: i32, chunk_z: i32) -> Vec<String> {
+        let mut env = JVM.get_env().unwrap();
+        let biomes = unsafe {
+            let x = jvalue { i: chunk_x };
+            let y = jvalue { i: chunk_y };
+            let z = jvalue { i: chunk_z };
+            env.call_method_unchecked(
+                &self.generator_java_instance,
+                self.get_biomes_method,
+                ReturnType::Array,
+                &[x, y, z],
+            )
+            .unwrap()
+            .l()
+            .unwrap()
+        };
+        let biomes = JObjectArray::from(biomes);
+        let len = env.get_array_length(&biomes).unwrap();
+        let names = (0..len)
+            .map(|index| {
+                let biome = JString::from(env.get_object_array_element(&biomes, index).unwrap());
+                let name = env.get_string(&biome).unwrap().into();
+                env.delete_local_ref(biome).unwrap();
+                name
+            })
+            .collect();
+        env.delete_local_ref(biomes).unwrap();
+        names
+    }
 }
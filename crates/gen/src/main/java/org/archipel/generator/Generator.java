@@ -19,10 +19,15 @@ public class Generator
     private static final int SEA_LEVEL = -14;
     private static final int SNOW_LEVEL = 22;
 
-    public int getBlock(int x, int y, int z)
+    private int getSurfaceLevel(int x, int z)
     {
         final var noise = this.perlinNoise.fractalBrownianMotion(x * INPUT_FACTOR, z * INPUT_FACTOR, 8);
-        final var surfaceLevel = Math.round(noise * 35);
+        return Math.round(noise * 35);
+    }
+
+    public int getBlock(int x, int y, int z)
+    {
+        final var surfaceLevel = this.getSurfaceLevel(x, z);
 
         if(y >= SNOW_LEVEL && y <= surfaceLevel)
             return 11;
@@ -43,6 +48,37 @@ public class Generator
         return 0;
     }
 
+    public String getBiome(int x, int y, int z)
+    {
+        final var surfaceLevel = this.getSurfaceLevel(x, z);
+
+        if(surfaceLevel < SEA_LEVEL)
+            return "minecraft:ocean";
+
+        if(surfaceLevel >= SNOW_LEVEL)
+            return "minecraft:snowy_slopes";
+
+        return "minecraft:plains";
+    }
+
+    private static final int CHUNK_SIZE = 16;
+    private static final int BIOME_CELL_SIZE = 4;
+    private static final int BIOME_CELL_SIDE = CHUNK_SIZE / BIOME_CELL_SIZE;
+
+    // the biomes of the cells of a chunk in the y, z, x order, sampled at the lowest corner of each cell
+    public String[] getBiomes(int chunkX, int chunkY, int chunkZ)
+    {
+        final var biomes = new String[BIOME_CELL_SIDE * BIOME_CELL_SIDE * BIOME_CELL_SIDE];
+        for(int index = 0; index < biomes.length; index++)
+        {
+            final var x = chunkX * CHUNK_SIZE + (index % BIOME_CELL_SIDE) * BIOME_CELL_SIZE;
+            final var y = chunkY * CHUNK_SIZE + (index / (BIOME_CELL_SIDE * BIOME_CELL_SIDE)) * BIOME_CELL_SIZE;
+            final var z = chunkZ * CHUNK_SIZE + ((index / BIOME_CELL_SIDE) % BIOME_CELL_SIDE) * BIOME_CELL_SIZE;
+            biomes[index] = this.getBiome(x, y, z);
+        }
+        return biomes;
+    }
+
     public long getSeed()
     {
         return this.seed;
@@ -1,4 +1,4 @@
-use glam::IVec3;
+use glam::{IVec3, Vec3};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct AABB {
@@ -20,6 +20,16 @@ impl AABB {
         Self { min, max }
     }
 
+    ///the smallest corner, included in the AABB
+    pub fn min(&self) -> IVec3 {
+        self.min
+    }
+
+    ///the biggest corner, excluded from the AABB
+    pub fn max(&self) -> IVec3 {
+        self.max
+    }
+
     pub fn contains(&self, pos: IVec3) -> bool {
         pos.x >= self.min.x
             && pos.x <= self.max.x
@@ -88,3 +98,65 @@ impl AABB {
         pos.clamp(self.min, self.max)
     }
 }
+
+///a floating point AABB, used for the hitbox of the entities and the collision shapes of the blocks
+///the coordinates are usually relative to a chunk or a block to keep the precision of the f32
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloatAABB {
+    pub(crate) min: Vec3,
+    pub(crate) max: Vec3,
+}
+
+impl FloatAABB {
+    ///the AABB of a full block, from 0 to 1 on each axis
+    pub const UNIT: FloatAABB = FloatAABB {
+        min: Vec3::ZERO,
+        max: Vec3::ONE,
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        debug_assert!(min.cmple(max).all(), "min is greater than max");
+        Self { min, max }
+    }
+
+    ///an AABB centered horizontally on the origin with its bottom at the origin, like the hitbox of an entity
+    pub fn from_feet(width: f32, height: f32) -> Self {
+        let half_width = width / 2.0;
+        Self::new(
+            Vec3::new(-half_width, 0.0, -half_width),
+            Vec3::new(half_width, height, half_width),
+        )
+    }
+
+    pub fn min(&self) -> Vec3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Vec3 {
+        self.max
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn offset(&self, offset: Vec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    ///extend the AABB in the direction of the motion, so it contains every position of the AABB along the motion
+    pub fn expand_towards(&self, motion: Vec3) -> Self {
+        Self {
+            min: self.min + motion.min(Vec3::ZERO),
+            max: self.max + motion.max(Vec3::ZERO),
+        }
+    }
+
+    ///return true if the AABBs overlap, touching faces don't count
+    pub fn intersects(&self, other: &FloatAABB) -> bool {
+        self.min.cmplt(other.max).all() && self.max.cmpgt(other.min).all()
+    }
+}
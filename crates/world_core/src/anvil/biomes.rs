@@ -0,0 +1,35 @@
+use crate::anvil::block_states::unpack_indices;
+use crate::anvil::AnvilError;
+use crate::biome::{Biome, ChunkBiomes, BIOME_CELL_COUNT, MAX_BIOME_LEN};
+use nbt::{Compound, List, Value};
+
+///convert the biomes of a 1.18+ section, vanilla stores them per 4x4x4 cell in the same y, z, x order as ChunkBiomes
+pub(super) fn read_biomes(biomes: &Compound) -> Result<ChunkBiomes, AnvilError> {
+    let palette = match biomes.get("palette") {
+        Some(Value::List(List::String(palette))) if !palette.is_empty() => palette,
+        _ => return Err(AnvilError::InvalidField("palette")),
+    };
+    let palette = palette
+        .iter()
+        .map(|name| match Biome::try_from(name.as_str()) {
+            Ok(biome) if biome.as_str().len() <= MAX_BIOME_LEN => Ok(biome),
+            _ => Err(AnvilError::InvalidField("biomes")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    //a single entry palette doesn't store any data
+    if palette.len() == 1 {
+        return Ok(ChunkBiomes::new_uniform(palette[0].clone()));
+    }
+
+    //vanilla use as few bits as possible for the biomes, and its palette may keep unused entries, so the cells are set one by one
+    let indices = match biomes.get("data") {
+        Some(Value::LongArray(data)) => unpack_indices(data, palette.len(), BIOME_CELL_COUNT, 1)?,
+        _ => return Err(AnvilError::InvalidField("data")),
+    };
+    let mut chunk_biomes = ChunkBiomes::new_uniform(palette[indices[0] as usize].clone());
+    for (cell, index) in indices.iter().enumerate().skip(1) {
+        chunk_biomes.set_cell(cell, palette[*index as usize].clone());
+    }
+    Ok(chunk_biomes)
+}
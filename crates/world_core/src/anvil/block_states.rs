@@ -0,0 +1,160 @@
+use crate::anvil::AnvilError;
+use crate::block_state::{BlockState, BlockStateRegistry, AIR};
+use crate::Chunk;
+use math::positions::{BlockPos, ChunkPos};
+use nbt::{Compound, List, Value};
+
+///the number of blocks in a vanilla section, vanilla sections line up with our chunks
+const SECTION_VOLUME: usize = Chunk::BLOCK_COUNT;
+
+///translate the palette of a section, the unknown blocks are replaced by unknown_state or make the import fail
+fn read_palette(
+    registry: &BlockStateRegistry,
+    unknown_state: Option<BlockState>,
+    palette: &[Compound],
+) -> Result<Vec<BlockState>, AnvilError> {
+    palette
+        .iter()
+        .map(|entry| {
+            let Some(Value::String(name)) = entry.get("Name") else {
+                return Err(AnvilError::InvalidField("Name"));
+            };
+            let properties = match entry.get("Properties") {
+                Some(Value::Compound(properties)) => properties
+                    .iter()
+                    .map(|(key, value)| match value {
+                        Value::String(value) => Ok((key.as_str(), value.as_str())),
+                        _ => Err(AnvilError::InvalidField("Properties")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                Some(_) => return Err(AnvilError::InvalidField("Properties")),
+                None => Vec::new(),
+            };
+
+            match registry.state_of(name, &properties) {
+                Some(state) => Ok(state),
+                None => unknown_state.ok_or_else(|| AnvilError::UnknownBlock(name.clone())),
+            }
+        })
+        .collect()
+}
+
+///vanilla use at least 4 bits per block
+const MIN_BLOCK_BITS: u32 = 4;
+
+///unpack the palette indices of a section, since 1.16 an index never spans two longs, the unused high bits of each long are padding
+///the indices are in the y, z, x order, count is the number of entries, 4096 for the blocks and 64 for the biomes
+pub(super) fn unpack_indices(
+    data: &[i64],
+    palette_len: usize,
+    count: usize,
+    min_bits: u32,
+) -> Result<Vec<u16>, AnvilError> {
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(min_bits) as usize;
+    let per_long = 64 / bits;
+    if data.len() != count.div_ceil(per_long) {
+        return Err(AnvilError::InvalidField("data"));
+    }
+
+    let mask = (1u64 << bits) - 1;
+    let mut indices = Vec::with_capacity(count);
+    for long in data {
+        let long = *long as u64;
+        for i in 0..per_long {
+            if indices.len() == count {
+                break;
+            }
+            let index = ((long >> (i * bits)) & mask) as u16;
+            if index as usize >= palette_len {
+                return Err(AnvilError::InvalidPaletteIndex(index));
+            }
+            indices.push(index);
+        }
+    }
+    Ok(indices)
+}
+
+///convert the block_states of a 1.18+ section into a chunk, return None if the section only contains air
+pub(super) fn read_section(
+    registry: &BlockStateRegistry,
+    unknown_state: Option<BlockState>,
+    position: ChunkPos,
+    block_states: &Compound,
+) -> Result<Option<Chunk>, AnvilError> {
+    let palette = match block_states.get("palette") {
+        Some(Value::List(List::Compound(palette))) if !palette.is_empty() => palette,
+        _ => return Err(AnvilError::InvalidField("palette")),
+    };
+    let palette = read_palette(registry, unknown_state, palette)?;
+
+    //a single entry palette doesn't store any data
+    if palette.len() == 1 {
+        return Ok(match palette[0] {
+            AIR => None,
+            state => Some(Chunk::new_uniform(position, state)),
+        });
+    }
+
+    let indices = match block_states.get("data") {
+        Some(Value::LongArray(data)) => {
+            unpack_indices(data, palette.len(), SECTION_VOLUME, MIN_BLOCK_BITS)?
+        }
+        _ => return Err(AnvilError::InvalidField("data")),
+    };
+    if indices.iter().all(|index| palette[*index as usize] == AIR) {
+        return Ok(None);
+    }
+
+    let mut chunk = Chunk::new(position);
+    for (i, index) in indices.iter().enumerate() {
+        let state = palette[*index as usize];
+        if state != AIR {
+            let i = i as i32;
+            let pos = BlockPos::new(i % 16, i / 256, (i / 16) % 16); //vanilla use the y, z, x order
+            chunk.set_block(pos, state);
+        }
+    }
+    chunk.compact();
+    Ok(Some(chunk))
+}
+
+///pack palette indices like vanilla does, the reverse of unpack_indices
+#[cfg(test)]
+pub(super) fn pack_indices(indices: &[u16], palette_len: usize, min_bits: u32) -> Vec<i64> {
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(min_bits) as usize;
+    let per_long = 64 / bits;
+    indices
+        .chunks(per_long)
+        .map(|indices| {
+            let mut long = 0u64;
+            for (i, index) in indices.iter().enumerate() {
+                long |= (*index as u64) << (i * bits);
+            }
+            long as i64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn unpack() {
+        //5 entries need 4 bits, 17 entries need 5 bits and leave 4 bits of padding in each long
+        for palette_len in [5, 17, 300] {
+            let indices = (0..4096)
+                .map(|i| (i * 7 % palette_len) as u16)
+                .collect::<Vec<_>>();
+            let data = pack_indices(&indices, palette_len, MIN_BLOCK_BITS);
+            assert_eq!(
+                unpack_indices(&data, palette_len, SECTION_VOLUME, MIN_BLOCK_BITS).unwrap(),
+                indices
+            );
+        }
+
+        let data = pack_indices(&[3; 4096], 5, MIN_BLOCK_BITS);
+        assert!(unpack_indices(&data, 3, SECTION_VOLUME, MIN_BLOCK_BITS).is_err());
+        assert!(unpack_indices(&data[1..], 5, SECTION_VOLUME, MIN_BLOCK_BITS).is_err());
+    }
+}
@@ -0,0 +1,476 @@
+mod biomes;
+mod block_states;
+
+use crate::block_entity::BlockEntity;
+use crate::block_state::{BlockState, BlockStateRegistry};
+use crate::{Chunk, ChunkManager};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use ident::Ident;
+use math::positions::{BlockPos, ChunkPos};
+use math::IVec3;
+use nbt::{Compound, List, Value};
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+///a region file starts with the location table then the timestamp table, both are 1024 entries of 4 bytes
+const SECTOR_SIZE: usize = 4096;
+const REGION_SIDE: i32 = 32;
+const REGION_CHUNK_COUNT: usize = 1024;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+const COMPRESSION_NONE: u8 = 3;
+///set on the compression type when the chunk is stored in its own .mcc file, because it's too big for the region
+const EXTERNAL_CHUNK_FLAG: u8 = 128;
+
+#[derive(Debug, Error)]
+pub enum AnvilError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("the region file is truncated")]
+    TruncatedRegion,
+    #[error("the chunk {index} of the region points outside of the file")]
+    InvalidChunkLocation { index: usize },
+    #[error("unsupported chunk compression {0}")]
+    UnsupportedCompression(u8),
+    #[error(
+        "the chunk {index} of the region is stored in an external .mcc file, which isn't supported"
+    )]
+    ExternalChunk { index: usize },
+    #[error("invalid chunk nbt: {0}")]
+    Nbt(#[from] nbt::binary::Error),
+    #[error("the chunk nbt doesn't have a valid {0} field, only the 1.18+ format is supported")]
+    InvalidField(&'static str),
+    #[error("the palette index {0} is out of the palette")]
+    InvalidPaletteIndex(u16),
+    #[error("the block {0} isn't in the registry")]
+    UnknownBlock(String),
+    #[error("the chunk {index} of the region stores the column ({x}, {z}), which belongs to another place")]
+    UnexpectedColumnPosition { index: usize, x: i32, z: i32 },
+}
+
+///import the blocks, the biomes and the block entities of a Java Edition world saved in the anvil format (.mca region files), only the 1.18+ chunk format is supported
+///the block names are resolved with a BlockStateRegistry, so it should be loaded from the report of the same version as the world
+pub struct AnvilImporter<'a> {
+    registry: &'a BlockStateRegistry,
+    unknown_state: Option<BlockState>,
+}
+
+impl<'a> AnvilImporter<'a> {
+    pub fn new(registry: &'a BlockStateRegistry) -> Self {
+        Self {
+            registry,
+            unknown_state: None,
+        }
+    }
+
+    ///replace the blocks missing from the registry by the given state instead of failing the import
+    pub fn with_unknown_state(mut self, state: BlockState) -> Self {
+        self.unknown_state = Some(state);
+        self
+    }
+
+    ///convert the nbt of a vanilla chunk column into our chunks, a chunk per non-empty section
+    pub fn read_chunk_nbt(&self, nbt: &Compound) -> Result<Vec<Chunk>, AnvilError> {
+        let (x, z) = column_pos(nbt)?;
+        let sections = match nbt.get("sections") {
+            Some(Value::List(List::Compound(sections))) => sections.as_slice(),
+            Some(Value::List(List::End)) => &[],
+            _ => return Err(AnvilError::InvalidField("sections")),
+        };
+
+        let mut chunks = Vec::new();
+        for section in sections {
+            let y = section
+                .get("Y")
+                .and_then(Value::as_i32)
+                .ok_or(AnvilError::InvalidField("Y"))?;
+            //the sections above and below the world only store light
+            let Some(Value::Compound(block_states)) = section.get("block_states") else {
+                continue;
+            };
+            let position = ChunkPos::new(x, y, z);
+            if let Some(mut chunk) = block_states::read_section(
+                self.registry,
+                self.unknown_state,
+                position,
+                block_states,
+            )? {
+                if let Some(Value::Compound(section_biomes)) = section.get("biomes") {
+                    *chunk.biomes_mut() = biomes::read_biomes(section_biomes)?;
+                }
+                chunks.push(chunk);
+            }
+        }
+
+        let block_entities = match nbt.get("block_entities") {
+            Some(Value::List(List::Compound(block_entities))) => block_entities.as_slice(),
+            Some(Value::List(List::End)) | None => &[],
+            _ => return Err(AnvilError::InvalidField("block_entities")),
+        };
+        for block_entity in block_entities {
+            let (pos, block_entity) = read_block_entity(block_entity)?;
+            let chunk_pos = pos.div_euclid(IVec3::splat(Chunk::SIZE));
+            //a block entity in an air section has lost its block
+            if let Some(chunk) = chunks
+                .iter_mut()
+                .find(|chunk| chunk.position() == chunk_pos)
+            {
+                chunk.set_block_entity(pos.rem_euclid(IVec3::splat(Chunk::SIZE)), block_entity);
+            }
+        }
+        Ok(chunks)
+    }
+
+    ///read all the chunks of the content of a region file
+    ///the position of each column is checked against its slot in the region, but the region itself is unknown, see read_region_file
+    pub fn read_region(&self, data: &[u8]) -> Result<Vec<Chunk>, AnvilError> {
+        self.read_region_at(data, None)
+    }
+
+    ///same as read_region, the columns must also belong to the region at the given position, in regions of 32x32 columns
+    pub fn read_region_at(
+        &self,
+        data: &[u8],
+        region: Option<(i32, i32)>,
+    ) -> Result<Vec<Chunk>, AnvilError> {
+        if data.len() < SECTOR_SIZE * 2 {
+            return Err(AnvilError::TruncatedRegion);
+        }
+
+        let mut chunks = Vec::new();
+        for index in 0..REGION_CHUNK_COUNT {
+            let location = &data[index * 4..index * 4 + 4];
+            let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+            let sector_count = location[3] as usize;
+            if sector == 0 && sector_count == 0 {
+                continue; //the chunk was never generated
+            }
+
+            let start = sector * SECTOR_SIZE;
+            let Some(header) = data.get(start..start + 5) else {
+                return Err(AnvilError::InvalidChunkLocation { index });
+            };
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let compression = header[4];
+            if compression & EXTERNAL_CHUNK_FLAG != 0 {
+                return Err(AnvilError::ExternalChunk { index });
+            }
+            //the length includes the compression byte
+            let Some(payload) = length
+                .checked_sub(1)
+                .and_then(|length| data.get(start + 5..start + 5 + length))
+            else {
+                return Err(AnvilError::InvalidChunkLocation { index });
+            };
+
+            let nbt = decompress(compression, payload)?;
+            let (nbt, _) = nbt::from_binary::<String>(&mut nbt.as_slice())?;
+            //a column at the wrong place would replace another column
+            let (x, z) = column_pos(&nbt)?;
+            let slot = (x.rem_euclid(REGION_SIDE), z.rem_euclid(REGION_SIDE));
+            let in_region = region.is_none_or(|region| {
+                region == (x.div_euclid(REGION_SIDE), z.div_euclid(REGION_SIDE))
+            });
+            if slot.0 + slot.1 * REGION_SIDE != index as i32 || !in_region {
+                return Err(AnvilError::UnexpectedColumnPosition { index, x, z });
+            }
+            chunks.extend(self.read_chunk_nbt(&nbt)?);
+        }
+        Ok(chunks)
+    }
+
+    ///read all the chunks of a region file, the position of the region is read from the name of the file, like r.-1.2.mca
+    ///the region isn't checked if the file has another name
+    pub fn read_region_file(&self, path: impl AsRef<Path>) -> Result<Vec<Chunk>, AnvilError> {
+        let path = path.as_ref();
+        self.read_region_at(&std::fs::read(path)?, region_of_file(path))
+    }
+
+    ///insert all the chunks of a world in the ChunkManager, the directory is the "region" directory of the world
+    ///return the number of imported chunks
+    pub fn import_world(
+        &self,
+        region_directory: impl AsRef<Path>,
+        chunk_manager: &mut ChunkManager,
+    ) -> Result<usize, AnvilError> {
+        let mut imported = 0;
+        for entry in std::fs::read_dir(region_directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "mca") {
+                for chunk in self.read_region_file(&path)? {
+                    chunk_manager.insert_chunk(chunk);
+                    imported += 1;
+                }
+            }
+        }
+        Ok(imported)
+    }
+}
+
+///the position of a chunk column in chunks
+fn column_pos(nbt: &Compound) -> Result<(i32, i32), AnvilError> {
+    let x = nbt
+        .get("xPos")
+        .and_then(Value::as_i32)
+        .ok_or(AnvilError::InvalidField("xPos"))?;
+    let z = nbt
+        .get("zPos")
+        .and_then(Value::as_i32)
+        .ok_or(AnvilError::InvalidField("zPos"))?;
+    Ok((x, z))
+}
+
+///parse the position of the region from a file name like r.-1.2.mca
+fn region_of_file(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    match parts.next() {
+        None => Some((x, z)),
+        Some(_) => None,
+    }
+}
+
+///split the position and the id of a vanilla block entity from its data
+fn read_block_entity(block_entity: &Compound) -> Result<(BlockPos, BlockEntity), AnvilError> {
+    let mut coords = [0; 3];
+    for (coord, name) in coords.iter_mut().zip(["x", "y", "z"]) {
+        *coord = block_entity
+            .get(name)
+            .and_then(Value::as_i32)
+            .ok_or(AnvilError::InvalidField("block_entities"))?;
+    }
+    let kind = match block_entity.get("id") {
+        Some(Value::String(id)) => {
+            Ident::try_from(id.as_str()).map_err(|_| AnvilError::InvalidField("id"))?
+        }
+        _ => return Err(AnvilError::InvalidField("id")),
+    };
+    let mut data = block_entity.clone();
+    for key in ["x", "y", "z", "id", "keepPacked"] {
+        data.remove(key);
+    }
+    Ok((BlockPos::from_array(coords), BlockEntity::new(kind, data)))
+}
+
+fn decompress(compression: u8, payload: &[u8]) -> Result<Vec<u8>, AnvilError> {
+    let mut data = Vec::new();
+    match compression {
+        COMPRESSION_GZIP => GzDecoder::new(payload).read_to_end(&mut data)?,
+        COMPRESSION_ZLIB => ZlibDecoder::new(payload).read_to_end(&mut data)?,
+        COMPRESSION_NONE => return Ok(payload.to_vec()),
+        _ => return Err(AnvilError::UnsupportedCompression(compression)),
+    };
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::{Property, AIR};
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use ident::ident;
+    use nbt::compound;
+    use std::io::Write;
+
+    fn registry() -> BlockStateRegistry {
+        let mut registry = BlockStateRegistry::new();
+        registry
+            .register_block(ident!("minecraft:stone").into(), Vec::new())
+            .unwrap();
+        registry
+            .register_block(
+                ident!("minecraft:snow").into(),
+                vec![Property::int("layers", 1, 8)],
+            )
+            .unwrap();
+        registry
+    }
+
+    fn palette_entry(name: &str, properties: Option<Compound>) -> Compound {
+        let mut entry = compound! { "Name" => name };
+        if let Some(properties) = properties {
+            entry.insert("Properties", properties);
+        }
+        entry
+    }
+
+    ///a column with a stone section at y = -1, a section with a snow layer at y = 0 and an air section at y = 1
+    fn column_nbt(x: i32, z: i32) -> Compound {
+        let mut indices = vec![0u16; 4096];
+        indices[5 + 7 * 16 + 3 * 256] = 1; //x = 5, z = 7, y = 3
+        let snow = palette_entry("minecraft:snow", Some(compound! { "layers" => "3" }));
+        let mut biome_indices = vec![0u16; 64];
+        biome_indices[5] = 1; //the cell of the snow layer, x = 1, y = 0 and z = 1
+
+        compound! {
+            "DataVersion" => 3465,
+            "xPos" => x,
+            "zPos" => z,
+            "yPos" => -4,
+            "sections" => List::Compound(vec![
+                compound! {
+                    "Y" => -1i8,
+                    "block_states" => compound! {
+                        "palette" => List::Compound(vec![palette_entry("minecraft:stone", None)]),
+                    },
+                },
+                compound! {
+                    "Y" => 0i8,
+                    "block_states" => compound! {
+                        "palette" => List::Compound(vec![palette_entry("minecraft:air", None), snow]),
+                        "data" => block_states::pack_indices(&indices, 2, 4),
+                    },
+                    "biomes" => compound! {
+                        "palette" => List::String(vec!["minecraft:plains".into(), "minecraft:snowy_plains".into()]),
+                        "data" => block_states::pack_indices(&biome_indices, 2, 1),
+                    },
+                },
+                compound! {
+                    "Y" => 1i8,
+                    "block_states" => compound! {
+                        "palette" => List::Compound(vec![palette_entry("minecraft:air", None)]),
+                    },
+                },
+                compound! { "Y" => 2i8 },
+            ]),
+            "block_entities" => List::Compound(vec![compound! {
+                "id" => "minecraft:chest",
+                "x" => x * 16 + 3,
+                "y" => -5,
+                "z" => z * 16,
+                "keepPacked" => false,
+                "Lock" => "key",
+            }]),
+        }
+    }
+
+    ///build a region file with a chunk column per compression type, in the slots of the columns (0, 2), (1, 2) and (2, 2)
+    fn region() -> Vec<u8> {
+        region_with_z(2)
+    }
+
+    ///the columns are at z in their nbt, whatever their slot
+    fn region_with_z(z: i32) -> Vec<u8> {
+        let mut region = vec![0u8; SECTOR_SIZE * 2];
+        for (i, compression) in [COMPRESSION_GZIP, COMPRESSION_ZLIB, COMPRESSION_NONE]
+            .into_iter()
+            .enumerate()
+        {
+            let mut nbt = Vec::new();
+            nbt::to_binary(&column_nbt(i as i32, z), &mut nbt, "").unwrap();
+            let payload = match compression {
+                COMPRESSION_GZIP => {
+                    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(&nbt).unwrap();
+                    encoder.finish().unwrap()
+                }
+                COMPRESSION_ZLIB => {
+                    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(&nbt).unwrap();
+                    encoder.finish().unwrap()
+                }
+                _ => nbt,
+            };
+
+            let sector = region.len() / SECTOR_SIZE;
+            let index = i + 2 * 32; //the index of a column is x + z * 32
+            region[index * 4..index * 4 + 3].copy_from_slice(&(sector as u32).to_be_bytes()[1..]);
+            region[index * 4 + 3] = 1;
+            region.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+            region.push(compression);
+            region.extend_from_slice(&payload);
+            region.resize(region.len().div_ceil(SECTOR_SIZE) * SECTOR_SIZE, 0);
+        }
+        region
+    }
+
+    #[test]
+    pub fn import_region() {
+        let registry = registry();
+        let stone = registry.default_state("stone").unwrap();
+        let snow = registry.state_of("snow", &[("layers", "3")]).unwrap();
+
+        let chunks = AnvilImporter::new(&registry)
+            .read_region(&region())
+            .unwrap();
+        assert_eq!(chunks.len(), 3 * 2); //the air sections are skipped
+
+        let mut chunk_manager = ChunkManager::new();
+        for chunk in chunks {
+            chunk_manager.insert_chunk(chunk);
+        }
+        for x in 0..3 {
+            let chunk = chunk_manager.get_chunk(ChunkPos::new(x, -1, 2)).unwrap();
+            assert_eq!(chunk.get_block_at(9, 9, 9), stone);
+            let chest = chunk.get_block_entity(BlockPos::new(3, 11, 0)).unwrap();
+            assert_eq!(chest.kind, ident!("minecraft:chest"));
+            assert_eq!(chest.data, compound! { "Lock" => "key" });
+            let chunk = chunk_manager.get_chunk(ChunkPos::new(x, 0, 2)).unwrap();
+            assert_eq!(chunk.get_block_at(5, 3, 7), snow);
+            assert_eq!(chunk.get_block_at(7, 3, 5), AIR);
+            assert_eq!(
+                chunk.get_biome(BlockPos::new(5, 3, 7)).as_str(),
+                "minecraft:snowy_plains"
+            );
+            assert_eq!(
+                chunk.get_biome(BlockPos::new(0, 0, 0)).as_str(),
+                "minecraft:plains"
+            );
+        }
+    }
+
+    #[test]
+    pub fn unknown_blocks() {
+        let registry = BlockStateRegistry::new();
+        let data = region();
+        assert!(matches!(
+            AnvilImporter::new(&registry).read_region(&data),
+            Err(AnvilError::UnknownBlock(_))
+        ));
+
+        let chunks = AnvilImporter::new(&registry)
+            .with_unknown_state(BlockState::from_raw(1))
+            .read_region(&data)
+            .unwrap();
+        assert_eq!(chunks.len(), 3 * 2);
+
+        assert!(matches!(
+            AnvilImporter::new(&registry).read_region(&data[..SECTOR_SIZE * 2 + 10]),
+            Err(AnvilError::InvalidChunkLocation { .. })
+        ));
+    }
+
+    #[test]
+    pub fn reject_misplaced_columns() {
+        let registry = registry();
+        let importer = AnvilImporter::new(&registry);
+        assert!(matches!(
+            importer.read_region(&region_with_z(3)),
+            Err(AnvilError::UnexpectedColumnPosition {
+                index: 64,
+                x: 0,
+                z: 3
+            })
+        ));
+
+        //the right slot of another region
+        let data = region_with_z(2 + 32);
+        assert!(importer.read_region(&data).is_ok());
+        assert!(importer.read_region_at(&data, Some((0, 1))).is_ok());
+        assert!(matches!(
+            importer.read_region_at(&data, Some((0, 0))),
+            Err(AnvilError::UnexpectedColumnPosition { .. })
+        ));
+
+        assert_eq!(
+            region_of_file(Path::new("region/r.-1.2.mca")),
+            Some((-1, 2))
+        );
+        assert_eq!(region_of_file(Path::new("r.1.2.3.mca")), None);
+        assert_eq!(region_of_file(Path::new("r.1.2.mcc")), None);
+    }
+}
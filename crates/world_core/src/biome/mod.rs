@@ -0,0 +1,239 @@
+use crate::Chunk;
+use ident::{ident, Ident};
+use math::positions::BlockPos;
+use math::IVec3;
+
+///the identifier of a biome, like minecraft:plains
+pub type Biome = Ident<String>;
+
+///the biome of a chunk that has never been given one
+pub const DEFAULT_BIOME: Ident<&str> = ident!("minecraft:plains");
+
+///the side of the cubes sharing the same biome, like vanilla
+pub const BIOME_CELL_SIZE: i32 = 4;
+///the number of cells along a side of a chunk
+pub const BIOME_CELL_SIDE: i32 = Chunk::SIZE / BIOME_CELL_SIZE;
+pub const BIOME_CELL_COUNT: usize = (BIOME_CELL_SIDE * BIOME_CELL_SIDE * BIOME_CELL_SIDE) as usize;
+
+///the longest biome identifier a chunk can store, so the identifier length fits in a byte in the chunk format
+pub const MAX_BIOME_LEN: usize = u8::MAX as usize;
+
+///the biomes of a chunk at the resolution of a 4x4x4 cell, like vanilla
+///the palette only contains the biomes in use, a chunk with a single biome doesn't allocate the indices
+#[derive(Clone)]
+pub struct ChunkBiomes {
+    palette: Vec<Biome>,                          //never empty
+    indices: Option<Box<[u8; BIOME_CELL_COUNT]>>, //the palette index of each cell, None when the palette has a single entry
+}
+
+impl Default for ChunkBiomes {
+    fn default() -> Self {
+        Self::new_uniform(DEFAULT_BIOME.into())
+    }
+}
+
+impl ChunkBiomes {
+    pub fn new_uniform(biome: Biome) -> Self {
+        check_len(&biome);
+        Self {
+            palette: vec![biome],
+            indices: None,
+        }
+    }
+
+    ///the index of a cell in the y, z, x order of vanilla, so a vanilla palette can be read and written without conversion
+    pub fn cell_index(cell: IVec3) -> usize {
+        debug_assert!(
+            cell.cmpge(IVec3::ZERO).all() && cell.cmplt(IVec3::splat(BIOME_CELL_SIDE)).all()
+        );
+        (cell.x + cell.z * BIOME_CELL_SIDE + cell.y * BIOME_CELL_SIDE * BIOME_CELL_SIDE) as usize
+    }
+
+    ///the cell containing the block at the given position in the chunk
+    pub fn cell_of(pos: BlockPos) -> IVec3 {
+        pos / BIOME_CELL_SIZE
+    }
+
+    ///the position in the chunk of the block at the corner of the cell with the lowest coordinates
+    pub fn cell_origin(index: usize) -> BlockPos {
+        let index = index as i32;
+        IVec3::new(
+            index % BIOME_CELL_SIDE,
+            index / (BIOME_CELL_SIDE * BIOME_CELL_SIDE),
+            (index / BIOME_CELL_SIDE) % BIOME_CELL_SIDE,
+        ) * BIOME_CELL_SIZE
+    }
+
+    ///get the biome of the block at the given position in the chunk
+    pub fn get(&self, pos: BlockPos) -> &Biome {
+        self.get_cell(Self::cell_index(Self::cell_of(pos)))
+    }
+
+    pub fn get_cell(&self, index: usize) -> &Biome {
+        match &self.indices {
+            Some(indices) => &self.palette[indices[index] as usize],
+            None => &self.palette[0],
+        }
+    }
+
+    ///set the biome of the whole cell containing the block at the given position in the chunk, return true if it changed
+    pub fn set(&mut self, pos: BlockPos, biome: Biome) -> bool {
+        self.set_cell(Self::cell_index(Self::cell_of(pos)), biome)
+    }
+
+    ///return true if the biome of the cell changed
+    ///panic if the identifier is longer than MAX_BIOME_LEN
+    pub fn set_cell(&mut self, index: usize, biome: Biome) -> bool {
+        check_len(&biome);
+        if *self.get_cell(index) == biome {
+            return false;
+        }
+        let palette_index = match self.palette.iter().position(|entry| *entry == biome) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(biome);
+                self.palette.len() - 1
+            }
+        };
+        let indices = self
+            .indices
+            .get_or_insert_with(|| Box::new([0; BIOME_CELL_COUNT]));
+        let old_index = std::mem::replace(&mut indices[index], palette_index as u8);
+
+        //the palette only keeps the biomes in use, the last entry takes the place of the unused one
+        if !indices.contains(&old_index) {
+            let last = self.palette.len() as u8 - 1;
+            self.palette.swap_remove(old_index as usize);
+            for entry in indices.iter_mut().filter(|entry| **entry == last) {
+                *entry = old_index;
+            }
+        }
+        if self.palette.len() == 1 {
+            self.indices = None;
+        }
+        true
+    }
+
+    ///set every cell to the given biome and free the indices
+    pub fn fill(&mut self, biome: Biome) {
+        *self = Self::new_uniform(biome);
+    }
+
+    ///set the biome of every cell from the position in the chunk of its origin, see cell_origin
+    ///meant for the generators, they usually sample their biome noise at the origin of the cells
+    pub fn fill_with(&mut self, mut func: impl FnMut(BlockPos) -> Biome) {
+        self.fill(func(Self::cell_origin(0)));
+        for index in 1..BIOME_CELL_COUNT {
+            self.set_cell(index, func(Self::cell_origin(index)));
+        }
+    }
+
+    ///the biomes in use, the palette indices point in this slice
+    pub fn palette(&self) -> &[Biome] {
+        &self.palette
+    }
+
+    ///the palette index of each cell in the y, z, x order, None if the palette has a single entry
+    pub fn palette_indices(&self) -> Option<&[u8; BIOME_CELL_COUNT]> {
+        self.indices.as_deref()
+    }
+
+    ///build the biomes from a palette and the palette index of each cell, the indices are ignored for a single entry palette
+    ///return None if the palette is empty or has duplicates, if an index is out of the palette or if an entry isn't used
+    pub fn from_palette(
+        palette: Vec<Biome>,
+        indices: Option<&[u8; BIOME_CELL_COUNT]>,
+    ) -> Option<Self> {
+        if palette.is_empty()
+            || palette.len() > BIOME_CELL_COUNT
+            || palette
+                .iter()
+                .any(|biome| biome.as_str().len() > MAX_BIOME_LEN)
+        {
+            return None;
+        }
+        if palette
+            .iter()
+            .enumerate()
+            .any(|(i, biome)| palette[..i].contains(biome))
+        {
+            return None;
+        }
+        if palette.len() == 1 {
+            return Some(Self::new_uniform(palette.into_iter().next().unwrap()));
+        }
+
+        let indices = indices?;
+        let mut used = vec![false; palette.len()];
+        for index in indices {
+            *used.get_mut(*index as usize)? = true;
+        }
+        if used.contains(&false) {
+            return None;
+        }
+        Some(Self {
+            palette,
+            indices: Some(Box::new(*indices)),
+        })
+    }
+
+    ///return the biome of every cell if the chunk has a single biome
+    pub fn uniform_biome(&self) -> Option<&Biome> {
+        match self.indices {
+            Some(_) => None,
+            None => Some(&self.palette[0]),
+        }
+    }
+
+    ///an estimation of the memory used by the biomes in bytes
+    pub fn memory_size(&self) -> usize {
+        let names: usize = self.palette.iter().map(|biome| biome.as_str().len()).sum();
+        let indices = self.indices.as_ref().map_or(0, |_| BIOME_CELL_COUNT);
+        self.palette.len() * std::mem::size_of::<Biome>() + names + indices
+    }
+}
+
+fn check_len(biome: &Biome) {
+    assert!(
+        biome.as_str().len() <= MAX_BIOME_LEN,
+        "the biome identifier {biome} is too long"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn palette() {
+        let desert: Biome = ident!("minecraft:desert").into();
+        let ocean: Biome = ident!("minecraft:ocean").into();
+        let mut biomes = ChunkBiomes::default();
+        assert_eq!(biomes.uniform_biome(), Some(&DEFAULT_BIOME.into()));
+
+        assert!(biomes.set(BlockPos::new(5, 0, 0), desert.clone()));
+        assert!(!biomes.set(BlockPos::new(7, 3, 3), desert.clone())); //same cell
+        assert!(biomes.set(BlockPos::new(15, 15, 15), ocean.clone()));
+        assert_eq!(*biomes.get(BlockPos::new(4, 1, 2)), desert);
+        assert_eq!(*biomes.get(BlockPos::new(3, 1, 2)), DEFAULT_BIOME);
+        assert_eq!(*biomes.get(BlockPos::new(12, 12, 12)), ocean);
+        assert_eq!(biomes.palette().len(), 3);
+
+        //the entries that aren't used anymore leave the palette
+        biomes.set(BlockPos::new(4, 0, 0), DEFAULT_BIOME.into());
+        assert_eq!(biomes.palette().len(), 2);
+        assert_eq!(*biomes.get(BlockPos::new(12, 12, 12)), ocean);
+        biomes.set(BlockPos::new(12, 12, 12), DEFAULT_BIOME.into());
+        assert_eq!(biomes.uniform_biome(), Some(&DEFAULT_BIOME.into()));
+
+        biomes.fill_with(|origin| match origin.y < 8 {
+            true => ocean.clone(),
+            false => desert.clone(),
+        });
+        assert_eq!(*biomes.get(BlockPos::new(15, 7, 0)), ocean);
+        assert_eq!(*biomes.get(BlockPos::new(0, 8, 15)), desert);
+        let copy =
+            ChunkBiomes::from_palette(biomes.palette().to_vec(), biomes.palette_indices()).unwrap();
+        assert_eq!(*copy.get(BlockPos::new(0, 8, 15)), desert);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::chunk::{block_index, block_pos_of};
+use ident::Ident;
+use math::positions::BlockPos;
+use nbt::Compound;
+use std::collections::BTreeMap;
+
+///the extra data of a block that a blockState can't hold, like the items of a chest or the text of a sign
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEntity {
+    ///the type of the block entity, like minecraft:chest
+    pub kind: Ident<String>,
+    ///every field but the position and the type, like the block entity nbt of vanilla
+    pub data: Compound,
+}
+
+impl BlockEntity {
+    pub fn new(kind: Ident<String>, data: Compound) -> Self {
+        Self { kind, data }
+    }
+}
+
+///the block entities of a chunk by position, most chunks don't have any so nothing is allocated for them
+///a block entity is removed when its block is replaced, see Chunk::set_block
+#[derive(Clone, Default)]
+pub struct ChunkBlockEntities {
+    entries: BTreeMap<u16, BlockEntity>, //by block index, in the x, y, z order of Chunk::blocks
+}
+
+impl ChunkBlockEntities {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///get the block entity at the given position in the chunk
+    pub fn get(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.entries.get(&(block_index(pos) as u16))
+    }
+
+    pub fn get_mut(&mut self, pos: BlockPos) -> Option<&mut BlockEntity> {
+        self.entries.get_mut(&(block_index(pos) as u16))
+    }
+
+    ///return the block entity previously at the position
+    pub fn insert(&mut self, pos: BlockPos, block_entity: BlockEntity) -> Option<BlockEntity> {
+        self.entries.insert(block_index(pos) as u16, block_entity)
+    }
+
+    pub fn remove(&mut self, pos: BlockPos) -> Option<BlockEntity> {
+        self.entries.remove(&(block_index(pos) as u16))
+    }
+
+    ///keep only the block entities for which the predicate returns true
+    pub fn retain(&mut self, mut predicate: impl FnMut(BlockPos, &BlockEntity) -> bool) {
+        self.entries
+            .retain(|index, block_entity| predicate(block_pos_of(*index as usize), block_entity));
+    }
+
+    ///iterate over the block entities and their position in the chunk, in the x, y, z order
+    pub fn iter(&self) -> impl Iterator<Item = (BlockPos, &BlockEntity)> {
+        self.entries
+            .iter()
+            .map(|(index, block_entity)| (block_pos_of(*index as usize), block_entity))
+    }
+
+    ///a rough estimation of the memory used in bytes, the size of the nbt isn't counted
+    pub fn memory_size(&self) -> usize {
+        self.entries.len() * std::mem::size_of::<(u16, BlockEntity)>()
+    }
+}
@@ -1,4 +0,0 @@
-//TODO: make a proper block state system
-
-pub type BlockState = u16;
-pub const AIR: BlockState = 0;
@@ -0,0 +1,24 @@
+mod property;
+mod registry;
+mod vanilla_report;
+
+pub use property::{Property, PropertyKind, PropertyValue};
+pub use registry::{Block, BlockStateRegistry, RegistryError};
+
+///a dense identifier of a block with all its properties, the meaning of the id is given by a BlockStateRegistry
+///the ids of a block are contiguous, so a chunk palette can store them without knowing the registry
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockState(u16);
+
+impl BlockState {
+    pub const fn from_raw(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+}
+
+///air is always the state 0, every registry must respect this
+pub const AIR: BlockState = BlockState(0);
@@ -0,0 +1,142 @@
+use std::fmt::{Display, Formatter};
+
+///the set of values a property can take, the index of a value is its position in the set
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropertyKind {
+    ///true then false, like vanilla
+    Bool,
+    ///all the integers from min to max, both included
+    Int { min: u8, max: u8 },
+    ///a list of named values, like the facing or the half of a stair
+    Enum(Vec<String>),
+}
+
+///a typed value of a property
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PropertyValue<'a> {
+    Bool(bool),
+    Int(u8),
+    Enum(&'a str),
+}
+
+impl Display for PropertyValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyValue::Bool(value) => write!(f, "{}", value),
+            PropertyValue::Int(value) => write!(f, "{}", value),
+            PropertyValue::Enum(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+///a named property of a block, like "facing", "waterlogged" or "age"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+impl Property {
+    pub fn new(name: impl Into<String>, kind: PropertyKind) -> Self {
+        if let PropertyKind::Int { min, max } = kind {
+            assert!(min <= max, "an int property needs at least one value");
+        }
+        Self {
+            name: name.into(),
+            kind,
+        }
+    }
+
+    pub fn bool(name: impl Into<String>) -> Self {
+        Self::new(name, PropertyKind::Bool)
+    }
+
+    pub fn int(name: impl Into<String>, min: u8, max: u8) -> Self {
+        Self::new(name, PropertyKind::Int { min, max })
+    }
+
+    pub fn enumeration(name: impl Into<String>, values: &[&str]) -> Self {
+        let values = values.iter().map(|value| value.to_string()).collect();
+        Self::new(name, PropertyKind::Enum(values))
+    }
+
+    ///guess the kind of the property from the textual values, like they appear in the vanilla reports
+    ///return None if there is no value
+    pub fn from_values(name: impl Into<String>, values: Vec<String>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        if values == ["true", "false"] {
+            return Some(Self::bool(name));
+        }
+
+        let ints = values
+            .iter()
+            .map(|value| value.parse::<u8>())
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok(ints) = ints {
+            let is_range = ints
+                .windows(2)
+                .all(|pair| pair[0].checked_add(1) == Some(pair[1]));
+            if is_range {
+                return Some(Self::int(name, ints[0], ints[ints.len() - 1]));
+            }
+        }
+
+        Some(Self::new(name, PropertyKind::Enum(values)))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &PropertyKind {
+        &self.kind
+    }
+
+    ///the number of values this property can take
+    pub fn value_count(&self) -> usize {
+        match &self.kind {
+            PropertyKind::Bool => 2,
+            PropertyKind::Int { min, max } => (max - min) as usize + 1,
+            PropertyKind::Enum(values) => values.len(),
+        }
+    }
+
+    ///get the value at the given index
+    pub fn value(&self, index: usize) -> Option<PropertyValue<'_>> {
+        if index >= self.value_count() {
+            return None;
+        }
+        let value = match &self.kind {
+            PropertyKind::Bool => PropertyValue::Bool(index == 0),
+            PropertyKind::Int { min, .. } => PropertyValue::Int(min + index as u8),
+            PropertyKind::Enum(values) => PropertyValue::Enum(&values[index]),
+        };
+        Some(value)
+    }
+
+    ///get the index of a typed value, return None if the value doesn't belong to this property
+    pub fn index_of(&self, value: PropertyValue) -> Option<usize> {
+        match (&self.kind, value) {
+            (PropertyKind::Bool, PropertyValue::Bool(value)) => Some(if value { 0 } else { 1 }),
+            (PropertyKind::Int { min, max }, PropertyValue::Int(value)) => (*min..=*max)
+                .contains(&value)
+                .then(|| (value - min) as usize),
+            (PropertyKind::Enum(values), PropertyValue::Enum(value)) => {
+                values.iter().position(|candidate| candidate == value)
+            }
+            _ => None,
+        }
+    }
+
+    ///get the index of a value written as text, like "true", "3" or "north"
+    pub fn index_of_str(&self, value: &str) -> Option<usize> {
+        match &self.kind {
+            PropertyKind::Bool => self.index_of(PropertyValue::Bool(value.parse().ok()?)),
+            PropertyKind::Int { .. } => self.index_of(PropertyValue::Int(value.parse().ok()?)),
+            PropertyKind::Enum(_) => self.index_of(PropertyValue::Enum(value)),
+        }
+    }
+}
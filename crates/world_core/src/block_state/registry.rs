@@ -0,0 +1,318 @@
+use crate::block_state::{BlockState, Property, PropertyValue, AIR};
+use ident::{ident, Ident, IdentError};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("block {0} is already registered")]
+    DuplicateBlock(String),
+    #[error("property {property} of block {block} doesn't have any value")]
+    EmptyProperty { block: String, property: String },
+    #[error("too many block states, the ids must fit in an u16")]
+    TooManyStates,
+    #[error(transparent)]
+    InvalidIdent(#[from] IdentError),
+    #[error("invalid block report: {0}")]
+    InvalidReport(#[from] serde_json::Error),
+    #[error("block {block} has the state {found}, but the registry expected {expected}")]
+    UnexpectedStateId {
+        block: String,
+        found: u16,
+        expected: u16,
+    },
+    #[error("the state 0 must be minecraft:air")]
+    AirIsNotZero,
+}
+
+///a block and all its properties, its states are the contiguous ids [first_state, first_state + state_count[
+///the id of a state is computed from the index of its property values, the last property being the fastest varying one (like vanilla)
+#[derive(Debug)]
+pub struct Block {
+    ident: Ident<String>,
+    properties: Vec<Property>,
+    first_state: u16,
+    state_count: u16,
+    default_state: BlockState,
+}
+
+impl Block {
+    pub fn ident(&self) -> &Ident<String> {
+        &self.ident
+    }
+
+    pub fn properties(&self) -> &[Property] {
+        &self.properties
+    }
+
+    pub fn default_state(&self) -> BlockState {
+        self.default_state
+    }
+
+    pub fn state_count(&self) -> u16 {
+        self.state_count
+    }
+
+    ///iterate over all the states of the block, in id order
+    pub fn states(&self) -> impl Iterator<Item = BlockState> {
+        (self.first_state..self.first_state + self.state_count).map(BlockState::from_raw)
+    }
+
+    ///return true if the state belongs to this block
+    pub fn contains(&self, state: BlockState) -> bool {
+        (self.first_state..self.first_state + self.state_count).contains(&state.raw())
+    }
+
+    fn property_index(&self, name: &str) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| property.name() == name)
+    }
+
+    ///the distance in id between two consecutive values of a property
+    fn stride(&self, property_index: usize) -> u16 {
+        self.properties[property_index + 1..]
+            .iter()
+            .map(|property| property.value_count() as u16)
+            .product()
+    }
+
+    fn value_index(&self, state: BlockState, property_index: usize) -> usize {
+        debug_assert!(self.contains(state));
+        let offset = state.raw() - self.first_state;
+        let count = self.properties[property_index].value_count() as u16;
+        ((offset / self.stride(property_index)) % count) as usize
+    }
+
+    fn with_value_index(
+        &self,
+        state: BlockState,
+        property_index: usize,
+        index: usize,
+    ) -> BlockState {
+        let old_index = self.value_index(state, property_index) as u16;
+        let stride = self.stride(property_index);
+        BlockState::from_raw(state.raw() - old_index * stride + index as u16 * stride)
+    }
+
+    ///get the value of a property for the given state, return None if the state doesn't belong to this block or the property doesn't exist
+    pub fn property_value(&self, state: BlockState, name: &str) -> Option<PropertyValue<'_>> {
+        if !self.contains(state) {
+            return None;
+        }
+        let property_index = self.property_index(name)?;
+        self.properties[property_index].value(self.value_index(state, property_index))
+    }
+
+    ///iterate over the name and the value of all the properties of a state, the state must belong to this block
+    pub fn property_values(
+        &self,
+        state: BlockState,
+    ) -> impl Iterator<Item = (&str, PropertyValue<'_>)> {
+        assert!(
+            self.contains(state),
+            "the state doesn't belong to this block"
+        );
+        self.properties
+            .iter()
+            .enumerate()
+            .map(move |(i, property)| {
+                let value = property.value(self.value_index(state, i)).unwrap();
+                (property.name(), value)
+            })
+    }
+
+    ///return the state with one property changed, the value is given as text
+    pub fn with_property(&self, state: BlockState, name: &str, value: &str) -> Option<BlockState> {
+        if !self.contains(state) {
+            return None;
+        }
+        let property_index = self.property_index(name)?;
+        let index = self.properties[property_index].index_of_str(value)?;
+        Some(self.with_value_index(state, property_index, index))
+    }
+
+    ///return the state with one property changed
+    pub fn with_property_value(
+        &self,
+        state: BlockState,
+        name: &str,
+        value: PropertyValue,
+    ) -> Option<BlockState> {
+        if !self.contains(state) {
+            return None;
+        }
+        let property_index = self.property_index(name)?;
+        let index = self.properties[property_index].index_of(value)?;
+        Some(self.with_value_index(state, property_index, index))
+    }
+
+    ///build a state from the default state and a list of textual (name, value), the missing properties keep their default value
+    pub fn state_with(&self, values: &[(&str, &str)]) -> Option<BlockState> {
+        values
+            .iter()
+            .try_fold(self.default_state, |state, (name, value)| {
+                self.with_property(state, name, value)
+            })
+    }
+}
+
+///the list of all known blocks and their states, the states are numbered densely from 0 (air) in registration order
+pub struct BlockStateRegistry {
+    blocks: Vec<Block>, //sorted by first_state, because the states are allocated in registration order
+    block_by_ident: HashMap<Ident<String>, usize>,
+    state_count: u32, //an u32 to detect the overflow
+}
+
+impl BlockStateRegistry {
+    ///create a registry containing only minecraft:air
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register_block(ident!("minecraft:air").into(), Vec::new())
+            .unwrap();
+        registry
+    }
+
+    pub(super) fn empty() -> Self {
+        Self {
+            blocks: Vec::new(),
+            block_by_ident: HashMap::new(),
+            state_count: 0,
+        }
+    }
+
+    ///allocate the states of a new block, its default state is the first one
+    pub fn register_block(
+        &mut self,
+        ident: Ident<String>,
+        properties: Vec<Property>,
+    ) -> Result<&Block, RegistryError> {
+        if self.block_by_ident.contains_key(&ident) {
+            return Err(RegistryError::DuplicateBlock(ident.to_string()));
+        }
+        if let Some(property) = properties.iter().find(|p| p.value_count() == 0) {
+            return Err(RegistryError::EmptyProperty {
+                block: ident.to_string(),
+                property: property.name().to_string(),
+            });
+        }
+
+        let state_count = properties
+            .iter()
+            .try_fold(1u32, |count, property| {
+                count.checked_mul(property.value_count() as u32)
+            })
+            .filter(|count| self.state_count + count <= u16::MAX as u32)
+            .ok_or(RegistryError::TooManyStates)?;
+
+        let first_state = self.state_count as u16;
+        self.state_count += state_count;
+
+        let index = self.blocks.len();
+        self.block_by_ident.insert(ident.clone(), index);
+        self.blocks.push(Block {
+            ident,
+            properties,
+            first_state,
+            state_count: state_count as u16,
+            default_state: BlockState::from_raw(first_state),
+        });
+        Ok(&self.blocks[index])
+    }
+
+    ///change the default state of a block, return false if the state doesn't belong to the block
+    pub fn set_default_state(&mut self, ident: &str, state: BlockState) -> bool {
+        let Some(index) = self.block_by_ident.get(ident) else {
+            return false;
+        };
+        let block = &mut self.blocks[*index];
+        if !block.contains(state) {
+            return false;
+        }
+        block.default_state = state;
+        true
+    }
+
+    ///the number of states in the registry, all the state ids are lower than this value
+    pub fn state_count(&self) -> u32 {
+        self.state_count
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+
+    ///get a block by its identifier, the namespace can be omitted for minecraft blocks
+    pub fn block(&self, ident: &str) -> Option<&Block> {
+        let index = match self.block_by_ident.get(ident) {
+            Some(index) => *index,
+            None if !ident.contains(':') => *self
+                .block_by_ident
+                .get(format!("minecraft:{ident}").as_str())?,
+            None => return None,
+        };
+        Some(&self.blocks[index])
+    }
+
+    ///get the block a state belongs to
+    pub fn block_of(&self, state: BlockState) -> Option<&Block> {
+        let index = self
+            .blocks
+            .partition_point(|block| block.first_state <= state.raw());
+        let block = self.blocks.get(index.checked_sub(1)?)?;
+        block.contains(state).then_some(block)
+    }
+
+    pub fn default_state(&self, ident: &str) -> Option<BlockState> {
+        self.block(ident).map(Block::default_state)
+    }
+
+    ///build a state from a block identifier and a list of textual (name, value), the missing properties keep their default value
+    pub fn state_of(&self, ident: &str, values: &[(&str, &str)]) -> Option<BlockState> {
+        self.block(ident)?.state_with(values)
+    }
+
+    ///parse a state written like "minecraft:oak_stairs[facing=east,half=top]"
+    pub fn parse_state(&self, text: &str) -> Option<BlockState> {
+        let (ident, properties) = match text.split_once('[') {
+            Some((ident, properties)) => (ident, properties.strip_suffix(']')?),
+            None => (text, ""),
+        };
+
+        let values = properties
+            .split(',')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').map(|(k, v)| (k.trim(), v.trim())))
+            .collect::<Option<Vec<_>>>()?;
+        self.state_of(ident.trim(), &values)
+    }
+
+    ///write a state like "minecraft:oak_stairs[facing=east,half=top]", the reverse of parse_state
+    pub fn format_state(&self, state: BlockState) -> Option<String> {
+        let block = self.block_of(state)?;
+        let mut text = block.ident().to_string();
+        if !block.properties().is_empty() {
+            let properties = block
+                .property_values(state)
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>();
+            text = format!("{text}[{}]", properties.join(","));
+        }
+        Some(text)
+    }
+
+    ///check the invariant every chunk relies on
+    pub(super) fn check_air(&self) -> Result<(), RegistryError> {
+        match self.block_of(AIR) {
+            Some(block) if block.ident().as_str() == "minecraft:air" => Ok(()),
+            _ => Err(RegistryError::AirIsNotZero),
+        }
+    }
+}
+
+impl Default for BlockStateRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
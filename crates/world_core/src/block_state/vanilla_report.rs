@@ -0,0 +1,169 @@
+use crate::block_state::{BlockState, BlockStateRegistry, Property, RegistryError};
+use ident::Ident;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+///a block of the blocks.json report generated by the vanilla server with `--reports`
+///the properties are read in alphabetical order, which is the order vanilla uses to number the states
+#[derive(Deserialize)]
+struct ReportBlock {
+    #[serde(default)]
+    properties: BTreeMap<String, Vec<String>>,
+    states: Vec<ReportState>,
+}
+
+#[derive(Deserialize)]
+struct ReportState {
+    id: u16,
+    #[serde(default)]
+    default: bool,
+    #[serde(default)]
+    properties: BTreeMap<String, String>,
+}
+
+impl BlockStateRegistry {
+    ///load a registry from the vanilla blocks.json report, the state ids of the report are kept
+    pub fn from_vanilla_report(json: &str) -> Result<Self, RegistryError> {
+        let report: HashMap<String, ReportBlock> = serde_json::from_str(json)?;
+
+        //the json object isn't ordered, the blocks are sorted back by state id
+        let mut report = report
+            .into_iter()
+            .map(|(ident, block)| {
+                let first_id = block.states.iter().map(|state| state.id).min();
+                (first_id.unwrap_or(u16::MAX), ident, block)
+            })
+            .collect::<Vec<_>>();
+        report.sort_by_key(|(first_id, _, _)| *first_id);
+
+        let mut registry = Self::empty();
+        for (first_id, ident, block) in report {
+            let ident: Ident<String> = Ident::new(ident)?.into();
+            let expected = registry.state_count() as u16;
+            if first_id != expected {
+                return Err(RegistryError::UnexpectedStateId {
+                    block: ident.to_string(),
+                    found: first_id,
+                    expected,
+                });
+            }
+
+            let mut properties = Vec::with_capacity(block.properties.len());
+            for (name, values) in block.properties {
+                let property = Property::from_values(name.clone(), values).ok_or_else(|| {
+                    RegistryError::EmptyProperty {
+                        block: ident.to_string(),
+                        property: name,
+                    }
+                })?;
+                properties.push(property);
+            }
+
+            let registered = registry.register_block(ident.clone(), properties)?;
+            if registered.state_count() as usize != block.states.len() {
+                return Err(RegistryError::UnexpectedStateId {
+                    block: ident.to_string(),
+                    found: first_id + block.states.len() as u16,
+                    expected: first_id + registered.state_count(),
+                });
+            }
+
+            //make sure our numbering is the same as the vanilla one
+            let mut default_state = None;
+            for state in &block.states {
+                let values = state
+                    .properties
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str()))
+                    .collect::<Vec<_>>();
+                let computed = registered.state_with(&values);
+                if computed != Some(BlockState::from_raw(state.id)) {
+                    return Err(RegistryError::UnexpectedStateId {
+                        block: ident.to_string(),
+                        found: state.id,
+                        expected: computed.map_or(u16::MAX, BlockState::raw),
+                    });
+                }
+                if state.default {
+                    default_state = computed;
+                }
+            }
+
+            if let Some(default_state) = default_state {
+                registry.set_default_state(ident.as_str(), default_state);
+            }
+        }
+
+        registry.check_air()?;
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block_state::{BlockState, BlockStateRegistry, PropertyValue, AIR};
+
+    const REPORT: &str = r#"{
+        "minecraft:stone": { "states": [{ "id": 1, "default": true }] },
+        "minecraft:air": { "states": [{ "id": 0, "default": true }] },
+        "minecraft:lantern": {
+            "properties": { "waterlogged": ["true", "false"], "hanging": ["true", "false"] },
+            "states": [
+                { "id": 2, "properties": { "hanging": "true", "waterlogged": "true" } },
+                { "id": 3, "properties": { "hanging": "true", "waterlogged": "false" } },
+                { "id": 4, "properties": { "hanging": "false", "waterlogged": "true" } },
+                { "id": 5, "default": true, "properties": { "hanging": "false", "waterlogged": "false" } }
+            ]
+        },
+        "minecraft:wheat": {
+            "properties": { "age": ["0", "1", "2", "3", "4", "5", "6", "7"] },
+            "states": [
+                { "id": 6, "default": true, "properties": { "age": "0" } },
+                { "id": 7, "properties": { "age": "1" } },
+                { "id": 8, "properties": { "age": "2" } },
+                { "id": 9, "properties": { "age": "3" } },
+                { "id": 10, "properties": { "age": "4" } },
+                { "id": 11, "properties": { "age": "5" } },
+                { "id": 12, "properties": { "age": "6" } },
+                { "id": 13, "properties": { "age": "7" } }
+            ]
+        }
+    }"#;
+
+    #[test]
+    pub fn load_report() {
+        let registry = BlockStateRegistry::from_vanilla_report(REPORT).unwrap();
+        assert_eq!(registry.state_count(), 14);
+        assert_eq!(registry.default_state("air"), Some(AIR));
+
+        let lantern = registry.block("minecraft:lantern").unwrap();
+        assert_eq!(lantern.default_state(), BlockState::from_raw(5));
+        assert_eq!(
+            lantern.property_value(BlockState::from_raw(4), "hanging"),
+            Some(PropertyValue::Bool(false))
+        );
+        assert_eq!(
+            registry
+                .block_of(BlockState::from_raw(3))
+                .unwrap()
+                .ident()
+                .as_str(),
+            "minecraft:lantern"
+        );
+
+        let wheat = registry.parse_state("wheat[age=5]").unwrap();
+        assert_eq!(wheat, BlockState::from_raw(11));
+        assert_eq!(
+            registry.format_state(wheat).unwrap(),
+            "minecraft:wheat[age=5]"
+        );
+        assert!(registry.parse_state("wheat[age=8]").is_none());
+        assert!(registry.block_of(BlockState::from_raw(14)).is_none());
+    }
+
+    #[test]
+    pub fn reject_wrong_ids() {
+        let report = REPORT.replace(r#""id": 7"#, r#""id": 8"#);
+        assert!(BlockStateRegistry::from_vanilla_report(&report).is_err());
+    }
+}
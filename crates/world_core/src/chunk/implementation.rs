@@ -1,6 +1,7 @@
 use crate::block_state::{BlockState, AIR};
 use crate::chunk::BlockPos;
 use math::consts::CHUNK_SIZE;
+use std::collections::HashSet;
 
 ///a common interface for all types of world_core in memory
 pub trait InMemoryChunk {
@@ -14,6 +15,7 @@ const AVAILABLE_PALETTE_ENTRY: BlockState = AIR;
 
 ///stores blockStates without any compression. There is no limit of blockState Variants.
 ///use 8192 bytes of memory
+#[derive(Clone)]
 pub struct ChunkNative {
     blocks: [BlockState; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
 }
@@ -24,6 +26,32 @@ impl ChunkNative {
             blocks: [AVAILABLE_PALETTE_ENTRY; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
         }
     }
+
+    ///count the different non-air blockStates of the chunk, stop counting after max_count to save time
+    pub fn distinct_state_count(&self, max_count: usize) -> usize {
+        let mut states = HashSet::new();
+        for state in self.blocks.iter() {
+            if *state != AIR && states.insert(*state) && states.len() > max_count {
+                break;
+            }
+        }
+        states.len()
+    }
+
+    pub fn non_air_block_count(&self) -> usize {
+        self.blocks.iter().filter(|state| **state != AIR).count()
+    }
+
+    pub fn blocks(&self) -> &[BlockState] {
+        &self.blocks
+    }
+
+    ///create a chunk from all its blocks, in the x, y, z order of the chunk
+    pub fn from_blocks(blocks: &[BlockState]) -> Self {
+        let mut chunk = Self::new();
+        chunk.blocks.copy_from_slice(blocks);
+        chunk
+    }
 }
 
 impl InMemoryChunk for ChunkNative {
@@ -41,27 +69,103 @@ impl InMemoryChunk for ChunkNative {
 }
 
 ///a common interface for all types of world_core using palette compression
+///each palette entry is reference counted, when no block use an entry anymore it is freed and can be reused by another blockState
 pub trait PaletteChunk {
     fn corresponding_palette_index(&self, state: BlockState) -> Option<u8>;
     fn get_or_create_palette_index(&mut self, state: BlockState) -> Option<u8>;
     fn get_block_state_from_index(&self, palette_index: u8) -> BlockState;
+    ///the number of palette entries used by at least one block, air isn't counted
+    fn used_palette_entry_count(&self) -> usize;
+    ///the number of blocks that aren't air, it's the sum of the palette reference counts
+    fn non_air_block_count(&self) -> usize;
+    ///the number of blocks of the given state, read from the reference counts
+    fn block_count_of(&self, state: BlockState) -> usize;
+}
+
+///move a block from a palette entry to another one, the old entry is freed if no block use it anymore
+fn update_ref_count(
+    palette: &mut [BlockState],
+    ref_count: &mut [u16],
+    old_palette_index: u8,
+    new_palette_index: u8,
+) {
+    if old_palette_index == new_palette_index {
+        return;
+    }
+    if old_palette_index != 0 {
+        let entry = old_palette_index as usize - 1; // -1 because 0 is air
+        ref_count[entry] -= 1;
+        if ref_count[entry] == 0 {
+            palette[entry] = AVAILABLE_PALETTE_ENTRY;
+        }
+    }
+    if new_palette_index != 0 {
+        ref_count[new_palette_index as usize - 1] += 1;
+    }
+}
+
+///rebuild the reference counts of a palette from the palette indices of all the blocks
+fn count_references(ref_count: &mut [u16], palette_indices: impl Iterator<Item = u8>) {
+    for palette_index in palette_indices.filter(|palette_index| *palette_index != 0) {
+        ref_count[palette_index as usize - 1] += 1; // -1 because 0 is air
+    }
+}
+
+///when the palette is full, the entry of the replaced block can still be recycled if this block is its only user
+///return false if it's not possible, in this case the chunk has to be promoted
+fn reuse_last_entry(
+    palette: &mut [BlockState],
+    ref_count: &[u16],
+    old_palette_index: u8,
+    state: BlockState,
+) -> bool {
+    if old_palette_index == 0 || ref_count[old_palette_index as usize - 1] != 1 {
+        return false;
+    }
+    palette[old_palette_index as usize - 1] = state;
+    true
 }
 
 ///stores blockStates on 8bits. There is a limit of 256 blockState Variants.
-///use 47% less memory than NativeChunk (4352 bytes vs 8192 bytes)
+///use 38% less memory than NativeChunk (5116 bytes vs 8192 bytes)
+#[derive(Clone)]
 pub struct Chunk8Bits {
     palette: [BlockState; 255], //256 is the size of an u8 - 1 for the air, we could use a Vec<BlockState> but it might be less efficient since it would be allocated on the heap
+    palette_ref_count: [u16; 255], //number of blocks using each palette entry
     blocks: [u8; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
 }
 
 impl Chunk8Bits {
+    pub const PALETTE_SIZE: usize = 255;
+
     pub fn new() -> Chunk8Bits {
         Chunk8Bits {
             palette: [AVAILABLE_PALETTE_ENTRY; 255],
+            palette_ref_count: [0; 255],
             blocks: [0; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
         }
     }
 
+    ///the palette entries, the palette index of an entry is its position + 1, free entries are air
+    pub fn palette(&self) -> &[BlockState] {
+        &self.palette
+    }
+
+    ///the palette index of each block, in the x, y, z order of the chunk
+    pub fn raw_blocks(&self) -> &[u8] {
+        &self.blocks
+    }
+
+    ///rebuild a chunk from its palette and its palette indices, the reference counts are recomputed
+    ///the caller must make sure every index points to an entry of the palette which isn't free, and that no used entry is duplicated
+    pub fn from_raw_parts(palette: &[BlockState], blocks: &[u8]) -> Self {
+        let mut chunk = Self::new();
+        chunk.palette[..palette.len()].copy_from_slice(palette);
+        chunk.blocks.copy_from_slice(blocks);
+        count_references(&mut chunk.palette_ref_count, blocks.iter().copied());
+        chunk
+    }
+
     pub fn promote_to(&self, native_chunk: &mut ChunkNative) {
         for (i, palette_index) in self.blocks.iter().enumerate() {
             native_chunk.blocks[i] = self.get_block_state_from_index(*palette_index);
@@ -88,15 +192,13 @@ impl PaletteChunk for Chunk8Bits {
         }
 
         for i in 0..self.palette.len() {
-            if self.palette[i] == 0 {
+            if self.palette[i] == AVAILABLE_PALETTE_ENTRY {
                 //0 means empty and can be used
                 self.palette[i] = state;
                 return Some(i as u8 + 1); //+1 because 0 is air
             }
         }
 
-        //we should try to add a mechanism to free palette_index when the block is removed !
-
         None
     }
 
@@ -106,6 +208,28 @@ impl PaletteChunk for Chunk8Bits {
         }
         self.palette[palette_index as usize - 1] // -1 because 0 is air
     }
+
+    fn used_palette_entry_count(&self) -> usize {
+        self.palette_ref_count
+            .iter()
+            .filter(|count| **count > 0)
+            .count()
+    }
+
+    fn non_air_block_count(&self) -> usize {
+        self.palette_ref_count
+            .iter()
+            .map(|count| *count as usize)
+            .sum()
+    }
+
+    fn block_count_of(&self, state: BlockState) -> usize {
+        match self.corresponding_palette_index(state) {
+            Some(0) => (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize - self.non_air_block_count(),
+            Some(palette_index) => self.palette_ref_count[palette_index as usize - 1] as usize,
+            None => 0,
+        }
+    }
 }
 
 impl InMemoryChunk for Chunk8Bits {
@@ -118,35 +242,86 @@ impl InMemoryChunk for Chunk8Bits {
 
     fn try_set_block(&mut self, pos: BlockPos, state: BlockState) -> bool {
         assert!(pos.x < CHUNK_SIZE && pos.y < CHUNK_SIZE && pos.z < CHUNK_SIZE);
-        let get_or_create_palette_index = self.get_or_create_palette_index(state);
-        if let Some(palette_index) = get_or_create_palette_index {
-            self.blocks[(pos.x + pos.y * CHUNK_SIZE + pos.z * CHUNK_SIZE * CHUNK_SIZE) as usize] =
-                palette_index;
-            return true;
-        }
-        false
+        let array_index = (pos.x + pos.y * CHUNK_SIZE + pos.z * CHUNK_SIZE * CHUNK_SIZE) as usize;
+        let old_palette_index = self.blocks[array_index];
+        let palette_index = match self.get_or_create_palette_index(state) {
+            Some(palette_index) => palette_index,
+            None => {
+                return reuse_last_entry(
+                    &mut self.palette,
+                    &self.palette_ref_count,
+                    old_palette_index,
+                    state,
+                )
+            }
+        };
+        update_ref_count(
+            &mut self.palette,
+            &mut self.palette_ref_count,
+            old_palette_index,
+            palette_index,
+        );
+        self.blocks[array_index] = palette_index;
+        true
     }
 }
 
 /// stores blockStates on 4bits. There is a limit of 15 blockState Variants.
-/// use 74% less memory than NativeChunk (2063 bytes vs 8192 bytes)
+/// use 74% less memory than NativeChunk (2108 bytes vs 8192 bytes)
+#[derive(Clone)]
 pub struct Chunk4Bits {
     palette: [BlockState; 15], //16 is the size of an u8 - 1 for the air, we could use a Vec<BlockState> but it might be less efficient since it would be allocated on the heap
+    palette_ref_count: [u16; 15], //number of blocks using each palette entry
     blocks: [u8; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE / 2) as usize], //4 bits per block u4 doesn't exist in rust so we use u8...
 }
 
 impl Chunk4Bits {
+    pub const PALETTE_SIZE: usize = 15;
+
     pub fn new() -> Self {
         Self {
             palette: [AVAILABLE_PALETTE_ENTRY; 15], // a bit tricky, we use the fact that air is always 0, but in fact, we set two values at a time
+            palette_ref_count: [0; 15],
             blocks: [0; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE / 2) as usize],
         }
     }
 
+    ///create a chunk where every block is the given state, the state shouldn't be air
+    pub fn new_filled(state: BlockState) -> Self {
+        debug_assert!(state != AIR);
+        let mut chunk = Self::new();
+        chunk.palette[0] = state;
+        chunk.palette_ref_count[0] = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as u16;
+        chunk.blocks = [0b00010001; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE / 2) as usize]; //palette index 1 in both halves
+        chunk
+    }
+
+    ///the palette entries, the palette index of an entry is its position + 1, free entries are air
+    pub fn palette(&self) -> &[BlockState] {
+        &self.palette
+    }
+
+    ///the palette indices of the blocks, two per byte, the first block in the low half
+    pub fn raw_blocks(&self) -> &[u8] {
+        &self.blocks
+    }
+
+    ///rebuild a chunk from its palette and its packed palette indices, the reference counts are recomputed
+    ///the caller must make sure every index points to an entry of the palette which isn't free, and that no used entry is duplicated
+    pub fn from_raw_parts(palette: &[BlockState], blocks: &[u8]) -> Self {
+        let mut chunk = Self::new();
+        chunk.palette[..palette.len()].copy_from_slice(palette);
+        chunk.blocks.copy_from_slice(blocks);
+        let palette_indices = blocks.iter().flat_map(|block| [block & 0b1111, block >> 4]);
+        count_references(&mut chunk.palette_ref_count, palette_indices);
+        chunk
+    }
+
     pub fn promote_to(&self, chunk8bits: &mut Chunk8Bits) {
         //copy the palette
         for (i, blockstate) in self.palette.iter().enumerate() {
             chunk8bits.palette[i] = *blockstate;
+            chunk8bits.palette_ref_count[i] = self.palette_ref_count[i];
         }
         //copy the blocks
         for (i, block) in self.blocks.iter().enumerate() {
@@ -177,14 +352,13 @@ impl PaletteChunk for Chunk4Bits {
         }
 
         for i in 0..self.palette.len() {
-            if self.palette[i] == 0 {
+            if self.palette[i] == AVAILABLE_PALETTE_ENTRY {
                 //0 means empty and can be used
                 self.palette[i] = state;
                 return Some(i as u8 + 1); //+1 because 0 is air
             }
         }
 
-        //we should try to add a mechanism to free palette_index when the block is removed !
         None
     }
 
@@ -194,6 +368,28 @@ impl PaletteChunk for Chunk4Bits {
         }
         self.palette[palette_index as usize - 1] // -1 because 0 is air
     }
+
+    fn used_palette_entry_count(&self) -> usize {
+        self.palette_ref_count
+            .iter()
+            .filter(|count| **count > 0)
+            .count()
+    }
+
+    fn non_air_block_count(&self) -> usize {
+        self.palette_ref_count
+            .iter()
+            .map(|count| *count as usize)
+            .sum()
+    }
+
+    fn block_count_of(&self, state: BlockState) -> usize {
+        match self.corresponding_palette_index(state) {
+            Some(0) => (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize - self.non_air_block_count(),
+            Some(palette_index) => self.palette_ref_count[palette_index as usize - 1] as usize,
+            None => 0,
+        }
+    }
 }
 
 impl InMemoryChunk for Chunk4Bits {
@@ -216,22 +412,41 @@ impl InMemoryChunk for Chunk4Bits {
 
     fn try_set_block(&mut self, pos: BlockPos, state: BlockState) -> bool {
         assert!(pos.x < CHUNK_SIZE && pos.y < CHUNK_SIZE && pos.z < CHUNK_SIZE);
-        let get_or_create_palette_index = self.get_or_create_palette_index(state);
-        if let Some(palette_index) = get_or_create_palette_index {
-            let linear_coord = pos.x + pos.y * CHUNK_SIZE + pos.z * CHUNK_SIZE * CHUNK_SIZE;
-            let array_index = linear_coord >> 1; // divide by 2
-            let is_first_half = linear_coord & 1 == 0; // modulo 2
-
-            //set the good half of the byte
-            if is_first_half {
-                self.blocks[array_index as usize] =
-                    (self.blocks[array_index as usize] & 0b11110000) | palette_index;
-            } else {
-                self.blocks[array_index as usize] =
-                    (self.blocks[array_index as usize] & 0b00001111) | (palette_index << 4);
+        let linear_coord = pos.x + pos.y * CHUNK_SIZE + pos.z * CHUNK_SIZE * CHUNK_SIZE;
+        let array_index = linear_coord >> 1; // divide by 2
+        let is_first_half = linear_coord & 1 == 0; // modulo 2
+
+        let old_palette_index = if is_first_half {
+            self.blocks[array_index as usize] & 0b1111
+        } else {
+            self.blocks[array_index as usize] >> 4
+        };
+        let palette_index = match self.get_or_create_palette_index(state) {
+            Some(palette_index) => palette_index,
+            None => {
+                return reuse_last_entry(
+                    &mut self.palette,
+                    &self.palette_ref_count,
+                    old_palette_index,
+                    state,
+                )
             }
-            return true;
+        };
+        update_ref_count(
+            &mut self.palette,
+            &mut self.palette_ref_count,
+            old_palette_index,
+            palette_index,
+        );
+
+        //set the good half of the byte
+        if is_first_half {
+            self.blocks[array_index as usize] =
+                (self.blocks[array_index as usize] & 0b11110000) | palette_index;
+        } else {
+            self.blocks[array_index as usize] =
+                (self.blocks[array_index as usize] & 0b00001111) | (palette_index << 4);
         }
-        false
+        true
     }
 }
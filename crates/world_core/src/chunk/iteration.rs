@@ -0,0 +1,96 @@
+use crate::block_state::{BlockState, AIR};
+use crate::chunk::{block_pos_of, Chunk, ChunkHandle};
+use math::positions::BlockPos;
+
+///where the iterator reads the blocks, the palette formats are read directly to avoid a match per block on the handle
+enum BlockSource<'a> {
+    Uniform(BlockState),
+    Native(&'a [BlockState]),
+    Bits8 {
+        palette: &'a [BlockState],
+        blocks: &'a [u8],
+    },
+    Bits4 {
+        palette: &'a [BlockState],
+        blocks: &'a [u8],
+    },
+}
+
+///iterate over the blocks of a chunk with their local position, in the x, y, z order of Chunk::blocks
+///see Chunk::iter_blocks and Chunk::iter_non_air_blocks
+pub struct BlockIter<'a> {
+    source: BlockSource<'a>,
+    index: usize,
+    skip_air: bool,
+}
+
+impl<'a> BlockIter<'a> {
+    pub(super) fn new(chunk: &'a Chunk, skip_air: bool) -> Self {
+        let source = match &chunk.handle {
+            ChunkHandle::ChunkEmpty => BlockSource::Uniform(AIR),
+            ChunkHandle::ChunkUniform(state) => BlockSource::Uniform(*state),
+            ChunkHandle::ChunkNative(chunk) => BlockSource::Native(chunk.blocks()),
+            ChunkHandle::Chunk8bits(chunk) => BlockSource::Bits8 {
+                palette: chunk.palette(),
+                blocks: chunk.raw_blocks(),
+            },
+            ChunkHandle::Chunk4bits(chunk) => BlockSource::Bits4 {
+                palette: chunk.palette(),
+                blocks: chunk.raw_blocks(),
+            },
+        };
+        //nothing to visit in an empty chunk
+        let index = match source {
+            BlockSource::Uniform(AIR) if skip_air => Chunk::BLOCK_COUNT,
+            _ => 0,
+        };
+        Self {
+            source,
+            index,
+            skip_air,
+        }
+    }
+
+    fn state_at(&self, index: usize) -> BlockState {
+        match self.source {
+            BlockSource::Uniform(state) => state,
+            BlockSource::Native(blocks) => blocks[index],
+            BlockSource::Bits8 { palette, blocks } => from_palette(palette, blocks[index]),
+            BlockSource::Bits4 { palette, blocks } => {
+                let palette_index = (blocks[index >> 1] >> ((index & 1) * 4)) & 0b1111; //the first block in the low half
+                from_palette(palette, palette_index)
+            }
+        }
+    }
+}
+
+impl Iterator for BlockIter<'_> {
+    type Item = (BlockPos, BlockState);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < Chunk::BLOCK_COUNT {
+            let index = self.index;
+            self.index += 1;
+            let state = self.state_at(index);
+            if !self.skip_air || state != AIR {
+                return Some((block_pos_of(index), state));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = Chunk::BLOCK_COUNT - self.index;
+        match self.skip_air {
+            true => (0, Some(remaining)),
+            false => (remaining, Some(remaining)),
+        }
+    }
+}
+
+fn from_palette(palette: &[BlockState], palette_index: u8) -> BlockState {
+    match palette_index {
+        0 => AIR, //0 is always air
+        _ => palette[palette_index as usize - 1],
+    }
+}
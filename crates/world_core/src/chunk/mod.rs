@@ -1,18 +1,47 @@
 mod implementation;
+mod iteration;
+mod serialization;
+mod snapshot;
 
+use crate::biome::{Biome, ChunkBiomes};
+use crate::block_entity::{BlockEntity, ChunkBlockEntities};
 use crate::block_state::{BlockState, AIR};
+use crate::heightmap::ChunkHeightmaps;
+use crate::light::ChunkLight;
+use crate::tick::ChunkTicks;
 use ctor::ctor;
-use implementation::{Chunk4Bits, Chunk8Bits, ChunkNative, InMemoryChunk};
+use implementation::{Chunk4Bits, Chunk8Bits, ChunkNative, InMemoryChunk, PaletteChunk};
 use math::positions::{BlockPos, ChunkPos};
 use math::{consts::CHUNK_SIZE, IVec3};
 use shared_arena::{ArenaBox, SharedArena};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 use utils::memory_utils::MemorySize;
 
+pub use iteration::BlockIter;
+pub use serialization::{
+    ChunkCompression, ChunkDecodeError, ChunkEncodeError, CHUNK_FORMAT_VERSION,
+    MAX_BLOCK_ENTITIES_SIZE,
+};
+pub use snapshot::ChunkSnapshot;
+
 ///class where all memory used by the chunk is stored, should leave longer than all the world_core loaded in memory
 pub struct ChunkMemoryPool {
     chunks_native: SharedArena<ChunkNative>,
     chunks8bits: SharedArena<Chunk8Bits>,
     chunks4bits: SharedArena<Chunk4Bits>,
+    uniform_chunks: AtomicUsize, //uniform chunks don't use the arenas, they are only counted
+}
+
+///a snapshot of the memory used by the chunks
+pub struct ChunkMemoryStats {
+    ///memory used by the chunks stored in the arenas
+    pub used: MemorySize,
+    ///memory pre-allocated by the arenas but not used yet
+    pub pre_allocated: MemorySize,
+    ///number of chunks filled with a single blockState, they don't use any arena memory
+    pub uniform_chunks: usize,
 }
 
 impl ChunkMemoryPool {
@@ -21,11 +50,12 @@ impl ChunkMemoryPool {
             chunks_native: SharedArena::new(),
             chunks8bits: SharedArena::new(),
             chunks4bits: SharedArena::new(),
+            uniform_chunks: AtomicUsize::new(0),
         }
     }
 
-    ///return the memory used and the memory pre-allocated but not used
-    pub fn stats(&self) -> (MemorySize, MemorySize) {
+    ///return the memory used, the memory pre-allocated but not used and the number of uniform chunks
+    pub fn stats(&self) -> ChunkMemoryStats {
         let (native_used, native_free) = self.chunks_native.stats();
         let (bits8_used, bits8_free) = self.chunks8bits.stats();
         let (bits4_used, bits4_free) = self.chunks4bits.stats();
@@ -38,22 +68,64 @@ impl ChunkMemoryPool {
 
         let total_used = memory_used(native_used, bits8_used, bits4_used);
         let total_free = memory_used(native_free, bits8_free, bits4_free);
-        (total_used.into(), total_free.into())
+        ChunkMemoryStats {
+            used: total_used.into(),
+            pre_allocated: total_free.into(),
+            uniform_chunks: self.uniform_chunks.load(Ordering::Relaxed),
+        }
+    }
+
+    ///give the free slots of the arenas back to the system, useful after unloading a lot of chunks
+    ///return true if some memory was released
+    pub fn shrink_to_fit(&self) -> bool {
+        let native = self.chunks_native.shrink_to_fit();
+        let bits8 = self.chunks8bits.shrink_to_fit();
+        let bits4 = self.chunks4bits.shrink_to_fit();
+        native || bits8 || bits4
     }
 }
 
+///the storage of a chunk in the arenas, it's shared with the snapshots of the chunk and copied on the next write, see Chunk::snapshot
+type SharedStorage<T> = Arc<ArenaBox<T>>;
+
+#[derive(Clone)]
 enum ChunkHandle {
     ChunkEmpty,
-    ChunkNative(ArenaBox<ChunkNative>),
-    Chunk8bits(ArenaBox<Chunk8Bits>),
-    Chunk4bits(ArenaBox<Chunk4Bits>),
+    ///every block of the chunk is the same blockState, which is never air (that would be an empty chunk)
+    ChunkUniform(BlockState),
+    ChunkNative(SharedStorage<ChunkNative>),
+    Chunk8bits(SharedStorage<Chunk8Bits>),
+    Chunk4bits(SharedStorage<Chunk4Bits>),
+}
+
+///get the storage with mutable capabilities, it's copied in a new arena slot first if a snapshot still uses it
+fn unique_storage<'a, T: Clone>(
+    storage: &'a mut SharedStorage<T>,
+    arena: &SharedArena<T>,
+) -> &'a mut T {
+    if Arc::get_mut(storage).is_none() {
+        *storage = Arc::new(arena.alloc(T::clone(storage)));
+    }
+    Arc::get_mut(storage).unwrap()
 }
 
 ///represent a non-empty chunk loaded in memory, this class is responsible for the memory management of the chunk as well as the chunk format
 pub struct Chunk {
     position: ChunkPos,
     handle: ChunkHandle,
-    //memory map and metadata can be safely added here
+    light: ChunkLight,
+    heightmaps: ChunkHeightmaps,
+    ticks: ChunkTicks,
+    biomes: ChunkBiomes,
+    block_entities: ChunkBlockEntities,
+    last_access: AtomicU64, //updated by the ChunkManager, used to evict the least recently used chunks
+                            //memory map and metadata can be safely added here
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        self.set_handle(ChunkHandle::ChunkEmpty); //keep the uniform chunk count right
+    }
 }
 
 #[ctor]
@@ -61,12 +133,43 @@ pub static MEMORY_MANAGER: ChunkMemoryPool = ChunkMemoryPool::new();
 
 impl Chunk {
     pub const SIZE: i32 = CHUNK_SIZE;
+    pub const BLOCK_COUNT: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
 
     pub fn new(position: ChunkPos) -> Self {
         Self {
             position,
             handle: ChunkHandle::ChunkEmpty,
+            light: ChunkLight::default(),
+            heightmaps: ChunkHeightmaps::default(),
+            ticks: ChunkTicks::default(),
+            biomes: ChunkBiomes::default(),
+            block_entities: ChunkBlockEntities::default(),
+            last_access: AtomicU64::new(0),
+        }
+    }
+
+    ///create a chunk where every block is the given state without allocating anything, useful for the underground or the oceans
+    pub fn new_uniform(position: ChunkPos, state: BlockState) -> Self {
+        let mut chunk = Self::new(position);
+        if state != AIR {
+            chunk.set_handle(ChunkHandle::ChunkUniform(state));
+        }
+        chunk
+    }
+
+    ///replace the handle, every change of format should go through this function to keep the uniform chunk count right
+    fn set_handle(&mut self, handle: ChunkHandle) {
+        if matches!(handle, ChunkHandle::ChunkUniform(_)) {
+            MEMORY_MANAGER
+                .uniform_chunks
+                .fetch_add(1, Ordering::Relaxed);
         }
+        if matches!(self.handle, ChunkHandle::ChunkUniform(_)) {
+            MEMORY_MANAGER
+                .uniform_chunks
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+        self.handle = handle;
     }
 
     ///promote the chunk to a bigger format, if the chunk is already in the largest format, nothing happens
@@ -77,26 +180,201 @@ impl Chunk {
             ChunkHandle::Chunk8bits(handle) => {
                 let mut new_handle = MEMORY_MANAGER.chunks_native.alloc(ChunkNative::new());
                 handle.promote_to(&mut new_handle);
-                self.handle = ChunkHandle::ChunkNative(new_handle);
+                self.set_handle(ChunkHandle::ChunkNative(Arc::new(new_handle)));
             }
             ChunkHandle::Chunk4bits(chunk) => {
                 let mut new_handle = MEMORY_MANAGER.chunks8bits.alloc(Chunk8Bits::new());
                 chunk.promote_to(&mut new_handle);
-                self.handle = ChunkHandle::Chunk8bits(new_handle)
+                self.set_handle(ChunkHandle::Chunk8bits(Arc::new(new_handle)))
+            }
+            ChunkHandle::ChunkUniform(state) => {
+                let new_handle = MEMORY_MANAGER
+                    .chunks4bits
+                    .alloc(Chunk4Bits::new_filled(*state));
+                self.set_handle(ChunkHandle::Chunk4bits(Arc::new(new_handle)))
             }
             ChunkHandle::ChunkEmpty => {
                 let new_handle = MEMORY_MANAGER.chunks4bits.alloc(Chunk4Bits::new()); //nothing to copy
-                self.handle = ChunkHandle::Chunk4bits(new_handle)
+                self.set_handle(ChunkHandle::Chunk4bits(Arc::new(new_handle)))
             }
         }
     }
 
+    ///demote the chunk to the smallest format that can still store its blocks, return true if the format changed
+    ///it's the opposite of promote, it can go back to an empty chunk when only air remains, or to a uniform chunk when a single blockState fills it
+    ///the used palette entries are counted, so a palette chunk is cheap to check, but a native chunk has to be scanned
+    pub fn compact(&mut self) -> bool {
+        let (distinct_states, non_air_blocks) = match &self.handle {
+            ChunkHandle::ChunkEmpty | ChunkHandle::ChunkUniform(_) => return false,
+            ChunkHandle::Chunk4bits(chunk) => (
+                chunk.used_palette_entry_count(),
+                chunk.non_air_block_count(),
+            ),
+            ChunkHandle::Chunk8bits(chunk) => (
+                chunk.used_palette_entry_count(),
+                chunk.non_air_block_count(),
+            ),
+            ChunkHandle::ChunkNative(chunk) => (
+                chunk.distinct_state_count(Chunk8Bits::PALETTE_SIZE),
+                chunk.non_air_block_count(),
+            ),
+        };
+
+        let new_handle = match (&self.handle, distinct_states) {
+            (_, 0) => ChunkHandle::ChunkEmpty,
+            (_, 1) if non_air_blocks == Self::BLOCK_COUNT => {
+                ChunkHandle::ChunkUniform(self.get_block(BlockPos::ZERO))
+            }
+            (ChunkHandle::Chunk4bits(_), _) => return false,
+            (_, count) if count <= Chunk4Bits::PALETTE_SIZE => {
+                let mut new_handle = MEMORY_MANAGER.chunks4bits.alloc(Chunk4Bits::new());
+                self.copy_blocks_to(&mut *new_handle);
+                ChunkHandle::Chunk4bits(Arc::new(new_handle))
+            }
+            (ChunkHandle::Chunk8bits(_), _) => return false,
+            (_, count) if count <= Chunk8Bits::PALETTE_SIZE => {
+                let mut new_handle = MEMORY_MANAGER.chunks8bits.alloc(Chunk8Bits::new());
+                self.copy_blocks_to(&mut *new_handle);
+                ChunkHandle::Chunk8bits(Arc::new(new_handle))
+            }
+            _ => return false,
+        };
+        self.set_handle(new_handle);
+        true
+    }
+
+    ///get all the blocks of the chunk, the index of a block is x + y * 16 + z * 256
+    pub fn blocks(&self) -> Vec<BlockState> {
+        match &self.handle {
+            ChunkHandle::ChunkEmpty => vec![AIR; Self::BLOCK_COUNT],
+            ChunkHandle::ChunkUniform(state) => vec![*state; Self::BLOCK_COUNT],
+            ChunkHandle::ChunkNative(chunk) => chunk.blocks().to_vec(),
+            _ => self.iter_blocks().map(|(_, state)| state).collect(),
+        }
+    }
+
+    ///iterate over all the blocks of the chunk with their local position, in the order of Chunk::blocks
+    ///the format is matched once instead of once per block like with get_block
+    pub fn iter_blocks(&self) -> BlockIter<'_> {
+        BlockIter::new(self, false)
+    }
+
+    ///iterate over the non-air blocks of the chunk with their local position, nothing is visited in an empty chunk
+    pub fn iter_non_air_blocks(&self) -> BlockIter<'_> {
+        BlockIter::new(self, true)
+    }
+
+    ///the distinct states present in the chunk, air included if at least one block is air
+    ///it's read from the palette, only a native chunk has to be scanned
+    pub fn palette(&self) -> Vec<BlockState> {
+        let (mut palette, non_air_blocks) = match &self.handle {
+            ChunkHandle::ChunkEmpty => return vec![AIR],
+            ChunkHandle::ChunkUniform(state) => return vec![*state],
+            ChunkHandle::ChunkNative(chunk) => {
+                let mut states = HashSet::new();
+                let palette = chunk
+                    .blocks()
+                    .iter()
+                    .filter(|state| states.insert(**state))
+                    .copied()
+                    .collect();
+                return palette;
+            }
+            //the free entries are air
+            ChunkHandle::Chunk8bits(chunk) => {
+                (chunk.palette().to_vec(), chunk.non_air_block_count())
+            }
+            ChunkHandle::Chunk4bits(chunk) => {
+                (chunk.palette().to_vec(), chunk.non_air_block_count())
+            }
+        };
+        palette.retain(|state| *state != AIR);
+        if non_air_blocks < Self::BLOCK_COUNT {
+            palette.insert(0, AIR);
+        }
+        palette
+    }
+
+    ///return true if at least one block of the chunk is the given state, only a native chunk has to be scanned
+    pub fn contains_state(&self, state: BlockState) -> bool {
+        match &self.handle {
+            ChunkHandle::ChunkNative(chunk) => chunk.blocks().contains(&state),
+            _ => self.count_state(state) > 0,
+        }
+    }
+
+    ///count the blocks of the given state, it's read from the palette reference counts, only a native chunk has to be scanned
+    pub fn count_state(&self, state: BlockState) -> usize {
+        match &self.handle {
+            ChunkHandle::ChunkEmpty if state == AIR => Self::BLOCK_COUNT,
+            ChunkHandle::ChunkUniform(uniform_state) if *uniform_state == state => {
+                Self::BLOCK_COUNT
+            }
+            ChunkHandle::ChunkEmpty | ChunkHandle::ChunkUniform(_) => 0,
+            ChunkHandle::ChunkNative(chunk) => chunk
+                .blocks()
+                .iter()
+                .filter(|block| **block == state)
+                .count(),
+            ChunkHandle::Chunk8bits(chunk) => chunk.block_count_of(state),
+            ChunkHandle::Chunk4bits(chunk) => chunk.block_count_of(state),
+        }
+    }
+
+    ///replace all the blocks of the chunk, in the order given by blocks
+    ///the chunk is rebuilt directly in the smallest format that can hold the blocks, so it never goes through promote like many set_block would
+    ///the block entities of the blocks that change are removed
+    pub fn set_blocks(&mut self, blocks: &[BlockState]) {
+        assert_eq!(blocks.len(), Self::BLOCK_COUNT);
+        if !self.block_entities.is_empty() {
+            let mut block_entities = std::mem::take(&mut self.block_entities);
+            block_entities.retain(|pos, _| blocks[block_index(pos)] == self.get_block(pos));
+            self.block_entities = block_entities;
+        }
+        let mut states = HashSet::new();
+        for state in blocks {
+            if *state != AIR && states.insert(*state) && states.len() > Chunk8Bits::PALETTE_SIZE {
+                break;
+            }
+        }
+
+        let new_handle = match states.len() {
+            0 => ChunkHandle::ChunkEmpty,
+            1 if blocks.iter().all(|state| *state != AIR) => ChunkHandle::ChunkUniform(blocks[0]),
+            count if count <= Chunk4Bits::PALETTE_SIZE => {
+                let mut new_handle = MEMORY_MANAGER.chunks4bits.alloc(Chunk4Bits::new());
+                copy_non_air_blocks(blocks, &mut *new_handle);
+                ChunkHandle::Chunk4bits(Arc::new(new_handle))
+            }
+            count if count <= Chunk8Bits::PALETTE_SIZE => {
+                let mut new_handle = MEMORY_MANAGER.chunks8bits.alloc(Chunk8Bits::new());
+                copy_non_air_blocks(blocks, &mut *new_handle);
+                ChunkHandle::Chunk8bits(Arc::new(new_handle))
+            }
+            _ => ChunkHandle::ChunkNative(Arc::new(
+                MEMORY_MANAGER
+                    .chunks_native
+                    .alloc(ChunkNative::from_blocks(blocks)),
+            )),
+        };
+        self.set_handle(new_handle);
+    }
+
+    ///copy all the non-air blocks of the chunk into a smaller format, it must be able to hold them
+    fn copy_blocks_to(&self, target: &mut impl InMemoryChunk) {
+        for (pos, state) in self.iter_non_air_blocks() {
+            let success = target.try_set_block(pos, state);
+            debug_assert!(success, "the target format is too small");
+        }
+    }
+
     ///get the blockstate at the given position
     pub fn get_block(&self, pos: BlockPos) -> BlockState {
         match self.handle {
             ChunkHandle::ChunkNative(ref chunk) => chunk.get_block(pos),
             ChunkHandle::Chunk8bits(ref chunk) => chunk.get_block(pos),
             ChunkHandle::Chunk4bits(ref chunk) => chunk.get_block(pos),
+            ChunkHandle::ChunkUniform(state) => state,
             ChunkHandle::ChunkEmpty => AIR,
         }
     }
@@ -106,13 +384,23 @@ impl Chunk {
         self.get_block(BlockPos::new(x, y, z))
     }
 
-    ///set the blockstate at the given position
+    ///set the blockstate at the given position, the block entity of the block is removed if the blockstate changes
     pub fn set_block(&mut self, pos: BlockPos, state: BlockState) {
+        if !self.block_entities.is_empty() && self.get_block(pos) != state {
+            self.block_entities.remove(pos);
+        }
         //set the blockstate at the given position can fail if the chunk is not in the right format
         while !match self.handle {
-            ChunkHandle::ChunkNative(ref mut chunk) => chunk.try_set_block(pos, state),
-            ChunkHandle::Chunk8bits(ref mut chunk) => chunk.try_set_block(pos, state),
-            ChunkHandle::Chunk4bits(ref mut chunk) => chunk.try_set_block(pos, state),
+            ChunkHandle::ChunkNative(ref mut chunk) => {
+                unique_storage(chunk, &MEMORY_MANAGER.chunks_native).try_set_block(pos, state)
+            }
+            ChunkHandle::Chunk8bits(ref mut chunk) => {
+                unique_storage(chunk, &MEMORY_MANAGER.chunks8bits).try_set_block(pos, state)
+            }
+            ChunkHandle::Chunk4bits(ref mut chunk) => {
+                unique_storage(chunk, &MEMORY_MANAGER.chunks4bits).try_set_block(pos, state)
+            }
+            ChunkHandle::ChunkUniform(uniform_state) => uniform_state == state,
             ChunkHandle::ChunkEmpty => false,
         } {
             self.promote();
@@ -124,12 +412,115 @@ impl Chunk {
         self.position
     }
 
+    ///get the light of the chunk, it's computed by ChunkManager::update_light
+    pub fn light(&self) -> &ChunkLight {
+        &self.light
+    }
+
+    ///get the light of the chunk with mutable capabilities, the light set here isn't propagated
+    pub fn light_mut(&mut self) -> &mut ChunkLight {
+        &mut self.light
+    }
+
+    ///get the heightmaps of the chunk, they are maintained by the ChunkManager, see ChunkManager::get_height
+    pub fn heightmaps(&self) -> &ChunkHeightmaps {
+        &self.heightmaps
+    }
+
+    pub(crate) fn heightmaps_mut(&mut self) -> &mut ChunkHeightmaps {
+        &mut self.heightmaps
+    }
+
+    ///get the block ticks scheduled in the chunk, see ChunkManager::schedule_tick
+    pub fn scheduled_ticks(&self) -> &ChunkTicks {
+        &self.ticks
+    }
+
+    pub(crate) fn scheduled_ticks_mut(&mut self) -> &mut ChunkTicks {
+        &mut self.ticks
+    }
+
+    ///get the biomes of the chunk, they are stored per 4x4x4 cell
+    pub fn biomes(&self) -> &ChunkBiomes {
+        &self.biomes
+    }
+
+    ///get the biomes with mutable capabilities, to fill them during the generation
+    pub fn biomes_mut(&mut self) -> &mut ChunkBiomes {
+        &mut self.biomes
+    }
+
+    ///get the biome of the block at the given position in the chunk
+    pub fn get_biome(&self, pos: BlockPos) -> &Biome {
+        self.biomes.get(pos)
+    }
+
+    ///set the biome of the 4x4x4 cell containing the given position, return true if it changed
+    pub fn set_biome(&mut self, pos: BlockPos, biome: Biome) -> bool {
+        self.biomes.set(pos, biome)
+    }
+
+    ///get the block entities of the chunk
+    pub fn block_entities(&self) -> &ChunkBlockEntities {
+        &self.block_entities
+    }
+
+    ///get the block entity at the given position in the chunk
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.block_entities.get(pos)
+    }
+
+    pub fn get_block_entity_mut(&mut self, pos: BlockPos) -> Option<&mut BlockEntity> {
+        self.block_entities.get_mut(pos)
+    }
+
+    ///attach a block entity to the block at the given position, return the block entity it replaces
+    pub fn set_block_entity(
+        &mut self,
+        pos: BlockPos,
+        block_entity: BlockEntity,
+    ) -> Option<BlockEntity> {
+        self.block_entities.insert(pos, block_entity)
+    }
+
+    pub fn remove_block_entity(&mut self, pos: BlockPos) -> Option<BlockEntity> {
+        self.block_entities.remove(pos)
+    }
+
+    ///the value of the access clock of the ChunkManager the last time the chunk was accessed through it, 0 if it never was
+    ///only get_chunk, get_chunk_by_id and get_chunk_mut count as an access, the queries over many chunks like raycast don't
+    pub fn last_access(&self) -> u64 {
+        self.last_access.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn touch(&self, access_time: u64) {
+        self.last_access.fetch_max(access_time, Ordering::Relaxed);
+    }
+
+    ///an estimation of the memory used by the chunk in bytes, the memory shared with snapshots is counted too
+    pub fn memory_size(&self) -> usize {
+        let blocks = match self.handle {
+            ChunkHandle::ChunkEmpty | ChunkHandle::ChunkUniform(_) => 0,
+            ChunkHandle::ChunkNative(_) => std::mem::size_of::<ChunkNative>(),
+            ChunkHandle::Chunk8bits(_) => std::mem::size_of::<Chunk8Bits>(),
+            ChunkHandle::Chunk4bits(_) => std::mem::size_of::<Chunk4Bits>(),
+        };
+        std::mem::size_of::<Self>()
+            + blocks
+            + self.light.memory_size()
+            + self.heightmaps.memory_size()
+            + self.ticks.memory_size()
+            + self.biomes.memory_size()
+            + self.block_entities.memory_size()
+    }
+
     ///set the blockstate at the given position, just an alias for set_block
     pub fn set_block_at(&mut self, x: i32, y: i32, z: i32, state: BlockState) {
         self.set_block(BlockPos::new(x, y, z), state);
     }
 
     ///return true if the chunk only contains air, it doesn't mean that the chunk with only air will always return true (because of the promotion)
+    ///call compact to turn a chunk with only air back into an empty chunk
     ///useful to skip operation on empty chunk
     pub fn is_empty(&self) -> bool {
         matches!(self.handle, ChunkHandle::ChunkEmpty)
@@ -142,3 +533,169 @@ impl Chunk {
         (min, max)
     }
 }
+
+///the index of a block in Chunk::blocks
+pub(crate) fn block_index(pos: BlockPos) -> usize {
+    (pos.x + pos.y * CHUNK_SIZE + pos.z * CHUNK_SIZE * CHUNK_SIZE) as usize
+}
+
+///the position of a block from its index in Chunk::blocks
+pub(crate) fn block_pos_of(index: usize) -> BlockPos {
+    let index = index as i32;
+    BlockPos::new(
+        index % CHUNK_SIZE,
+        (index / CHUNK_SIZE) % CHUNK_SIZE,
+        index / (CHUNK_SIZE * CHUNK_SIZE),
+    )
+}
+
+fn copy_non_air_blocks(blocks: &[BlockState], target: &mut impl InMemoryChunk) {
+    for (i, state) in blocks.iter().enumerate() {
+        if *state != AIR {
+            let success = target.try_set_block(block_pos_of(i), *state);
+            debug_assert!(success, "the target format is too small");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn compact() {
+        //give a different position to each i, the layer allows to use another set of positions
+        let pos = |i: i32, layer: i32| BlockPos::new(i % 16, (i / 16) % 16, i / 256 + layer * 2);
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        for i in 0..300 {
+            chunk.set_block(pos(i, 0), BlockState::from_raw(i as u16 + 1));
+        }
+        assert!(matches!(chunk.handle, ChunkHandle::ChunkNative(_)));
+        assert!(!chunk.compact());
+
+        //keep 200 different states, it fits in a 8 bits chunk
+        for i in 200..300 {
+            chunk.set_block(pos(i, 0), AIR);
+        }
+        assert!(chunk.compact());
+        assert!(matches!(chunk.handle, ChunkHandle::Chunk8bits(_)));
+
+        //freed palette entries are reused instead of promoting
+        for i in 10..200 {
+            chunk.set_block(pos(i, 0), AIR);
+        }
+        for i in 10..200 {
+            chunk.set_block(pos(i, 1), BlockState::from_raw(1000 + i as u16));
+        }
+        assert!(matches!(chunk.handle, ChunkHandle::Chunk8bits(_)));
+        for i in 10..200 {
+            chunk.set_block(pos(i, 1), AIR);
+        }
+
+        assert!(chunk.compact());
+        assert!(matches!(chunk.handle, ChunkHandle::Chunk4bits(_)));
+        for i in 0..10 {
+            assert_eq!(
+                chunk.get_block(pos(i, 0)),
+                BlockState::from_raw(i as u16 + 1)
+            );
+        }
+
+        for i in 0..10 {
+            chunk.set_block(pos(i, 0), AIR);
+        }
+        assert!(chunk.compact());
+        assert!(chunk.is_empty());
+    }
+
+    #[test]
+    pub fn set_blocks() {
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        let mut blocks = vec![AIR; Chunk::BLOCK_COUNT];
+        for (i, block) in blocks.iter_mut().enumerate().step_by(3) {
+            *block = BlockState::from_raw((i % 100) as u16 + 1);
+        }
+        chunk.set_blocks(&blocks);
+        assert!(matches!(chunk.handle, ChunkHandle::Chunk8bits(_)));
+        assert_eq!(chunk.blocks(), blocks);
+        assert_eq!(chunk.get_block_at(3, 0, 0), BlockState::from_raw(4));
+
+        chunk.set_blocks(&[BlockState::from_raw(1); Chunk::BLOCK_COUNT]);
+        assert!(matches!(chunk.handle, ChunkHandle::ChunkUniform(_)));
+        chunk.set_blocks(&[AIR; Chunk::BLOCK_COUNT]);
+        assert!(chunk.is_empty());
+    }
+
+    #[test]
+    pub fn uniform() {
+        let stone = BlockState::from_raw(1);
+        let mut chunk = Chunk::new_uniform(ChunkPos::ZERO, stone);
+        assert!(matches!(chunk.handle, ChunkHandle::ChunkUniform(_)));
+
+        chunk.set_block(BlockPos::new(3, 4, 5), stone);
+        assert!(matches!(chunk.handle, ChunkHandle::ChunkUniform(_)));
+
+        chunk.set_block(BlockPos::new(3, 4, 5), AIR);
+        assert!(matches!(chunk.handle, ChunkHandle::Chunk4bits(_)));
+        assert_eq!(chunk.get_block(BlockPos::new(3, 4, 5)), AIR);
+        assert_eq!(chunk.get_block(BlockPos::new(15, 15, 15)), stone);
+
+        chunk.set_block(BlockPos::new(3, 4, 5), stone);
+        assert!(chunk.compact());
+        assert!(matches!(chunk.handle, ChunkHandle::ChunkUniform(_)));
+    }
+
+    #[test]
+    pub fn iterate_and_count() {
+        let stone = BlockState::from_raw(1);
+        let dirt = BlockState::from_raw(2);
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        assert_eq!(chunk.iter_non_air_blocks().count(), 0);
+        assert_eq!(chunk.iter_blocks().count(), Chunk::BLOCK_COUNT);
+        assert_eq!(chunk.palette(), vec![AIR]);
+
+        chunk.set_block_at(1, 2, 3, stone);
+        chunk.set_block_at(15, 15, 15, dirt);
+        chunk.set_block_at(4, 0, 0, dirt);
+        assert!(matches!(chunk.handle, ChunkHandle::Chunk4bits(_)));
+        let non_air = chunk.iter_non_air_blocks().collect::<Vec<_>>();
+        assert_eq!(
+            non_air,
+            vec![
+                (BlockPos::new(4, 0, 0), dirt),
+                (BlockPos::new(1, 2, 3), stone),
+                (BlockPos::new(15, 15, 15), dirt)
+            ]
+        );
+        assert_eq!(chunk.palette(), vec![AIR, stone, dirt]);
+        assert_eq!(chunk.count_state(dirt), 2);
+        assert_eq!(chunk.count_state(AIR), Chunk::BLOCK_COUNT - 3);
+        assert!(!chunk.contains_state(BlockState::from_raw(3)));
+
+        //every format gives the same blocks
+        for i in 0..300 {
+            chunk.set_block_at(
+                i % 16,
+                8 + i / 256,
+                (i / 16) % 16,
+                BlockState::from_raw(i as u16 + 10),
+            );
+            if i == 100 {
+                assert!(matches!(chunk.handle, ChunkHandle::Chunk8bits(_)));
+                assert_eq!(chunk.count_state(BlockState::from_raw(110)), 1);
+                assert_eq!(chunk.palette().len(), 1 + 2 + 101);
+            }
+        }
+        assert!(matches!(chunk.handle, ChunkHandle::ChunkNative(_)));
+        assert_eq!(chunk.palette().len(), 1 + 2 + 300);
+        assert!(chunk.contains_state(stone));
+        for (pos, state) in chunk.iter_blocks() {
+            assert_eq!(chunk.get_block(pos), state);
+        }
+
+        let chunk = Chunk::new_uniform(ChunkPos::ZERO, stone);
+        assert_eq!(chunk.iter_non_air_blocks().count(), Chunk::BLOCK_COUNT);
+        assert_eq!(chunk.count_state(stone), Chunk::BLOCK_COUNT);
+        assert!(!chunk.contains_state(AIR));
+    }
+}
@@ -0,0 +1,745 @@
+use crate::biome::{Biome, ChunkBiomes, BIOME_CELL_COUNT, MAX_BIOME_LEN};
+use crate::block_entity::BlockEntity;
+use crate::block_state::{BlockState, AIR};
+use crate::chunk::implementation::{Chunk4Bits, Chunk8Bits, ChunkNative};
+use crate::chunk::{block_index, block_pos_of, Chunk, ChunkHandle, MEMORY_MANAGER};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use ident::Ident;
+use math::positions::ChunkPos;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use thiserror::Error;
+
+///layout of a serialized chunk, all the numbers are little endian:
+///- header: version (u8), compression (u8), the rest is compressed if asked
+///- position: x, y, z (i32)
+///- format (u8) then its content:
+///  - empty: nothing
+///  - uniform: the blockState (u16)
+///  - 4 bits and 8 bits: the palette length (u8), the palette entries (u16, air for a free entry) then the palette indices as stored in memory
+///  - native: the 4096 blockStates (u16)
+///- scheduled ticks since the version 2: the tick count (u16) then for each tick the block index (u16) and the delay (u64) from the tick of the save, in the order they run
+///- biomes since the version 3: the palette length (u8), for each entry the identifier length (u8) and the identifier,
+///  then the palette index (u8) of the 64 cells in the y, z, x order if the palette has several entries
+///- block entities since the version 4: the block entity count (u16) then for each block entity the block index (u16),
+///  the type length (u16), the type, the nbt length (u32) and the data in the binary nbt format with an empty root name
+pub const CHUNK_FORMAT_VERSION: u8 = 4;
+
+const FORMAT_EMPTY: u8 = 0;
+const FORMAT_UNIFORM: u8 = 1;
+const FORMAT_4BITS: u8 = 2;
+const FORMAT_8BITS: u8 = 3;
+const FORMAT_NATIVE: u8 = 4;
+
+///the encoded size of the block entities of a chunk, without their count, like the limit of the chunk packets of vanilla
+///a chunk with bigger block entities can't be serialized, and the data of such a chunk are rejected by deserialize
+pub const MAX_BLOCK_ENTITIES_SIZE: usize = 2 * 1024 * 1024;
+
+///the biggest payload is a native chunk with a tick for every block, a biome per cell and the biggest block entities
+///anything longer is corrupted, it also protects against zip bombs
+const MAX_PAYLOAD_SIZE: usize = 3 * 4
+    + 1
+    + Chunk::BLOCK_COUNT * 2
+    + 2
+    + Chunk::BLOCK_COUNT * 10
+    + 1
+    + BIOME_CELL_COUNT * (1 + MAX_BIOME_LEN)
+    + BIOME_CELL_COUNT
+    + 2
+    + MAX_BLOCK_ENTITIES_SIZE;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkCompression {
+    #[default]
+    None,
+    ///zlib with the default level, small chunks might end up bigger than without compression
+    Zlib,
+}
+
+impl ChunkCompression {
+    fn id(self) -> u8 {
+        match self {
+            ChunkCompression::None => 0,
+            ChunkCompression::Zlib => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(ChunkCompression::None),
+            1 => Some(ChunkCompression::Zlib),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ChunkDecodeError {
+    #[error("unexpected end of the chunk data")]
+    UnexpectedEnd,
+    #[error("{0} unexpected bytes after the chunk data")]
+    TrailingBytes(usize),
+    #[error("unsupported chunk format version {0}, expected at most {CHUNK_FORMAT_VERSION}")]
+    UnsupportedVersion(u8),
+    #[error("unknown compression {0}")]
+    UnknownCompression(u8),
+    #[error("unknown chunk format {0}")]
+    UnknownFormat(u8),
+    #[error("failed to decompress the chunk: {0}")]
+    Decompression(#[from] std::io::Error),
+    #[error("the chunk data are longer than the biggest chunk ({MAX_PAYLOAD_SIZE} bytes)")]
+    PayloadTooLarge,
+    #[error("the uniform chunk is filled with air")]
+    UniformAir,
+    #[error("the palette has {len} entries, the format allows at most {max}")]
+    PaletteTooLarge { len: usize, max: usize },
+    #[error("the blockState {0} is used by two palette entries")]
+    DuplicatePaletteEntry(u16),
+    #[error("the palette index {index} is out of the palette ({palette_len} entries)")]
+    InvalidPaletteIndex { index: u8, palette_len: usize },
+    #[error("the palette index {0} points to a free palette entry")]
+    FreePaletteEntry(u8),
+    #[error("the palette index {0} isn't used by any block")]
+    UnusedPaletteEntry(u8),
+    #[error("the scheduled tick is at the block index {0}, out of the chunk")]
+    InvalidTickIndex(u16),
+    #[error("the block at the index {0} has two scheduled ticks")]
+    DuplicateTick(u16),
+    #[error("invalid biome identifier: {0}")]
+    InvalidBiome(String),
+    #[error("the biome palette is empty, has duplicates or doesn't match the cells")]
+    InvalidBiomePalette,
+    #[error("the block entity is at the block index {0}, out of the chunk")]
+    InvalidBlockEntityIndex(u16),
+    #[error("the block at the index {0} has two block entities")]
+    DuplicateBlockEntity(u16),
+    #[error("invalid block entity type: {0}")]
+    InvalidBlockEntityType(String),
+    #[error("invalid block entity nbt: {0}")]
+    BlockEntityNbt(#[from] nbt::binary::Error),
+    #[error("the block entities take {size} bytes, the format allows at most {max}")]
+    BlockEntitiesTooLarge { size: usize, max: usize },
+}
+
+#[derive(Debug, Error)]
+pub enum ChunkEncodeError {
+    #[error("the block entities take {size} bytes, the format allows at most {max}")]
+    BlockEntitiesTooLarge { size: usize, max: usize },
+}
+
+///read the primitive types of the format, every read fails cleanly at the end of the data
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ChunkDecodeError> {
+        if self.data.len() < len {
+            return Err(ChunkDecodeError::UnexpectedEnd);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ChunkDecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ChunkDecodeError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, ChunkDecodeError> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChunkDecodeError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, ChunkDecodeError> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+impl Chunk {
+    ///encode the chunk in its current format, call compact before to get the smallest encoding
+    ///the scheduled ticks are saved as delays from the current tick like vanilla, so the tick counter of the world doesn't need to be saved with the chunks
+    ///fail if the block entities are bigger than MAX_BLOCK_ENTITIES_SIZE, so a saved chunk can always be loaded
+    pub fn serialize(
+        &self,
+        compression: ChunkCompression,
+        current_tick: u64,
+    ) -> Result<Vec<u8>, ChunkEncodeError> {
+        let mut payload = Vec::with_capacity(3 * 4 + 1 + Chunk::BLOCK_COUNT * 2);
+        for coord in self.position.to_array() {
+            payload.extend_from_slice(&coord.to_le_bytes());
+        }
+
+        match &self.handle {
+            ChunkHandle::ChunkEmpty => payload.push(FORMAT_EMPTY),
+            ChunkHandle::ChunkUniform(state) => {
+                payload.push(FORMAT_UNIFORM);
+                payload.extend_from_slice(&state.raw().to_le_bytes());
+            }
+            ChunkHandle::Chunk4bits(chunk) => {
+                payload.push(FORMAT_4BITS);
+                write_palette(&mut payload, chunk.palette());
+                payload.extend_from_slice(chunk.raw_blocks());
+            }
+            ChunkHandle::Chunk8bits(chunk) => {
+                payload.push(FORMAT_8BITS);
+                write_palette(&mut payload, chunk.palette());
+                payload.extend_from_slice(chunk.raw_blocks());
+            }
+            ChunkHandle::ChunkNative(chunk) => {
+                payload.push(FORMAT_NATIVE);
+                for state in chunk.blocks() {
+                    payload.extend_from_slice(&state.raw().to_le_bytes());
+                }
+            }
+        }
+
+        payload.extend_from_slice(&(self.ticks.len() as u16).to_le_bytes());
+        for (pos, due_tick) in self.ticks.iter() {
+            payload.extend_from_slice(&(block_index(pos) as u16).to_le_bytes());
+            let delay = due_tick.saturating_sub(current_tick); //an overdue tick runs at the next tick after the load
+            payload.extend_from_slice(&delay.to_le_bytes());
+        }
+
+        let biomes = self.biomes.palette();
+        payload.push(biomes.len() as u8);
+        for biome in biomes {
+            payload.push(biome.as_str().len() as u8); //ChunkBiomes ensures it fits
+            payload.extend_from_slice(biome.as_str().as_bytes());
+        }
+        if let Some(indices) = self.biomes.palette_indices() {
+            payload.extend_from_slice(indices);
+        }
+
+        payload.extend_from_slice(&(self.block_entities.len() as u16).to_le_bytes());
+        let block_entities_start = payload.len();
+        for (pos, block_entity) in self.block_entities.iter() {
+            let kind = block_entity.kind.as_str();
+            payload.extend_from_slice(&(block_index(pos) as u16).to_le_bytes());
+            payload.extend_from_slice(&(kind.len() as u16).to_le_bytes());
+            payload.extend_from_slice(kind.as_bytes());
+            let mut data = Vec::new();
+            nbt::to_binary(&block_entity.data, &mut data, "").expect("writing in a Vec can't fail");
+            payload.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&data);
+        }
+        let size = payload.len() - block_entities_start;
+        if size > MAX_BLOCK_ENTITIES_SIZE {
+            return Err(ChunkEncodeError::BlockEntitiesTooLarge {
+                size,
+                max: MAX_BLOCK_ENTITIES_SIZE,
+            });
+        }
+
+        let mut data = vec![CHUNK_FORMAT_VERSION, compression.id()];
+        match compression {
+            ChunkCompression::None => data.extend_from_slice(&payload),
+            ChunkCompression::Zlib => {
+                let mut encoder = ZlibEncoder::new(data, flate2::Compression::default());
+                encoder
+                    .write_all(&payload)
+                    .expect("writing in a Vec can't fail");
+                data = encoder.finish().expect("writing in a Vec can't fail");
+            }
+        }
+        Ok(data)
+    }
+
+    ///decode a chunk encoded by serialize, the data is fully validated so corrupted data return an error instead of a broken chunk
+    ///the scheduled ticks are due at their saved delay after the current tick
+    pub fn deserialize(data: &[u8], current_tick: u64) -> Result<Chunk, ChunkDecodeError> {
+        let mut reader = Reader { data };
+        let version = reader.u8()?;
+        if version == 0 || version > CHUNK_FORMAT_VERSION {
+            return Err(ChunkDecodeError::UnsupportedVersion(version));
+        }
+        let compression = reader.u8()?;
+        let compression = ChunkCompression::from_id(compression)
+            .ok_or(ChunkDecodeError::UnknownCompression(compression))?;
+
+        let decompressed;
+        let payload = match compression {
+            ChunkCompression::None => reader.data,
+            ChunkCompression::Zlib => {
+                let mut buffer = Vec::new();
+                ZlibDecoder::new(reader.data)
+                    .take(MAX_PAYLOAD_SIZE as u64 + 1)
+                    .read_to_end(&mut buffer)?;
+                decompressed = buffer;
+                &decompressed
+            }
+        };
+        //the same limit with both compressions, the zlib payload is only read up to the first byte over it
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(ChunkDecodeError::PayloadTooLarge);
+        }
+
+        let mut reader = Reader { data: payload };
+        let position = ChunkPos::new(reader.i32()?, reader.i32()?, reader.i32()?);
+        let handle = match reader.u8()? {
+            FORMAT_EMPTY => ChunkHandle::ChunkEmpty,
+            FORMAT_UNIFORM => match BlockState::from_raw(reader.u16()?) {
+                AIR => return Err(ChunkDecodeError::UniformAir),
+                state => ChunkHandle::ChunkUniform(state),
+            },
+            FORMAT_4BITS => {
+                let palette = read_palette(&mut reader, Chunk4Bits::PALETTE_SIZE)?;
+                let blocks = reader.bytes(Chunk::BLOCK_COUNT / 2)?;
+                let palette_indices = blocks.iter().flat_map(|block| [block & 0b1111, block >> 4]);
+                check_palette_indices(&palette, palette_indices)?;
+                let chunk = Chunk4Bits::from_raw_parts(&palette, blocks);
+                ChunkHandle::Chunk4bits(Arc::new(MEMORY_MANAGER.chunks4bits.alloc(chunk)))
+            }
+            FORMAT_8BITS => {
+                let palette = read_palette(&mut reader, Chunk8Bits::PALETTE_SIZE)?;
+                let blocks = reader.bytes(Chunk::BLOCK_COUNT)?;
+                check_palette_indices(&palette, blocks.iter().copied())?;
+                let chunk = Chunk8Bits::from_raw_parts(&palette, blocks);
+                ChunkHandle::Chunk8bits(Arc::new(MEMORY_MANAGER.chunks8bits.alloc(chunk)))
+            }
+            FORMAT_NATIVE => {
+                let mut blocks = Vec::with_capacity(Chunk::BLOCK_COUNT);
+                for _ in 0..Chunk::BLOCK_COUNT {
+                    blocks.push(BlockState::from_raw(reader.u16()?));
+                }
+                let chunk = ChunkNative::from_blocks(&blocks);
+                ChunkHandle::ChunkNative(Arc::new(MEMORY_MANAGER.chunks_native.alloc(chunk)))
+            }
+            format => return Err(ChunkDecodeError::UnknownFormat(format)),
+        };
+        let mut chunk = Chunk::new(position);
+        chunk.set_handle(handle);
+
+        //the version 1 doesn't have scheduled ticks
+        if version >= 2 {
+            let tick_count = reader.u16()?;
+            for order in 0..tick_count as u64 {
+                let index = reader.u16()?;
+                let delay = reader.u64()?;
+                if index as usize >= Chunk::BLOCK_COUNT {
+                    return Err(ChunkDecodeError::InvalidTickIndex(index));
+                }
+                let pos = block_pos_of(index as usize);
+                if chunk.ticks.get(pos).is_some() {
+                    return Err(ChunkDecodeError::DuplicateTick(index));
+                }
+                chunk
+                    .ticks
+                    .schedule(pos, current_tick.saturating_add(delay), order);
+            }
+        }
+        //the older versions don't have biomes
+        if version >= 3 {
+            chunk.biomes = read_biomes(&mut reader)?;
+        }
+        if version >= 4 {
+            read_block_entities(&mut reader, &mut chunk)?;
+        }
+        if !reader.data.is_empty() {
+            return Err(ChunkDecodeError::TrailingBytes(reader.data.len()));
+        }
+        Ok(chunk)
+    }
+}
+
+///write the palette without the free entries at its end
+fn write_palette(payload: &mut Vec<u8>, palette: &[BlockState]) {
+    let len = palette
+        .iter()
+        .rposition(|state| *state != AIR)
+        .map_or(0, |last| last + 1);
+    payload.push(len as u8);
+    for state in &palette[..len] {
+        payload.extend_from_slice(&state.raw().to_le_bytes());
+    }
+}
+
+fn read_palette(reader: &mut Reader, max: usize) -> Result<Vec<BlockState>, ChunkDecodeError> {
+    let len = reader.u8()? as usize;
+    if len > max {
+        return Err(ChunkDecodeError::PaletteTooLarge { len, max });
+    }
+    let mut palette = Vec::with_capacity(len);
+    for _ in 0..len {
+        let state = BlockState::from_raw(reader.u16()?);
+        if state != AIR && palette.contains(&state) {
+            return Err(ChunkDecodeError::DuplicatePaletteEntry(state.raw()));
+        }
+        palette.push(state);
+    }
+    Ok(palette)
+}
+
+fn read_biomes(reader: &mut Reader) -> Result<ChunkBiomes, ChunkDecodeError> {
+    let len = reader.u8()?;
+    let mut palette = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        let name_len = reader.u8()? as usize;
+        let name = reader.bytes(name_len)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        match Biome::try_from(name.as_str()) {
+            Ok(biome) => palette.push(biome),
+            Err(_) => return Err(ChunkDecodeError::InvalidBiome(name)),
+        }
+    }
+    let indices = match len {
+        0 | 1 => None,
+        _ => Some(reader.bytes(BIOME_CELL_COUNT)?.try_into().unwrap()),
+    };
+    ChunkBiomes::from_palette(palette, indices).ok_or(ChunkDecodeError::InvalidBiomePalette)
+}
+
+fn read_block_entities(reader: &mut Reader, chunk: &mut Chunk) -> Result<(), ChunkDecodeError> {
+    let count = reader.u16()?;
+    let start_len = reader.data.len();
+    for _ in 0..count {
+        let index = reader.u16()?;
+        if index as usize >= Chunk::BLOCK_COUNT {
+            return Err(ChunkDecodeError::InvalidBlockEntityIndex(index));
+        }
+        let kind_len = reader.u16()? as usize;
+        let kind = String::from_utf8_lossy(reader.bytes(kind_len)?).into_owned();
+        let kind = match Ident::try_from(kind.as_str()) {
+            Ok(kind) => kind,
+            Err(_) => return Err(ChunkDecodeError::InvalidBlockEntityType(kind)),
+        };
+        let data_len = reader.u32()? as usize;
+        let mut nbt = reader.bytes(data_len)?;
+        let (data, _) = nbt::from_binary::<String>(&mut nbt)?;
+        if !nbt.is_empty() {
+            return Err(ChunkDecodeError::TrailingBytes(nbt.len()));
+        }
+
+        let pos = block_pos_of(index as usize);
+        if chunk.block_entities.get(pos).is_some() {
+            return Err(ChunkDecodeError::DuplicateBlockEntity(index));
+        }
+        chunk
+            .block_entities
+            .insert(pos, BlockEntity::new(kind, data));
+    }
+    let size = start_len - reader.data.len();
+    if size > MAX_BLOCK_ENTITIES_SIZE {
+        return Err(ChunkDecodeError::BlockEntitiesTooLarge {
+            size,
+            max: MAX_BLOCK_ENTITIES_SIZE,
+        });
+    }
+    Ok(())
+}
+
+///every palette index must point to a used entry, and every used entry must be referenced, like in memory
+fn check_palette_indices(
+    palette: &[BlockState],
+    palette_indices: impl Iterator<Item = u8>,
+) -> Result<(), ChunkDecodeError> {
+    let mut referenced = vec![false; palette.len()];
+    for index in palette_indices.filter(|index| *index != 0) {
+        let entry = index as usize - 1; // -1 because 0 is air
+        match palette.get(entry) {
+            None => {
+                return Err(ChunkDecodeError::InvalidPaletteIndex {
+                    index,
+                    palette_len: palette.len(),
+                })
+            }
+            Some(&AIR) => return Err(ChunkDecodeError::FreePaletteEntry(index)),
+            Some(_) => referenced[entry] = true,
+        }
+    }
+
+    let unused = (0..palette.len()).find(|entry| palette[*entry] != AIR && !referenced[*entry]);
+    match unused {
+        Some(entry) => Err(ChunkDecodeError::UnusedPaletteEntry(entry as u8 + 1)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::biome::DEFAULT_BIOME;
+    use ident::ident;
+    use math::positions::BlockPos;
+    use nbt::{compound, Compound};
+
+    fn assert_same_blocks(a: &Chunk, b: &Chunk) {
+        assert_eq!(a.position(), b.position());
+        for z in 0..Chunk::SIZE {
+            for y in 0..Chunk::SIZE {
+                for x in 0..Chunk::SIZE {
+                    assert_eq!(a.get_block_at(x, y, z), b.get_block_at(x, y, z));
+                }
+            }
+        }
+    }
+
+    ///a chunk with the given number of different blockStates, each one used several times
+    fn chunk_with_states(state_count: u16) -> Chunk {
+        let mut chunk = Chunk::new(ChunkPos::new(3, -7, 12));
+        for i in 0..state_count as i32 * 3 {
+            let pos = BlockPos::new(i % 16, (i / 16) % 16, i / 256);
+            chunk.set_block(
+                pos,
+                BlockState::from_raw((i % state_count as i32) as u16 + 1),
+            );
+        }
+        chunk
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let chunks = [
+            Chunk::new(ChunkPos::new(-1, 2, 3)),
+            Chunk::new_uniform(ChunkPos::new(0, -4, 0), BlockState::from_raw(42)),
+            chunk_with_states(10),
+            chunk_with_states(200),
+            chunk_with_states(1000),
+        ];
+        for chunk in &chunks {
+            for compression in [ChunkCompression::None, ChunkCompression::Zlib] {
+                let data = chunk.serialize(compression, 0).unwrap();
+                let decoded = Chunk::deserialize(&data, 0).unwrap();
+                assert_eq!(
+                    std::mem::discriminant(&decoded.handle),
+                    std::mem::discriminant(&chunk.handle)
+                );
+                assert_same_blocks(chunk, &decoded);
+            }
+        }
+    }
+
+    #[test]
+    pub fn round_trip_keeps_ref_counts() {
+        //free some palette entries in the middle, the decoded chunk must be able to reuse them
+        let mut chunk = chunk_with_states(10);
+        for i in 0..30 {
+            let pos = BlockPos::new(i % 16, (i / 16) % 16, i / 256);
+            if i % 10 == 4 {
+                chunk.set_block(pos, AIR);
+            }
+        }
+        let mut decoded =
+            Chunk::deserialize(&chunk.serialize(ChunkCompression::None, 0).unwrap(), 0).unwrap();
+        assert_same_blocks(&chunk, &decoded);
+
+        for i in 0..6 {
+            decoded.set_block_at(15, 15, i, BlockState::from_raw(500 + i as u16));
+        }
+        assert!(matches!(decoded.handle, ChunkHandle::Chunk4bits(_)));
+        for i in 0..30 {
+            let pos = BlockPos::new(i % 16, (i / 16) % 16, i / 256);
+            decoded.set_block(pos, AIR);
+        }
+        for i in 0..6 {
+            decoded.set_block_at(15, 15, i, AIR);
+        }
+        assert!(decoded.compact());
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    pub fn reject_corrupted_data() {
+        let data = chunk_with_states(10)
+            .serialize(ChunkCompression::None, 0)
+            .unwrap();
+        //header, position, format then the palette length
+        let palette_len_offset = 2 + 12 + 1;
+        let blocks_offset = palette_len_offset + 1 + 10 * 2;
+
+        assert!(matches!(
+            Chunk::deserialize(&data[..data.len() - 1], 0),
+            Err(ChunkDecodeError::UnexpectedEnd)
+        ));
+
+        let mut corrupted = data.clone();
+        corrupted.push(0);
+        assert!(matches!(
+            Chunk::deserialize(&corrupted, 0),
+            Err(ChunkDecodeError::TrailingBytes(1))
+        ));
+
+        //an uncompressed payload has the same limit as a decompressed one
+        let mut oversized = data.clone();
+        oversized.resize(2 + MAX_PAYLOAD_SIZE + 1, 0);
+        assert!(matches!(
+            Chunk::deserialize(&oversized, 0),
+            Err(ChunkDecodeError::PayloadTooLarge)
+        ));
+
+        let mut corrupted = data.clone();
+        corrupted[0] = CHUNK_FORMAT_VERSION + 1;
+        assert!(matches!(
+            Chunk::deserialize(&corrupted, 0),
+            Err(ChunkDecodeError::UnsupportedVersion(_))
+        ));
+
+        let mut corrupted = data.clone();
+        corrupted[blocks_offset] = 0x0C; //palette index 12 for a palette of 10 entries
+        assert!(matches!(
+            Chunk::deserialize(&corrupted, 0),
+            Err(ChunkDecodeError::InvalidPaletteIndex { index: 12, .. })
+        ));
+
+        let mut corrupted = data.clone();
+        corrupted[palette_len_offset] = 16;
+        assert!(matches!(
+            Chunk::deserialize(&corrupted, 0),
+            Err(ChunkDecodeError::PaletteTooLarge { len: 16, max: 15 })
+        ));
+
+        let mut corrupted = data.clone();
+        corrupted[palette_len_offset + 3..palette_len_offset + 5].copy_from_slice(&[1, 0]);
+        assert!(matches!(
+            Chunk::deserialize(&corrupted, 0),
+            Err(ChunkDecodeError::DuplicatePaletteEntry(1))
+        ));
+
+        let mut corrupted = data;
+        corrupted[palette_len_offset + 1..palette_len_offset + 3].copy_from_slice(&[0, 0]);
+        assert!(matches!(
+            Chunk::deserialize(&corrupted, 0),
+            Err(ChunkDecodeError::FreePaletteEntry(1))
+        ));
+
+        let zlib = chunk_with_states(10)
+            .serialize(ChunkCompression::Zlib, 0)
+            .unwrap();
+        assert!(Chunk::deserialize(&zlib[..zlib.len() / 2], 0).is_err());
+    }
+
+    #[test]
+    pub fn scheduled_ticks() {
+        let mut chunk = chunk_with_states(10);
+        chunk.ticks.schedule(BlockPos::new(1, 2, 3), 40, 0);
+        chunk.ticks.schedule(BlockPos::new(15, 15, 15), 20, 1);
+        //saved at the tick 15 and loaded at the tick 100, the delays are kept
+        let data = chunk.serialize(ChunkCompression::Zlib, 15).unwrap();
+        let decoded = Chunk::deserialize(&data, 100).unwrap();
+        assert_same_blocks(&chunk, &decoded);
+        assert_eq!(
+            decoded.scheduled_ticks().iter().collect::<Vec<_>>(),
+            vec![
+                (BlockPos::new(15, 15, 15), 105),
+                (BlockPos::new(1, 2, 3), 125)
+            ]
+        );
+
+        //the version 1 ends after the blocks
+        let tail_len = 1 + 1 + DEFAULT_BIOME.as_str().len() + 2; //the biomes and no block entity
+        let mut data = chunk.serialize(ChunkCompression::None, 0).unwrap();
+        data.truncate(data.len() - tail_len);
+        data[0] = 1;
+        assert!(matches!(
+            Chunk::deserialize(&data, 0),
+            Err(ChunkDecodeError::TrailingBytes(22))
+        ));
+        data.truncate(data.len() - 22);
+        let decoded = Chunk::deserialize(&data, 0).unwrap();
+        assert_same_blocks(&chunk, &decoded);
+        assert!(decoded.scheduled_ticks().is_empty());
+
+        //both ticks on the same block
+        let mut data = chunk.serialize(ChunkCompression::None, 0).unwrap();
+        let len = data.len() - tail_len;
+        data.copy_within(len - 20..len - 18, len - 10);
+        assert!(matches!(
+            Chunk::deserialize(&data, 0),
+            Err(ChunkDecodeError::DuplicateTick(_))
+        ));
+    }
+
+    #[test]
+    pub fn biomes() {
+        let mut chunk = chunk_with_states(10);
+        let desert: Biome = ident!("minecraft:desert").into();
+        chunk.set_biome(BlockPos::new(15, 0, 4), desert.clone());
+        let decoded =
+            Chunk::deserialize(&chunk.serialize(ChunkCompression::Zlib, 0).unwrap(), 0).unwrap();
+        assert_eq!(*decoded.get_biome(BlockPos::new(12, 3, 7)), desert);
+        assert_eq!(*decoded.get_biome(BlockPos::new(0, 0, 0)), DEFAULT_BIOME);
+
+        //a palette index out of the palette, before the block entity count
+        let mut data = chunk.serialize(ChunkCompression::None, 0).unwrap();
+        let last = data.len() - 3;
+        data[last] = 2;
+        assert!(matches!(
+            Chunk::deserialize(&data, 0),
+            Err(ChunkDecodeError::InvalidBiomePalette)
+        ));
+    }
+
+    #[test]
+    pub fn block_entities() {
+        let mut chunk = chunk_with_states(10);
+        let sign = BlockEntity::new(
+            ident!("minecraft:sign").into(),
+            compound! { "front_text" => compound! { "color" => "black" } },
+        );
+        chunk.set_block_entity(BlockPos::new(1, 0, 0), sign.clone());
+        chunk.set_block_entity(
+            BlockPos::new(2, 0, 0),
+            BlockEntity::new(ident!("minecraft:chest").into(), Compound::new()),
+        );
+        let decoded =
+            Chunk::deserialize(&chunk.serialize(ChunkCompression::Zlib, 0).unwrap(), 0).unwrap();
+        assert_eq!(decoded.block_entities().len(), 2);
+        assert_eq!(
+            decoded.get_block_entity(BlockPos::new(1, 0, 0)),
+            Some(&sign)
+        );
+
+        //the block entity is removed with its block
+        chunk.set_block(BlockPos::new(1, 0, 0), BlockState::from_raw(500));
+        assert!(chunk.get_block_entity(BlockPos::new(1, 0, 0)).is_none());
+        let mut blocks = chunk.blocks();
+        blocks[2] = AIR;
+        chunk.set_blocks(&blocks);
+        assert!(chunk.block_entities().is_empty());
+    }
+
+    #[test]
+    pub fn block_entities_size_limit() {
+        let chest = |len: usize| {
+            BlockEntity::new(
+                ident!("minecraft:chest").into(),
+                compound! { "items" => vec![0i8; len] },
+            )
+        };
+        //the index, the type and the nbt length come before the nbt
+        let mut nbt = Vec::new();
+        nbt::to_binary(&chest(0).data, &mut nbt, "").unwrap();
+        let overhead = 2 + 2 + "minecraft:chest".len() + 4 + nbt.len();
+
+        let mut chunk = chunk_with_states(10);
+        chunk.set_block_entity(BlockPos::ZERO, chest(MAX_BLOCK_ENTITIES_SIZE - overhead));
+        for compression in [ChunkCompression::None, ChunkCompression::Zlib] {
+            let decoded = Chunk::deserialize(&chunk.serialize(compression, 0).unwrap(), 0).unwrap();
+            assert_eq!(
+                decoded.get_block_entity(BlockPos::ZERO),
+                chunk.get_block_entity(BlockPos::ZERO)
+            );
+        }
+
+        chunk.set_block_entity(
+            BlockPos::ZERO,
+            chest(MAX_BLOCK_ENTITIES_SIZE - overhead + 1),
+        );
+        assert!(matches!(
+            chunk.serialize(ChunkCompression::Zlib, 0),
+            Err(ChunkEncodeError::BlockEntitiesTooLarge { size, .. }) if size == MAX_BLOCK_ENTITIES_SIZE + 1
+        ));
+    }
+}
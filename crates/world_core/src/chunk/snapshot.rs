@@ -0,0 +1,84 @@
+use crate::chunk::{Chunk, ChunkHandle};
+use std::ops::Deref;
+use std::sync::Arc;
+
+///an immutable copy of a chunk that can be sent to another thread, to mesh, save or encode it while the chunk keeps changing
+///the blocks, the light and the heightmaps are shared with the chunk, they are only copied when the chunk is modified afterward
+///cloning a snapshot is just a reference count increment
+#[derive(Clone)]
+pub struct ChunkSnapshot(Arc<Chunk>);
+
+impl Deref for ChunkSnapshot {
+    type Target = Chunk;
+
+    fn deref(&self) -> &Chunk {
+        &self.0
+    }
+}
+
+impl Chunk {
+    ///take a snapshot of the chunk in its current state, nothing is copied until the chunk is modified
+    pub fn snapshot(&self) -> ChunkSnapshot {
+        let mut chunk = Chunk::new(self.position);
+        chunk.set_handle(self.handle.clone()); //keep the uniform chunk count right
+        chunk.light = self.light.clone();
+        chunk.heightmaps = self.heightmaps.clone();
+        chunk.ticks = self.ticks.clone();
+        chunk.biomes = self.biomes.clone();
+        chunk.block_entities = self.block_entities.clone();
+        ChunkSnapshot(Arc::new(chunk))
+    }
+
+    ///return true if the blocks of both chunks are stored in the same memory, like a chunk and its snapshot before any modification
+    pub fn shares_blocks_with(&self, other: &Chunk) -> bool {
+        match (&self.handle, &other.handle) {
+            (ChunkHandle::ChunkNative(a), ChunkHandle::ChunkNative(b)) => Arc::ptr_eq(a, b),
+            (ChunkHandle::Chunk8bits(a), ChunkHandle::Chunk8bits(b)) => Arc::ptr_eq(a, b),
+            (ChunkHandle::Chunk4bits(a), ChunkHandle::Chunk4bits(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::{BlockState, AIR};
+    use crate::light::LightChannel;
+    use math::positions::{BlockPos, ChunkPos};
+
+    #[test]
+    pub fn copy_on_write() {
+        let stone = BlockState::from_raw(1);
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        chunk.set_block_at(1, 2, 3, stone);
+        chunk
+            .light_mut()
+            .set(LightChannel::Block, BlockPos::new(1, 2, 3), 7);
+
+        let snapshot = chunk.snapshot();
+        assert!(chunk.shares_blocks_with(&snapshot));
+
+        //the first write copies the storage, the snapshot keeps the old blocks
+        chunk.set_block_at(1, 2, 3, AIR);
+        chunk
+            .light_mut()
+            .set(LightChannel::Block, BlockPos::new(1, 2, 3), 0);
+        assert!(!chunk.shares_blocks_with(&snapshot));
+        assert_eq!(snapshot.get_block_at(1, 2, 3), stone);
+        assert_eq!(
+            snapshot
+                .light()
+                .get(LightChannel::Block, BlockPos::new(1, 2, 3)),
+            7
+        );
+        assert_eq!(chunk.get_block_at(1, 2, 3), AIR);
+
+        //the snapshot can be read from another thread
+        let snapshot_clone = snapshot.clone();
+        let count = std::thread::spawn(move || snapshot_clone.iter_non_air_blocks().count())
+            .join()
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::biome::Biome;
+use crate::{Chunk, ChunkManager};
+use math::positions::BlockPos;
+use math::IVec3;
+
+impl ChunkManager {
+    ///get the biome at the given block position, None if its chunk isn't loaded
+    pub fn get_biome(&self, pos: BlockPos) -> Option<&Biome> {
+        let chunk = self.get_chunk(pos.div_euclid(IVec3::splat(Chunk::SIZE)))?;
+        Some(chunk.get_biome(pos.rem_euclid(IVec3::splat(Chunk::SIZE))))
+    }
+
+    ///set the biome of the 4x4x4 cell containing the given block position, see ChunkGuard::set_biome
+    ///return false if the chunk isn't loaded or if the biome didn't change
+    pub fn set_biome(&mut self, pos: BlockPos, biome: Biome) -> bool {
+        let Some(mut chunk) = self.get_chunk_mut(pos.div_euclid(IVec3::splat(Chunk::SIZE))) else {
+            return false;
+        };
+        chunk.set_biome(pos.rem_euclid(IVec3::splat(Chunk::SIZE)), biome)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ident::ident;
+    use math::positions::ChunkPos;
+
+    #[test]
+    pub fn set_biome() {
+        let desert: Biome = ident!("minecraft:desert").into();
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(-1, 0, 0)));
+        chunk_manager.on_process_modified_chunks(|_, _| ());
+
+        assert!(chunk_manager.set_biome(BlockPos::new(-5, 3, 0), desert.clone()));
+        assert!(!chunk_manager.set_biome(BlockPos::new(-8, 0, 3), desert.clone())); //same cell
+        assert!(!chunk_manager.set_biome(BlockPos::new(5, 0, 0), desert.clone())); //not loaded
+        assert_eq!(
+            chunk_manager.get_biome(BlockPos::new(-6, 2, 1)),
+            Some(&desert)
+        );
+        assert_ne!(
+            chunk_manager.get_biome(BlockPos::new(-4, 2, 1)),
+            Some(&desert)
+        );
+        assert_eq!(chunk_manager.get_biome(BlockPos::new(5, 0, 0)), None);
+
+        let mut modified = 0;
+        chunk_manager.on_process_modified_chunks(|ids, _| modified = ids.len());
+        assert_eq!(modified, 1);
+    }
+}
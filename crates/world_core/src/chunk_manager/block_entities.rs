@@ -0,0 +1,108 @@
+use crate::block_entity::BlockEntity;
+use crate::{Chunk, ChunkManager};
+use math::positions::BlockPos;
+use math::IVec3;
+
+impl ChunkManager {
+    ///get the block entity at the given block position, None if there is none or if its chunk isn't loaded
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        let chunk = self.get_chunk(pos.div_euclid(IVec3::splat(Chunk::SIZE)))?;
+        chunk.get_block_entity(pos.rem_euclid(IVec3::splat(Chunk::SIZE)))
+    }
+
+    ///attach a block entity to the block at the given position, the chunk is marked as modified like for a block change, see ChunkGuard::set_block_entity
+    ///return false if the chunk isn't loaded
+    pub fn set_block_entity(&mut self, pos: BlockPos, block_entity: BlockEntity) -> bool {
+        let Some(mut chunk) = self.get_chunk_mut(pos.div_euclid(IVec3::splat(Chunk::SIZE))) else {
+            return false;
+        };
+        chunk.set_block_entity(pos.rem_euclid(IVec3::splat(Chunk::SIZE)), block_entity);
+        true
+    }
+
+    pub fn remove_block_entity(&mut self, pos: BlockPos) -> Option<BlockEntity> {
+        let mut chunk = self.get_chunk_mut(pos.div_euclid(IVec3::splat(Chunk::SIZE)))?;
+        chunk.remove_block_entity(pos.rem_euclid(IVec3::splat(Chunk::SIZE)))
+    }
+
+    ///modify the block entity at the given position, the chunk is marked as modified
+    ///return false if there is no block entity at this position
+    pub fn update_block_entity(
+        &mut self,
+        pos: BlockPos,
+        func: impl FnOnce(&mut BlockEntity),
+    ) -> bool {
+        let Some(mut chunk) = self.get_chunk_mut(pos.div_euclid(IVec3::splat(Chunk::SIZE))) else {
+            return false;
+        };
+        match chunk.get_block_entity_mut(pos.rem_euclid(IVec3::splat(Chunk::SIZE))) {
+            Some(block_entity) => {
+                func(block_entity);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::{BlockState, AIR};
+    use crate::ChunkUpdate;
+    use ident::ident;
+    use math::positions::ChunkPos;
+    use nbt::compound;
+
+    #[test]
+    pub fn block_entities() {
+        let chest = BlockState::from_raw(1);
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(0, -1, 0)));
+        chunk_manager.on_process_modified_chunks(|_, _| ());
+
+        let pos = BlockPos::new(3, -4, 5);
+        let block_entity = BlockEntity::new(
+            ident!("minecraft:chest").into(),
+            compound! { "Lock" => "key" },
+        );
+        chunk_manager
+            .get_chunk_mut(ChunkPos::new(0, -1, 0))
+            .unwrap()
+            .set_block(BlockPos::new(3, 12, 5), chest);
+        assert!(chunk_manager.set_block_entity(pos, block_entity.clone()));
+        assert!(!chunk_manager.set_block_entity(BlockPos::new(3, 4, 5), block_entity.clone())); //not loaded
+        assert!(chunk_manager.update_block_entity(pos, |block_entity| {
+            block_entity.data.insert("CustomName", "loot");
+        }));
+        assert_eq!(
+            chunk_manager
+                .get_block_entity(pos)
+                .unwrap()
+                .data
+                .get("CustomName"),
+            Some(&"loot".into())
+        );
+
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 1);
+            let (changes, update) = journal.updates(64).next().unwrap();
+            assert!(matches!(update, ChunkUpdate::Delta(_)));
+            assert_eq!(changes.block_entity_changes(), &[BlockPos::new(3, 12, 5)]);
+        });
+
+        //replacing the block removes its block entity, and the removal is recorded
+        let mut chunk = chunk_manager
+            .get_chunk_mut(ChunkPos::new(0, -1, 0))
+            .unwrap();
+        chunk.set_block(BlockPos::new(3, 12, 5), chest);
+        assert!(chunk.get_block_entity(BlockPos::new(3, 12, 5)).is_some());
+        chunk.set_block(BlockPos::new(3, 12, 5), AIR);
+        assert!(chunk.get_block_entity(BlockPos::new(3, 12, 5)).is_none());
+        chunk_manager.on_process_modified_chunks(|_, journal| {
+            let changes = &journal.chunks()[0];
+            assert_eq!(changes.block_entity_changes(), &[BlockPos::new(3, 12, 5)]);
+        });
+        assert!(chunk_manager.remove_block_entity(pos).is_none());
+    }
+}
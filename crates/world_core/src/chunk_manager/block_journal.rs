@@ -0,0 +1,170 @@
+use crate::chunk_manager::BlockChange;
+use crate::Chunk;
+use math::positions::{BlockPos, ChunkPos};
+use std::collections::HashMap;
+use utils::spare_set::Id;
+
+///what should be sent to refresh a chunk, see BlockJournal::updates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkUpdate<'a> {
+    ///the whole chunk should be sent again
+    Full,
+    ///only these blocks changed, like the multi block change packet of vanilla, the changed block entities are in ChunkChanges::block_entity_changes
+    Delta(&'a [BlockChange]),
+}
+
+///the block changes of a chunk during a tick, each block appears only once
+pub struct ChunkChanges {
+    chunk_id: Id,
+    chunk_pos: ChunkPos,
+    full_resend: bool, //the chunk was inserted or marked with make_dirty, the block changes don't describe the whole modification
+    changes: Vec<BlockChange>,
+    block_index: HashMap<u16, usize>, //index in changes of each changed block, by linear position in the chunk
+    block_entities: Vec<BlockPos>, //the blocks whose block entity was set, modified or removed, each one only once
+}
+
+impl ChunkChanges {
+    fn new(chunk_id: Id, chunk_pos: ChunkPos) -> Self {
+        Self {
+            chunk_id,
+            chunk_pos,
+            full_resend: false,
+            changes: Vec::new(),
+            block_index: HashMap::new(),
+            block_entities: Vec::new(),
+        }
+    }
+
+    pub fn chunk_id(&self) -> Id {
+        self.chunk_id
+    }
+
+    pub fn chunk_pos(&self) -> ChunkPos {
+        self.chunk_pos
+    }
+
+    ///return true if the chunk was modified without going through a ChunkGuard, in this case it must be entirely refreshed
+    pub fn needs_full_resend(&self) -> bool {
+        self.full_resend
+    }
+
+    ///the changed blocks, the old state is the state at the beginning of the tick and the new state is the last one
+    pub fn changes(&self) -> &[BlockChange] {
+        &self.changes
+    }
+
+    ///the position in the chunk of the blocks whose block entity was set, modified or removed, like the block entity data packet of vanilla
+    pub fn block_entity_changes(&self) -> &[BlockPos] {
+        &self.block_entities
+    }
+
+    fn record(&mut self, change: BlockChange) {
+        let linear_pos = linear_pos(change.pos);
+        match self.block_index.get(&linear_pos) {
+            Some(index) => self.changes[*index].new_state = change.new_state, //keep the state of the beginning of the tick
+            None => {
+                self.block_index.insert(linear_pos, self.changes.len());
+                self.changes.push(change);
+            }
+        }
+    }
+
+    ///drop the blocks that went back to their first state
+    fn coalesce(&mut self) {
+        self.changes
+            .retain(|change| change.old_state != change.new_state);
+        self.block_index.clear();
+        for (index, change) in self.changes.iter().enumerate() {
+            self.block_index.insert(linear_pos(change.pos), index);
+        }
+    }
+}
+
+fn linear_pos(pos: BlockPos) -> u16 {
+    (pos.x + pos.y * Chunk::SIZE + pos.z * Chunk::SIZE * Chunk::SIZE) as u16
+}
+
+///the block changes of the tick grouped by chunk, it's filled by the ChunkGuards and given by ChunkManager::on_process_modified_chunks
+///the same block changed several times is only reported once, with its first and last state
+#[derive(Default)]
+pub struct BlockJournal {
+    chunks: Vec<ChunkChanges>,
+    chunk_index: HashMap<Id, usize>, //index in chunks of each chunk
+}
+
+impl BlockJournal {
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    ///the modified chunks in the order of their first modification
+    pub fn chunks(&self) -> &[ChunkChanges] {
+        &self.chunks
+    }
+
+    pub fn get(&self, chunk_id: Id) -> Option<&ChunkChanges> {
+        self.chunk_index
+            .get(&chunk_id)
+            .map(|index| &self.chunks[*index])
+    }
+
+    ///choose how to refresh each modified chunk, a chunk with more changed blocks than the threshold is sent again entirely
+    ///the chunks where every block went back to its first state are skipped
+    pub fn updates(
+        &self,
+        full_resend_threshold: usize,
+    ) -> impl Iterator<Item = (&ChunkChanges, ChunkUpdate<'_>)> {
+        self.chunks.iter().filter_map(move |chunk| {
+            let update = if chunk.full_resend || chunk.changes.len() > full_resend_threshold {
+                ChunkUpdate::Full
+            } else if chunk.changes.is_empty() && chunk.block_entities.is_empty() {
+                return None;
+            } else {
+                ChunkUpdate::Delta(&chunk.changes)
+            };
+            Some((chunk, update))
+        })
+    }
+
+    fn chunk_mut(&mut self, chunk_id: Id, chunk_pos: ChunkPos) -> &mut ChunkChanges {
+        let chunks = &mut self.chunks;
+        let index = *self.chunk_index.entry(chunk_id).or_insert_with(|| {
+            chunks.push(ChunkChanges::new(chunk_id, chunk_pos));
+            chunks.len() - 1
+        });
+        &mut self.chunks[index]
+    }
+
+    pub(super) fn record(&mut self, change: BlockChange) {
+        self.chunk_mut(change.chunk_id, change.chunk_pos)
+            .record(change);
+    }
+
+    pub(super) fn record_block_entity(&mut self, chunk_id: Id, chunk_pos: ChunkPos, pos: BlockPos) {
+        let chunk = self.chunk_mut(chunk_id, chunk_pos);
+        if !chunk.block_entities.contains(&pos) {
+            chunk.block_entities.push(pos);
+        }
+    }
+
+    pub(super) fn mark_full_resend(&mut self, chunk_id: Id, chunk_pos: ChunkPos) {
+        self.chunk_mut(chunk_id, chunk_pos).full_resend = true;
+    }
+
+    pub(super) fn coalesce(&mut self) {
+        self.chunks.iter_mut().for_each(ChunkChanges::coalesce);
+    }
+
+    pub(super) fn forget(&mut self, is_removed: impl Fn(Id) -> bool) {
+        self.chunks.retain(|chunk| !is_removed(chunk.chunk_id));
+        self.chunk_index.clear();
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            self.chunk_index.insert(chunk.chunk_id, index);
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.chunks.clear();
+        self.chunk_index.clear();
+    }
+}
@@ -0,0 +1,175 @@
+use crate::biome::Biome;
+use crate::block_entity::BlockEntity;
+use crate::block_state::BlockState;
+use crate::chunk_manager::light_engine::LightQueue;
+use crate::chunk_manager::BlockJournal;
+use crate::heightmap::{ChunkHeightmaps, HeightmapRules};
+use crate::Chunk;
+use math::positions::{BlockPos, ChunkPos};
+use std::ops::Deref;
+use utils::spare_set::Id;
+
+///a block modified through a ChunkGuard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockChange {
+    pub chunk_id: Id,
+    pub chunk_pos: ChunkPos,
+    ///the position of the block in the chunk
+    pub pos: BlockPos,
+    pub old_state: BlockState,
+    pub new_state: BlockState,
+}
+
+///the modifications made to the world since the last call to ChunkManager::on_process_modified_chunks
+#[derive(Default)]
+pub(super) struct ChangeTracker {
+    pub(super) chunk_modified: Vec<Id>,
+    pub(super) journal: BlockJournal,
+    pub(super) light: LightQueue, //consumed by ChunkManager::update_light, not by on_process_modified_chunks
+}
+
+impl ChangeTracker {
+    ///a removed chunk shouldn't be reported as modified, its id could already belong to another chunk
+    pub(super) fn forget(&mut self, is_removed: impl Fn(Id) -> bool) {
+        self.chunk_modified.retain(|id| !is_removed(*id));
+        self.journal.forget(is_removed);
+    }
+}
+
+///mutable access to a chunk of the ChunkManager, the chunk is marked as modified only if a block actually changes
+///every changed block is recorded in the BlockJournal, so the consumers of the modified chunks can see the exact edit
+///reading the chunk goes through Deref, there is no DerefMut so an edit can't be missed
+pub struct ChunkGuard<'a> {
+    chunk: &'a mut Chunk,
+    id: Id,
+    tracker: &'a mut ChangeTracker,
+    heightmap_rules: &'a HeightmapRules,
+    dirty: bool, //the id is pushed only once per guard
+}
+
+impl<'a> ChunkGuard<'a> {
+    pub(super) fn new(
+        chunk: &'a mut Chunk,
+        id: Id,
+        tracker: &'a mut ChangeTracker,
+        heightmap_rules: &'a HeightmapRules,
+    ) -> Self {
+        Self {
+            chunk,
+            id,
+            tracker,
+            heightmap_rules,
+            dirty: false,
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    ///set the blockstate at the given position, return the previous blockstate
+    pub fn set_block(&mut self, pos: BlockPos, state: BlockState) -> BlockState {
+        let old_state = self.chunk.get_block(pos);
+        if old_state == state {
+            return old_state;
+        }
+
+        if self.chunk.get_block_entity(pos).is_some() {
+            self.record_block_entity(pos); //removed by set_block
+        }
+        self.chunk.set_block(pos, state);
+        ChunkHeightmaps::on_block_changed(self.chunk, self.heightmap_rules, pos);
+        self.tracker.journal.record(BlockChange {
+            chunk_id: self.id,
+            chunk_pos: self.chunk.position(),
+            pos,
+            old_state,
+            new_state: state,
+        });
+        let world_pos = self.chunk.position() * Chunk::SIZE + pos;
+        self.tracker.light.push_block(world_pos);
+        self.mark_dirty();
+        old_state
+    }
+
+    ///set the blockstate at the given position, just an alias for set_block
+    pub fn set_block_at(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> BlockState {
+        self.set_block(BlockPos::new(x, y, z), state)
+    }
+
+    ///replace all the blocks of the chunk at once, see Chunk::set_blocks
+    ///the changes aren't recorded block by block, like with ChunkManager::make_dirty the chunk will be entirely resent and its light computed again
+    pub fn set_blocks(&mut self, blocks: &[BlockState]) {
+        self.chunk.set_blocks(blocks);
+        *self.chunk.heightmaps_mut() = ChunkHeightmaps::default(); //computed again by the next query
+
+        let pos = self.chunk.position();
+        self.tracker.journal.mark_full_resend(self.id, pos);
+        self.tracker.light.push_chunk(pos);
+        self.mark_dirty();
+    }
+
+    ///set the biome of the 4x4x4 cell containing the given position, return true if it changed
+    ///the journal only records blocks, so like with set_blocks the chunk will be entirely resent
+    pub fn set_biome(&mut self, pos: BlockPos, biome: Biome) -> bool {
+        if !self.chunk.set_biome(pos, biome) {
+            return false;
+        }
+        self.tracker
+            .journal
+            .mark_full_resend(self.id, self.chunk.position());
+        self.mark_dirty();
+        true
+    }
+
+    ///attach a block entity to the block at the given position, return the block entity it replaces, see Chunk::set_block_entity
+    pub fn set_block_entity(
+        &mut self,
+        pos: BlockPos,
+        block_entity: BlockEntity,
+    ) -> Option<BlockEntity> {
+        self.record_block_entity(pos);
+        self.chunk.set_block_entity(pos, block_entity)
+    }
+
+    pub fn remove_block_entity(&mut self, pos: BlockPos) -> Option<BlockEntity> {
+        let removed = self.chunk.remove_block_entity(pos)?;
+        self.record_block_entity(pos);
+        Some(removed)
+    }
+
+    ///get the block entity at the given position with mutable capabilities, it's recorded as modified even if it isn't
+    pub fn get_block_entity_mut(&mut self, pos: BlockPos) -> Option<&mut BlockEntity> {
+        self.chunk.get_block_entity(pos)?;
+        self.record_block_entity(pos);
+        self.chunk.get_block_entity_mut(pos)
+    }
+
+    fn record_block_entity(&mut self, pos: BlockPos) {
+        self.tracker
+            .journal
+            .record_block_entity(self.id, self.chunk.position(), pos);
+        self.mark_dirty();
+    }
+
+    ///report the chunk as modified, like a block change
+    fn mark_dirty(&mut self) {
+        if !self.dirty {
+            self.tracker.chunk_modified.push(self.id);
+            self.dirty = true;
+        }
+    }
+
+    ///demote the chunk to its smallest format, see Chunk::compact, the blocks don't change so the chunk isn't marked as modified
+    pub fn compact(&mut self) -> bool {
+        self.chunk.compact()
+    }
+}
+
+impl Deref for ChunkGuard<'_> {
+    type Target = Chunk;
+
+    fn deref(&self) -> &Chunk {
+        self.chunk
+    }
+}
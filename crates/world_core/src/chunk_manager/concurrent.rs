@@ -0,0 +1,215 @@
+use super::NODE_SUBDIVISION;
+use crate::chunk_manager::{ChunkGuard, WorldSnapshot};
+use crate::{Chunk, ChunkManager, ChunkSnapshot};
+use math::aabb::AABB;
+use math::positions::ChunkPos;
+use math::IVec3;
+use std::collections::HashSet;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+///the chunks of a leaf node of the octree always belong to the same shard, so the neighbours of a chunk are usually behind the same lock
+const SHARD_SIDE: i32 = NODE_SUBDIVISION;
+
+///chunks shared between threads, so worker threads can generate, mesh or encode chunks while others insert them
+///the world is split between shards, each shard is a ChunkManager behind its own lock, so two threads only wait for each other when they use the same shard
+///the readers take snapshots, they only hold the lock of a shard while its chunks are snapshotted and never block the writers while they work
+///a shard only knows its own chunks, so there is no light, heightmap or id that is valid for the whole world, use into_chunk_manager to get them
+pub struct ConcurrentChunkManager {
+    shards: Box<[RwLock<ChunkManager>]>,
+}
+
+impl ConcurrentChunkManager {
+    ///create a manager with a few shards per available thread
+    pub fn new() -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        Self::with_shard_count(threads * 4)
+    }
+
+    pub fn with_shard_count(shard_count: usize) -> Self {
+        let shards = (0..shard_count.max(1))
+            .map(|_| RwLock::new(ChunkManager::new()))
+            .collect();
+        Self { shards }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self, pos: ChunkPos) -> &RwLock<ChunkManager> {
+        let cell = pos.div_euclid(IVec3::splat(SHARD_SIDE));
+        //spread the neighbouring cells over different shards
+        let hash = (cell.x as u32).wrapping_mul(73856093)
+            ^ (cell.y as u32).wrapping_mul(19349663)
+            ^ (cell.z as u32).wrapping_mul(83492791);
+        &self.shards[hash as usize % self.shards.len()]
+    }
+
+    ///lock the shard of the chunk for reading, the lock is held until the guard is dropped
+    fn read_shard(&self, pos: ChunkPos) -> RwLockReadGuard<'_, ChunkManager> {
+        self.shard(pos).read().unwrap()
+    }
+
+    ///lock the shard of the chunk for writing, the lock is held until the guard is dropped
+    fn write_shard(&self, pos: ChunkPos) -> RwLockWriteGuard<'_, ChunkManager> {
+        self.shard(pos).write().unwrap()
+    }
+
+    ///register a chunk, a chunk already loaded at the same position is dropped
+    pub fn insert_chunk(&self, chunk: Chunk) {
+        self.write_shard(chunk.position())
+            .insert_chunk_untracked(chunk);
+    }
+
+    ///unregister the chunk at the given position and give it back
+    pub fn remove_chunk(&self, pos: ChunkPos) -> Option<Chunk> {
+        self.write_shard(pos).remove_chunk(pos)
+    }
+
+    pub fn contains_chunk(&self, pos: ChunkPos) -> bool {
+        self.read_shard(pos).get_chunk(pos).is_some()
+    }
+
+    ///take a snapshot of a chunk, nothing is copied until the chunk is modified, see Chunk::snapshot
+    ///useful to encode a chunk for the network without holding the lock of its shard
+    pub fn snapshot_chunk(&self, pos: ChunkPos) -> Option<ChunkSnapshot> {
+        self.read_shard(pos)
+            .get_chunk_untracked(pos)
+            .map(Chunk::snapshot)
+    }
+
+    ///take a snapshot of the loaded chunks in the given AABB, like ChunkManager::snapshot
+    ///the shards are locked one after the other, so a chunk inserted in the meantime may be missing, but the snapshot never has half a chunk
+    ///a mesher takes the chunk and its neighbours, then builds the mesh from the snapshot while the chunks keep being generated
+    pub fn snapshot(&self, chunk_aabb: AABB) -> WorldSnapshot {
+        let mut snapshot = WorldSnapshot::default();
+        for shard in self.shards.iter() {
+            snapshot.merge(shard.read().unwrap().snapshot(chunk_aabb));
+        }
+        snapshot
+    }
+
+    ///call the function with the chunk at the given position, the shard is locked for reading during the call
+    pub fn with_chunk<R>(&self, pos: ChunkPos, func: impl FnOnce(&Chunk) -> R) -> Option<R> {
+        self.read_shard(pos).get_chunk(pos).map(func)
+    }
+
+    ///call the function with the chunk at the given position with mutable capabilities, the shard is locked for writing during the call
+    ///the id of the guard is only valid in the shard, it doesn't match the id of the chunk in the ChunkManager given by into_chunk_manager
+    ///the chunk is remembered as modified, see into_chunk_manager
+    pub fn with_chunk_mut<R>(
+        &self,
+        pos: ChunkPos,
+        func: impl FnOnce(ChunkGuard<'_>) -> R,
+    ) -> Option<R> {
+        self.write_shard(pos).get_chunk_mut(pos).map(func)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().chunk_positions.len())
+            .sum()
+    }
+
+    ///move all the chunks in a single ChunkManager
+    ///the inserted chunks aren't marked as modified, like the chunks loaded from the disk, call make_dirty for the ones that must be saved or sent
+    ///the chunks modified through with_chunk_mut are marked with make_dirty, the block changes of the shards can't be kept since their ids change
+    pub fn into_chunk_manager(self) -> ChunkManager {
+        let mut chunk_manager = ChunkManager::new();
+        for shard in self.shards.into_vec() {
+            let mut shard = shard.into_inner().unwrap();
+            let modified: HashSet<ChunkPos> = shard
+                .changes
+                .chunk_modified
+                .iter()
+                .filter_map(|id| shard.chunk_positions.get(*id).copied())
+                .collect();
+            let positions = shard
+                .chunk_positions
+                .iter()
+                .map(|(_, pos)| *pos)
+                .collect::<Vec<_>>();
+            for pos in positions {
+                let Some(chunk) = shard.remove_chunk(pos) else {
+                    continue;
+                };
+                if modified.contains(&pos) {
+                    chunk_manager.insert_chunk(chunk);
+                } else {
+                    chunk_manager.insert_chunk_untracked(chunk);
+                }
+            }
+        }
+        chunk_manager
+    }
+}
+
+impl Default for ConcurrentChunkManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::BlockState;
+
+    #[test]
+    pub fn parallel_insert() {
+        let chunk_manager = ConcurrentChunkManager::with_shard_count(8);
+        std::thread::scope(|scope| {
+            for x in -4..4 {
+                let chunk_manager = &chunk_manager;
+                scope.spawn(move || {
+                    for y in -4..4 {
+                        for z in -4..4 {
+                            let state = BlockState::from_raw((x + 10) as u16);
+                            chunk_manager
+                                .insert_chunk(Chunk::new_uniform(ChunkPos::new(x, y, z), state));
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(chunk_manager.chunk_count(), 8 * 8 * 8);
+
+        let pos = ChunkPos::new(-3, 2, 1);
+        let state = chunk_manager.with_chunk(pos, |chunk| chunk.get_block_at(0, 0, 0));
+        assert_eq!(state, Some(BlockState::from_raw(7)));
+        chunk_manager.with_chunk_mut(pos, |mut chunk| {
+            chunk.set_block_at(0, 0, 0, BlockState::from_raw(1));
+        });
+        assert!(chunk_manager.remove_chunk(ChunkPos::new(3, 3, 3)).is_some());
+        assert!(!chunk_manager.contains_chunk(ChunkPos::new(3, 3, 3)));
+
+        //a reader works on a snapshot while the chunks keep changing
+        let snapshot = chunk_manager.snapshot(AABB::new(pos - IVec3::ONE, pos + IVec3::splat(2)));
+        assert_eq!(snapshot.len(), 27);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                chunk_manager.with_chunk_mut(pos, |mut chunk| {
+                    chunk.set_block_at(1, 0, 0, BlockState::from_raw(2));
+                });
+            });
+        });
+        let chunk = snapshot.get_chunk(pos).unwrap();
+        assert_eq!(chunk.get_block_at(1, 0, 0), BlockState::from_raw(7));
+        assert!(chunk_manager
+            .snapshot_chunk(ChunkPos::new(3, 3, 3))
+            .is_none());
+
+        //only the chunk modified after its insertion is marked as modified
+        let mut chunk_manager = chunk_manager.into_chunk_manager();
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 1);
+            assert!(journal.get(ids[0]).unwrap().needs_full_resend());
+        });
+        let chunk = chunk_manager.get_chunk(pos).unwrap();
+        assert_eq!(chunk.get_block_at(0, 0, 0), BlockState::from_raw(1));
+        assert_eq!(chunk.get_block_at(1, 0, 0), BlockState::from_raw(2));
+        assert!(chunk_manager.get_chunk(ChunkPos::new(3, 3, 3)).is_none());
+        assert_eq!(chunk_manager.chunk_positions.len(), 8 * 8 * 8 - 1);
+    }
+}
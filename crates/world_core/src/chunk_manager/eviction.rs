@@ -0,0 +1,174 @@
+use super::Node;
+use crate::chunk::MEMORY_MANAGER;
+use crate::{Chunk, ChunkManager};
+use math::positions::ChunkPos;
+
+///the order in which the chunks are evicted when the ChunkManager is over its memory budget
+#[derive(Clone, Copy, Debug)]
+pub enum EvictionPolicy<'a> {
+    ///the chunks accessed the longest time ago first, see Chunk::last_access
+    LeastRecentlyUsed,
+    ///the chunks the farthest from their closest anchor first, the anchors are usually the chunks of the players
+    FarthestFrom(&'a [ChunkPos]),
+}
+
+///the memory the chunks of a ChunkManager can use in bytes, see Chunk::memory_size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBudget {
+    ///the eviction starts when the chunks use more memory than this
+    pub max_memory: usize,
+    ///the eviction stops when the chunks use less memory than this, it's lower than max_memory so the eviction doesn't run at every call
+    pub target_memory: usize,
+}
+
+impl MemoryBudget {
+    ///a budget that evicts down to 90% of the maximum
+    pub fn new(max_memory: usize) -> Self {
+        Self {
+            max_memory,
+            target_memory: max_memory / 10 * 9,
+        }
+    }
+}
+
+impl ChunkManager {
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.memory_budget
+    }
+
+    ///set the budget checked by enforce_memory_budget, None to never evict anything
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.memory_budget = budget;
+    }
+
+    ///the memory used by the loaded chunks in bytes, it walks over all the chunks
+    pub fn memory_usage(&self) -> usize {
+        let mut usage = 0;
+        for section in self.section_map.values() {
+            section.for_all_chunks(&mut |_, chunk| usage += chunk.memory_size());
+        }
+        usage
+    }
+
+    ///if the chunks use more memory than the budget, remove chunks in the order of the policy until they use less than the target memory
+    ///before_evict is called with each chunk before its removal, so a modified chunk can be saved, it returns false to keep the chunk loaded
+    ///the arenas are shrunk after an eviction, return the number of evicted chunks
+    ///it walks over all the chunks, so it should be run from time to time rather than each tick
+    pub fn enforce_memory_budget(
+        &mut self,
+        policy: EvictionPolicy,
+        mut before_evict: impl FnMut(&Chunk) -> bool,
+    ) -> usize {
+        let Some(budget) = self.memory_budget else {
+            return 0;
+        };
+        let mut usage = self.memory_usage();
+        if usage <= budget.max_memory {
+            return 0;
+        }
+
+        //the chunks to evict first are at the start
+        let mut candidates = Vec::with_capacity(self.chunk_positions.len());
+        for section in self.section_map.values() {
+            section.for_all_chunks(&mut |_, chunk| {
+                let priority = match policy {
+                    EvictionPolicy::LeastRecentlyUsed => chunk.last_access() as i64,
+                    EvictionPolicy::FarthestFrom(anchors) => -anchors
+                        .iter()
+                        .map(|anchor| (*anchor - chunk.position()).as_i64vec3().length_squared())
+                        .min()
+                        .unwrap_or(0),
+                };
+                candidates.push((priority, chunk.position(), chunk.memory_size()));
+            });
+        }
+        candidates.sort_unstable_by_key(|(priority, pos, _)| (*priority, pos.to_array()));
+
+        let mut evicted = 0;
+        for (_, pos, memory_size) in candidates {
+            if usage <= budget.target_memory {
+                break;
+            }
+            let Some(chunk) = self.get_chunk_untracked(pos) else {
+                continue;
+            };
+            if before_evict(chunk) {
+                self.remove_chunk(pos);
+                usage -= memory_size;
+                evicted += 1;
+            }
+        }
+        if evicted > 0 {
+            MEMORY_MANAGER.shrink_to_fit();
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::BlockState;
+    use crate::MissingChunks;
+    use math::positions::EntityPos;
+    use math::Vec3;
+
+    fn chunk_manager() -> ChunkManager {
+        let mut chunk_manager = ChunkManager::new();
+        for x in 0..8 {
+            let mut chunk = Chunk::new(ChunkPos::new(x, 0, 0));
+            chunk.set_block_at(0, 0, 0, BlockState::from_raw(1));
+            chunk_manager.insert_chunk(chunk);
+        }
+        chunk_manager
+    }
+
+    #[test]
+    pub fn least_recently_used() {
+        let mut chunk_manager = chunk_manager();
+        let chunk_size = chunk_manager.memory_usage() / 8;
+        assert_eq!(
+            chunk_manager.enforce_memory_budget(EvictionPolicy::LeastRecentlyUsed, |_| true),
+            0
+        );
+        chunk_manager.set_memory_budget(Some(MemoryBudget {
+            max_memory: chunk_size * 6,
+            target_memory: chunk_size * 5,
+        }));
+        chunk_manager.get_chunk(ChunkPos::new(0, 0, 0));
+        chunk_manager.get_chunk_mut(ChunkPos::new(1, 0, 0));
+        let origin = EntityPos::from(40.5, 5.5, 0.5);
+        chunk_manager.raycast(origin, Vec3::NEG_Y, 2.0, MissingChunks::Solid); //the queries don't count as an access
+
+        //the chunk 2 is kept by the callback
+        let mut saved = Vec::new();
+        let evicted =
+            chunk_manager.enforce_memory_budget(EvictionPolicy::LeastRecentlyUsed, |chunk| {
+                saved.push(chunk.position().x);
+                chunk.position().x != 2
+            });
+        assert_eq!(evicted, 3);
+        assert_eq!(saved, vec![2, 3, 4, 5]);
+        assert!(chunk_manager.get_chunk(ChunkPos::new(0, 0, 0)).is_some());
+        assert!(chunk_manager.get_chunk(ChunkPos::new(2, 0, 0)).is_some());
+        assert!(chunk_manager.get_chunk(ChunkPos::new(3, 0, 0)).is_none());
+        assert_eq!(chunk_manager.memory_usage(), chunk_size * 5);
+    }
+
+    #[test]
+    pub fn farthest_from_anchors() {
+        let mut chunk_manager = chunk_manager();
+        let chunk_size = chunk_manager.memory_usage() / 8;
+        chunk_manager.set_memory_budget(Some(MemoryBudget {
+            max_memory: chunk_size * 5,
+            target_memory: chunk_size * 5,
+        }));
+        let anchors = [ChunkPos::new(0, 0, 0), ChunkPos::new(4, 0, 0)];
+        let evicted =
+            chunk_manager.enforce_memory_budget(EvictionPolicy::FarthestFrom(&anchors), |_| true);
+        assert_eq!(evicted, 3);
+        for x in [0, 1, 3, 4, 5] {
+            assert!(chunk_manager.get_chunk(ChunkPos::new(x, 0, 0)).is_some());
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::block_state::{BlockState, AIR};
+use crate::fluid::{fluid_amount, Fluid, Fluids, FALLING_LEVEL, SOURCE_LEVEL};
+use crate::{Chunk, ChunkManager};
+use math::positions::BlockPos;
+use math::IVec3;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::NEG_Z, IVec3::Z, IVec3::NEG_X, IVec3::X];
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::NEG_Y,
+    IVec3::Y,
+    IVec3::NEG_Z,
+    IVec3::Z,
+    IVec3::NEG_X,
+    IVec3::X,
+];
+
+impl ChunkManager {
+    ///schedule an update of the fluids at the given position and around it, after their tick delay
+    ///to call after a block change that may let a fluid flow, like placing a fluid or breaking a dam
+    pub fn update_fluids_around(&mut self, pos: BlockPos, current_tick: u64, fluids: &Fluids) {
+        for offset in [IVec3::ZERO].iter().chain(&NEIGHBOURS) {
+            let pos = pos + *offset;
+            if let Some((fluid, _)) = self.block_at(pos).and_then(|state| fluids.get(state)) {
+                self.schedule_tick(pos, current_tick + fluid.tick_delay);
+            }
+        }
+    }
+
+    ///update the fluid at the given position like vanilla, meant to be called from the func of run_scheduled_ticks
+    ///a flowing block takes the level of its neighbours, then the fluid falls or spreads toward the closest hole
+    ///return false if the state isn't a fluid
+    pub fn tick_fluid(
+        &mut self,
+        pos: BlockPos,
+        state: BlockState,
+        current_tick: u64,
+        fluids: &Fluids,
+    ) -> bool {
+        let Some((fluid, level)) = fluids.get(state) else {
+            return false;
+        };
+        if level != SOURCE_LEVEL {
+            let new_state = match self.flowing_level(pos, fluid, fluids) {
+                Some(new_level) => fluid.state(new_level),
+                None => AIR, //nothing feeds the block anymore
+            };
+            if new_state != state {
+                self.set_fluid(pos, new_state, current_tick, fluids);
+                return true; //the new level spreads at the next update
+            }
+        }
+
+        let below = pos - IVec3::Y;
+        if self.can_flow_into(below, FALLING_LEVEL, fluid, fluids) {
+            self.set_fluid(below, fluid.state(FALLING_LEVEL), current_tick, fluids);
+            if self.count_sources(pos, fluid, fluids) >= 3 {
+                self.spread_to_sides(pos, level, current_tick, fluid, fluids);
+            }
+        } else if level == SOURCE_LEVEL || !self.is_hole(below, fluid, fluids) {
+            self.spread_to_sides(pos, level, current_tick, fluid, fluids);
+        }
+        true
+    }
+
+    ///return None if the block isn't loaded
+    fn block_at(&self, pos: BlockPos) -> Option<BlockState> {
+        let chunk = self.get_chunk_untracked(pos.div_euclid(IVec3::splat(Chunk::SIZE)))?;
+        Some(chunk.get_block(pos.rem_euclid(IVec3::splat(Chunk::SIZE))))
+    }
+
+    ///the level of the block if it is the given fluid
+    fn level_at(&self, pos: BlockPos, fluid: &Fluid, fluids: &Fluids) -> Option<u8> {
+        let state = self.block_at(pos)?;
+        if !fluids.is_fluid(state, fluid) {
+            return None;
+        }
+        fluids.get(state).map(|(_, level)| level)
+    }
+
+    fn count_sources(&self, pos: BlockPos, fluid: &Fluid, fluids: &Fluids) -> usize {
+        HORIZONTAL
+            .iter()
+            .filter(|direction| {
+                self.level_at(pos + **direction, fluid, fluids) == Some(SOURCE_LEVEL)
+            })
+            .count()
+    }
+
+    ///the level a flowing block should have from its neighbours, None if it should dry out
+    fn flowing_level(&self, pos: BlockPos, fluid: &Fluid, fluids: &Fluids) -> Option<u8> {
+        if fluid.infinite_sources && self.count_sources(pos, fluid, fluids) >= 2 {
+            let below = pos - IVec3::Y;
+            let on_ground = match self.block_at(below) {
+                Some(state) => state != AIR && fluids.get(state).is_none(),
+                None => false,
+            };
+            if on_ground || self.level_at(below, fluid, fluids) == Some(SOURCE_LEVEL) {
+                return Some(SOURCE_LEVEL);
+            }
+        }
+        if self.level_at(pos + IVec3::Y, fluid, fluids).is_some() {
+            return Some(FALLING_LEVEL);
+        }
+
+        let amount = HORIZONTAL
+            .iter()
+            .filter_map(|direction| self.level_at(pos + *direction, fluid, fluids))
+            .map(fluid_amount)
+            .max()?;
+        let amount = amount.checked_sub(fluid.level_decrease)?;
+        match amount {
+            0 => None,
+            amount => Some(8 - amount),
+        }
+    }
+
+    ///return true if the fluid at the given level can replace the block, air or a lower level of the same fluid
+    fn can_flow_into(&self, pos: BlockPos, level: u8, fluid: &Fluid, fluids: &Fluids) -> bool {
+        match self.block_at(pos) {
+            Some(AIR) => true,
+            Some(_) => match self.level_at(pos, fluid, fluids) {
+                Some(SOURCE_LEVEL) | None => false,
+                Some(other) => fluid_amount(other) < fluid_amount(level) && other < FALLING_LEVEL,
+            },
+            None => false,
+        }
+    }
+
+    ///a block the fluid can fall in, or that already holds the fluid
+    fn is_hole(&self, pos: BlockPos, fluid: &Fluid, fluids: &Fluids) -> bool {
+        self.block_at(pos) == Some(AIR) || self.level_at(pos, fluid, fluids).is_some()
+    }
+
+    ///a block the fluid could flow through, when looking for a hole
+    fn is_passable(&self, pos: BlockPos, fluid: &Fluid, fluids: &Fluids) -> bool {
+        match self.level_at(pos, fluid, fluids) {
+            Some(level) => level != SOURCE_LEVEL,
+            None => self.block_at(pos) == Some(AIR),
+        }
+    }
+
+    ///spread toward the closest holes within the slope distance, or in every direction if there is none
+    fn spread_to_sides(
+        &mut self,
+        pos: BlockPos,
+        level: u8,
+        current_tick: u64,
+        fluid: &Fluid,
+        fluids: &Fluids,
+    ) {
+        let Some(amount) = fluid_amount(level).checked_sub(fluid.level_decrease) else {
+            return;
+        };
+        if amount == 0 {
+            return;
+        }
+        let new_level = 8 - amount;
+
+        let mut targets = Vec::new();
+        let mut shortest = u8::MAX;
+        for direction in HORIZONTAL {
+            let side = pos + direction;
+            if !self.can_flow_into(side, new_level, fluid, fluids) {
+                continue;
+            }
+            let distance = if self.is_hole(side - IVec3::Y, fluid, fluids) {
+                0
+            } else {
+                self.slope_distance(side, 1, -direction, fluid, fluids)
+            };
+            if distance < shortest {
+                shortest = distance;
+                targets.clear();
+            }
+            if distance == shortest {
+                targets.push(side);
+            }
+        }
+        for side in targets {
+            self.set_fluid(side, fluid.state(new_level), current_tick, fluids);
+        }
+    }
+
+    ///the number of blocks to walk from pos to a hole, u8::MAX if there is none within the slope distance of the fluid
+    fn slope_distance(
+        &self,
+        pos: BlockPos,
+        depth: u8,
+        from: IVec3,
+        fluid: &Fluid,
+        fluids: &Fluids,
+    ) -> u8 {
+        let mut shortest = u8::MAX;
+        for direction in HORIZONTAL {
+            let next = pos + direction;
+            if direction == from || !self.is_passable(next, fluid, fluids) {
+                continue;
+            }
+            if self.is_hole(next - IVec3::Y, fluid, fluids) {
+                return depth;
+            }
+            if depth < fluid.slope_distance {
+                shortest =
+                    shortest.min(self.slope_distance(next, depth + 1, -direction, fluid, fluids));
+            }
+        }
+        shortest
+    }
+
+    ///change the block and wake up the fluids around it
+    fn set_fluid(&mut self, pos: BlockPos, state: BlockState, current_tick: u64, fluids: &Fluids) {
+        let Some(mut chunk) = self.get_chunk_mut(pos.div_euclid(IVec3::splat(Chunk::SIZE))) else {
+            return;
+        };
+        chunk.set_block(pos.rem_euclid(IVec3::splat(Chunk::SIZE)), state);
+        self.update_fluids_around(pos, current_tick, fluids);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fluid::FLUID_LEVEL_COUNT;
+    use math::positions::ChunkPos;
+
+    fn stone() -> BlockState {
+        BlockState::from_raw(1)
+    }
+
+    fn water(level: u8) -> BlockState {
+        BlockState::from_raw(2 + level as u16)
+    }
+
+    fn setup() -> (ChunkManager, Fluids) {
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        for x in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                chunk.set_block_at(x, 4, z, stone()); //the floor
+            }
+        }
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(chunk);
+        let mut fluids = Fluids::new();
+        let states: [BlockState; FLUID_LEVEL_COUNT] =
+            std::array::from_fn(|level| water(level as u8));
+        assert!(fluids.register(Fluid::new(states)));
+        (chunk_manager, fluids)
+    }
+
+    ///run the ticks from start to end included
+    fn run(chunk_manager: &mut ChunkManager, fluids: &Fluids, start: u64, end: u64) {
+        for tick in start..=end {
+            chunk_manager.run_scheduled_ticks(tick, |world, pos, state| {
+                world.tick_fluid(pos, state, tick, fluids);
+            });
+        }
+    }
+
+    fn place(
+        chunk_manager: &mut ChunkManager,
+        fluids: &Fluids,
+        pos: BlockPos,
+        state: BlockState,
+        tick: u64,
+    ) {
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block(pos, state);
+        chunk_manager.update_fluids_around(pos, tick, fluids);
+    }
+
+    #[test]
+    pub fn spread() {
+        let (mut chunk_manager, fluids) = setup();
+        let block = |chunk_manager: &ChunkManager, x, y, z| {
+            chunk_manager.block_at(BlockPos::new(x, y, z)).unwrap()
+        };
+        place(
+            &mut chunk_manager,
+            &fluids,
+            BlockPos::new(8, 5, 8),
+            water(0),
+            0,
+        );
+
+        run(&mut chunk_manager, &fluids, 0, 5);
+        assert_eq!(block(&chunk_manager, 9, 5, 8), water(1));
+        assert_eq!(block(&chunk_manager, 10, 5, 8), AIR);
+        run(&mut chunk_manager, &fluids, 6, 100);
+        for distance in 1..8 {
+            assert_eq!(
+                block(&chunk_manager, 8 - distance, 5, 8),
+                water(distance as u8)
+            );
+            assert_eq!(
+                block(&chunk_manager, 8, 5, 8 + distance),
+                water(distance as u8)
+            );
+        }
+        assert_eq!(block(&chunk_manager, 0, 5, 8), AIR);
+        assert_eq!(block(&chunk_manager, 4, 5, 4), AIR); //8 blocks away
+
+        //the flowing water dries out without its source
+        place(
+            &mut chunk_manager,
+            &fluids,
+            BlockPos::new(8, 5, 8),
+            AIR,
+            100,
+        );
+        run(&mut chunk_manager, &fluids, 101, 400);
+        for x in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                assert_eq!(block(&chunk_manager, x, 5, z), AIR);
+            }
+        }
+    }
+
+    #[test]
+    pub fn fall_and_infinite_source() {
+        let (mut chunk_manager, fluids) = setup();
+        let block = |chunk_manager: &ChunkManager, x, y, z| {
+            chunk_manager.block_at(BlockPos::new(x, y, z)).unwrap()
+        };
+        place(
+            &mut chunk_manager,
+            &fluids,
+            BlockPos::new(8, 8, 8),
+            water(0),
+            0,
+        );
+        run(&mut chunk_manager, &fluids, 0, 9);
+        assert_eq!(block(&chunk_manager, 8, 7, 8), water(FALLING_LEVEL));
+        assert_eq!(block(&chunk_manager, 9, 8, 8), AIR); //the source falls before spreading
+        run(&mut chunk_manager, &fluids, 10, 15);
+        assert_eq!(block(&chunk_manager, 8, 5, 8), water(FALLING_LEVEL));
+        assert_eq!(block(&chunk_manager, 9, 8, 8), water(1));
+        assert_eq!(block(&chunk_manager, 9, 6, 8), AIR); //the falling blocks don't spread
+        run(&mut chunk_manager, &fluids, 16, 20);
+        assert_eq!(block(&chunk_manager, 9, 5, 8), water(1));
+
+        //a flowing block between two sources becomes a source
+        place(
+            &mut chunk_manager,
+            &fluids,
+            BlockPos::new(2, 5, 2),
+            water(0),
+            100,
+        );
+        place(
+            &mut chunk_manager,
+            &fluids,
+            BlockPos::new(4, 5, 2),
+            water(0),
+            100,
+        );
+        run(&mut chunk_manager, &fluids, 100, 110);
+        assert_eq!(block(&chunk_manager, 3, 5, 2), water(0));
+    }
+}
@@ -0,0 +1,64 @@
+use super::Node;
+use crate::heightmap::{ChunkHeightmaps, HeightmapKind, HeightmapRules};
+use crate::{Chunk, ChunkManager};
+use math::positions::ChunkPos;
+use math::IVec2;
+
+impl ChunkManager {
+    ///get the world y of the highest block of the column counted by the heightmap, None if no loaded chunk of the column has such a block
+    ///only the chunks of the column are visited, from the top, and an empty chunk is skipped without reading its blocks
+    ///the heightmaps of a chunk are computed by the first query that reaches it
+    pub fn get_height(&self, kind: HeightmapKind, x: i32, z: i32) -> Option<i32> {
+        let column_pos = IVec2::new(x, z).div_euclid(IVec2::splat(Chunk::SIZE));
+        let local_pos = IVec2::new(x, z).rem_euclid(IVec2::splat(Chunk::SIZE));
+        let chunk_ys = self.columns.get(&column_pos)?;
+        chunk_ys.iter().rev().find_map(|chunk_y| {
+            let chunk_pos = ChunkPos::new(column_pos.x, *chunk_y, column_pos.y);
+            let chunk = self.get_chunk_untracked(chunk_pos)?;
+            let y = chunk.heightmaps().get_or_compute(
+                chunk,
+                &self.heightmap_rules,
+                kind,
+                local_pos.x,
+                local_pos.y,
+            )?;
+            Some(chunk_y * Chunk::SIZE + y)
+        })
+    }
+
+    pub fn heightmap_rules(&self) -> &HeightmapRules {
+        &self.heightmap_rules
+    }
+
+    ///change the blocks counted by the heightmaps, the heightmaps of every loaded chunk will be computed again by the next query
+    pub fn set_heightmap_rules(&mut self, rules: HeightmapRules) {
+        for section in self.section_map.values_mut() {
+            section.for_all_chunks_mut(&mut |_, chunk| {
+                *chunk.heightmaps_mut() = ChunkHeightmaps::default();
+            });
+        }
+        self.heightmap_rules = rules;
+    }
+
+    ///forget the heightmaps of the chunk, needed when it's modified without a ChunkGuard, they are computed again by the next query
+    pub(super) fn reset_heightmaps(&mut self, pos: ChunkPos) {
+        if let Some(chunk) = self.get_chunk_untracked_mut(pos) {
+            *chunk.heightmaps_mut() = ChunkHeightmaps::default();
+        }
+    }
+
+    pub(super) fn add_to_column(&mut self, pos: ChunkPos) {
+        let column_pos = IVec2::new(pos.x, pos.z);
+        self.columns.entry(column_pos).or_default().insert(pos.y);
+    }
+
+    pub(super) fn remove_from_column(&mut self, pos: ChunkPos) {
+        let column_pos = IVec2::new(pos.x, pos.z);
+        if let Some(chunk_ys) = self.columns.get_mut(&column_pos) {
+            chunk_ys.remove(&pos.y);
+            if chunk_ys.is_empty() {
+                self.columns.remove(&column_pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use crate::block_state::BlockState;
+use crate::light::{attenuate, ChunkLight, LightChannel, LightTable, MAX_LIGHT};
+use crate::{Chunk, ChunkManager};
+use math::positions::{BlockPos, ChunkPos};
+use math::IVec3;
+use std::collections::{HashMap, VecDeque};
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+///the blocks whose light must be computed again by ChunkManager::update_light
+///nothing is queued before the first call to update_light, so a world whose light is never computed doesn't fill the queue
+#[derive(Default)]
+pub(super) struct LightQueue {
+    enabled: bool,
+    blocks: Vec<BlockPos>,         //in world coordinates
+    chunks: Vec<ChunkPos>, //inserted or marked with make_dirty, the light of the chunk is computed again from scratch
+    removed_chunks: Vec<ChunkPos>, //the light of the neighbours may have come from these chunks
+}
+
+impl LightQueue {
+    pub(super) fn push_block(&mut self, pos: BlockPos) {
+        if self.enabled {
+            self.blocks.push(pos);
+        }
+    }
+
+    pub(super) fn push_chunk(&mut self, pos: ChunkPos) {
+        if self.enabled {
+            self.chunks.push(pos);
+        }
+    }
+
+    pub(super) fn push_removed_chunk(&mut self, pos: ChunkPos) {
+        if self.enabled {
+            self.removed_chunks.push(pos);
+        }
+    }
+}
+
+fn split_pos(pos: BlockPos) -> (ChunkPos, BlockPos) {
+    let size = IVec3::splat(Chunk::SIZE);
+    (pos.div_euclid(size), pos.rem_euclid(size))
+}
+
+fn chunk_blocks(chunk_pos: ChunkPos) -> impl Iterator<Item = BlockPos> {
+    let origin = chunk_pos * Chunk::SIZE;
+    (0..Chunk::BLOCK_COUNT as i32).map(move |i| {
+        origin
+            + BlockPos::new(
+                i % Chunk::SIZE,
+                (i / Chunk::SIZE) % Chunk::SIZE,
+                i / (Chunk::SIZE * Chunk::SIZE),
+            )
+    })
+}
+
+///the layer of blocks of the chunk on the given side
+fn face_blocks(chunk_pos: ChunkPos, face: IVec3) -> impl Iterator<Item = BlockPos> {
+    chunk_blocks(chunk_pos).filter(move |pos| {
+        let local_pos = pos.rem_euclid(IVec3::splat(Chunk::SIZE));
+        (0..3).any(|axis| match face[axis] {
+            1 => local_pos[axis] == Chunk::SIZE - 1,
+            -1 => local_pos[axis] == 0,
+            _ => false,
+        })
+    })
+}
+
+///the world seen by the light propagation, the chunks are only read and the new light is kept aside until the propagation ends
+struct LightWorld<'a> {
+    chunk_manager: &'a ChunkManager,
+    table: &'a LightTable,
+    lights: Vec<(ChunkPos, ChunkLight)>, //the light of the chunks changed by the propagation, moved in the chunks at the end
+    light_index: HashMap<ChunkPos, usize>,
+    current: Option<(ChunkPos, Option<&'a Chunk>, Option<usize>)>, //the last chunk used and its index in lights, most steps stay in the same chunk so the octree is rarely walked
+}
+
+impl<'a> LightWorld<'a> {
+    ///the light of the given chunks starts from zero
+    fn new(chunk_manager: &'a ChunkManager, table: &'a LightTable, reset: &[ChunkPos]) -> Self {
+        let lights: Vec<_> = reset
+            .iter()
+            .map(|pos| (*pos, ChunkLight::default()))
+            .collect();
+        let light_index = lights
+            .iter()
+            .enumerate()
+            .map(|(index, (pos, _))| (*pos, index))
+            .collect();
+        Self {
+            chunk_manager,
+            table,
+            lights,
+            light_index,
+            current: None,
+        }
+    }
+
+    fn lookup(&mut self, chunk_pos: ChunkPos) -> (Option<&'a Chunk>, Option<usize>) {
+        match self.current {
+            Some((pos, chunk, light)) if pos == chunk_pos => (chunk, light),
+            _ => {
+                let chunk = self.chunk_manager.get_chunk_untracked(chunk_pos);
+                let light = self.light_index.get(&chunk_pos).copied();
+                self.current = Some((chunk_pos, chunk, light));
+                (chunk, light)
+            }
+        }
+    }
+
+    ///return None if the chunk of the block isn't loaded, the light doesn't go in missing chunks
+    fn get(&mut self, channel: LightChannel, pos: BlockPos) -> Option<(u8, BlockState)> {
+        let (chunk_pos, local_pos) = split_pos(pos);
+        let (chunk, light) = self.lookup(chunk_pos);
+        let chunk = chunk?;
+        let level = match light {
+            Some(index) => self.lights[index].1.get(channel, local_pos),
+            None => chunk.light().get(channel, local_pos),
+        };
+        Some((level, chunk.get_block(local_pos)))
+    }
+
+    fn set(&mut self, channel: LightChannel, pos: BlockPos, level: u8) {
+        let (chunk_pos, local_pos) = split_pos(pos);
+        let (Some(chunk), light) = self.lookup(chunk_pos) else {
+            return;
+        };
+        let index = light.unwrap_or_else(|| {
+            let index = self.lights.len();
+            self.lights.push((chunk_pos, chunk.light().clone()));
+            self.light_index.insert(chunk_pos, index);
+            self.current = Some((chunk_pos, Some(chunk), Some(index)));
+            index
+        });
+        self.lights[index].1.set(channel, local_pos, level);
+    }
+
+    ///the light emitted by the block itself, the sky light enters from the top of the chunks without any loaded chunk above
+    fn source(&self, channel: LightChannel, pos: BlockPos, state: BlockState) -> u8 {
+        match channel {
+            LightChannel::Block => self.table.emission(state),
+            LightChannel::Sky => {
+                let (chunk_pos, local_pos) = split_pos(pos);
+                let open_sky = local_pos.y == Chunk::SIZE - 1
+                    && self
+                        .chunk_manager
+                        .get_chunk_untracked(chunk_pos + IVec3::Y)
+                        .is_none();
+                match open_sky {
+                    true => attenuate(channel, MAX_LIGHT, true, self.table.opacity(state)),
+                    false => 0,
+                }
+            }
+        }
+    }
+
+    ///remove the light of the given blocks and everything that came from them, then propagate the light again from the remaining sources
+    fn relight(&mut self, channel: LightChannel, blocks: &[BlockPos]) {
+        let mut removal = VecDeque::new();
+        let mut sources = blocks.to_vec();
+        let mut propagation = VecDeque::new();
+
+        for pos in blocks {
+            if let Some((level, _)) = self.get(channel, *pos) {
+                if level > 0 {
+                    self.set(channel, *pos, 0);
+                    removal.push_back((*pos, level));
+                }
+            }
+        }
+        while let Some((pos, level)) = removal.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbour = pos + direction;
+                let Some((neighbour_level, state)) = self.get(channel, neighbour) else {
+                    continue;
+                };
+                if neighbour_level == 0 {
+                    continue;
+                }
+                let sky_column = channel == LightChannel::Sky
+                    && direction == IVec3::NEG_Y
+                    && level == MAX_LIGHT
+                    && neighbour_level == MAX_LIGHT;
+                if neighbour_level < level || sky_column {
+                    //the light of the neighbour came from the removed light
+                    self.set(channel, neighbour, 0);
+                    removal.push_back((neighbour, neighbour_level));
+                    if self.source(channel, neighbour, state) > 0 {
+                        sources.push(neighbour);
+                    }
+                } else {
+                    //the neighbour has its own light, it will fill the hole
+                    propagation.push_back(neighbour);
+                }
+            }
+        }
+
+        for pos in sources {
+            let Some((level, state)) = self.get(channel, pos) else {
+                continue;
+            };
+            let source = self.source(channel, pos, state);
+            if source > level {
+                self.set(channel, pos, source);
+            }
+            propagation.push_back(pos);
+            //a block that became transparent is lit by its neighbours
+            for direction in DIRECTIONS {
+                if let Some((level, _)) = self.get(channel, pos + direction) {
+                    if level > 0 {
+                        propagation.push_back(pos + direction);
+                    }
+                }
+            }
+        }
+
+        while let Some(pos) = propagation.pop_front() {
+            let Some((level, _)) = self.get(channel, pos) else {
+                continue;
+            };
+            if level == 0 {
+                continue;
+            }
+            for direction in DIRECTIONS {
+                let neighbour = pos + direction;
+                let Some((neighbour_level, state)) = self.get(channel, neighbour) else {
+                    continue;
+                };
+                let opacity = self.table.opacity(state);
+                let new_level = attenuate(channel, level, direction == IVec3::NEG_Y, opacity);
+                if new_level > neighbour_level {
+                    self.set(channel, neighbour, new_level);
+                    propagation.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+impl ChunkManager {
+    ///get the light level of a block in the world, return None if its chunk isn't loaded
+    pub fn get_light(&self, channel: LightChannel, pos: BlockPos) -> Option<u8> {
+        let (chunk_pos, local_pos) = split_pos(pos);
+        let chunk = self.get_chunk_untracked(chunk_pos)?;
+        Some(chunk.light().get(channel, local_pos))
+    }
+
+    ///propagate the light changes caused by the blocks changed through a ChunkGuard and by the inserted, removed or dirty chunks since the last call
+    ///the light enters from the top of the highest loaded chunk of each column and never goes in a missing chunk
+    ///the light of an inserted or dirty chunk is computed again from its light sources, the open sky and the borders of its loaded neighbours
+    ///return the chunks whose light changed, so they can be sent again, they aren't marked as modified
+    pub fn update_light(&mut self, table: &LightTable) -> Vec<ChunkPos> {
+        let mut queue = std::mem::take(&mut self.changes.light);
+        self.changes.light.enabled = true;
+        if !queue.enabled {
+            //nothing was queued before the first call, every loaded chunk is lit
+            queue.chunks = self
+                .columns
+                .iter()
+                .flat_map(|(column, chunk_ys)| {
+                    chunk_ys
+                        .iter()
+                        .map(|y| ChunkPos::new(column.x, *y, column.y))
+                })
+                .collect();
+        }
+        let mut blocks = queue.blocks;
+        for pos in queue.removed_chunks {
+            for direction in DIRECTIONS {
+                let neighbour = pos + direction;
+                if self.get_chunk_untracked(neighbour).is_some() {
+                    blocks.extend(face_blocks(neighbour, -direction));
+                }
+            }
+        }
+        let mut reset = queue.chunks;
+        reset.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+        reset.dedup();
+        reset.retain(|pos| self.get_chunk_untracked(*pos).is_some());
+        for pos in &reset {
+            let chunk = self.get_chunk_untracked(*pos).unwrap();
+            if chunk
+                .palette()
+                .iter()
+                .any(|state| table.emission(*state) > 0)
+            {
+                let origin = *pos * Chunk::SIZE;
+                blocks.extend(
+                    chunk
+                        .iter_non_air_blocks()
+                        .filter(|(_, state)| table.emission(*state) > 0)
+                        .map(|(local_pos, _)| origin + local_pos),
+                );
+            }
+            if self.get_chunk_untracked(*pos + IVec3::Y).is_none() {
+                blocks.extend(face_blocks(*pos, IVec3::Y)); //lit by the sky
+            }
+            //the light coming from the neighbours, and the light they got from the previous blocks of the chunk
+            for direction in DIRECTIONS {
+                let neighbour = *pos + direction;
+                if self.get_chunk_untracked(neighbour).is_some() {
+                    blocks.extend(face_blocks(neighbour, -direction));
+                }
+            }
+        }
+        blocks.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+        blocks.dedup();
+        blocks.retain(|pos| self.get_chunk_untracked(split_pos(*pos).0).is_some());
+        if blocks.is_empty() && reset.is_empty() {
+            return Vec::new();
+        }
+
+        let mut world = LightWorld::new(&*self, table, &reset);
+        for channel in LightChannel::ALL {
+            world.relight(channel, &blocks);
+        }
+
+        let lights = world.lights;
+        let mut modified = Vec::with_capacity(lights.len());
+        for (pos, mut light) in lights {
+            if let Some(chunk) = self.get_chunk_untracked_mut(pos) {
+                light.compact();
+                *chunk.light_mut() = light;
+                modified.push(pos);
+            }
+        }
+        modified.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+        modified
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::AIR;
+
+    const STONE: BlockState = BlockState::from_raw(1);
+    const TORCH: BlockState = BlockState::from_raw(2);
+
+    fn light_table() -> LightTable {
+        let mut table = LightTable::new();
+        table.set_emission(TORCH, 14);
+        table.set_opacity(TORCH, 0);
+        table
+    }
+
+    ///two chunks of air side by side, the first one has a stone roof at y = 10
+    fn chunk_manager() -> ChunkManager {
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::ZERO));
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::X));
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block_at(x, 10, z, STONE);
+            }
+        }
+        assert!(chunk_manager.changes.light.blocks.is_empty()); //queued by the first update_light
+        chunk_manager
+    }
+
+    #[test]
+    pub fn block_light() {
+        let table = light_table();
+        let mut chunk_manager = chunk_manager();
+        chunk_manager.update_light(&table);
+
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(15, 5, 8, TORCH);
+        let modified = chunk_manager.update_light(&table);
+        assert_eq!(modified, vec![ChunkPos::ZERO, ChunkPos::X]);
+
+        let light = |pos| chunk_manager.get_light(LightChannel::Block, pos).unwrap();
+        assert_eq!(light(BlockPos::new(15, 5, 8)), 14);
+        assert_eq!(light(BlockPos::new(12, 5, 8)), 11);
+        assert_eq!(light(BlockPos::new(17, 5, 8)), 12); //in the other chunk
+        assert_eq!(light(BlockPos::new(15, 10, 8)), 0); //the roof is opaque
+        assert_eq!(light(BlockPos::new(15, 11, 8)), 6); //around the roof
+
+        //a second torch keeps its light when the first one is removed
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(2, 5, 8, TORCH);
+        chunk_manager.update_light(&table);
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(15, 5, 8, AIR);
+        chunk_manager.update_light(&table);
+
+        let light = |pos| chunk_manager.get_light(LightChannel::Block, pos).unwrap();
+        assert_eq!(light(BlockPos::new(15, 5, 8)), 1);
+        assert_eq!(light(BlockPos::new(17, 5, 8)), 0);
+        assert_eq!(light(BlockPos::new(3, 5, 8)), 13);
+    }
+
+    #[test]
+    pub fn sky_light() {
+        let table = light_table();
+        let mut chunk_manager = chunk_manager();
+        chunk_manager.update_light(&table);
+
+        let light = |chunk_manager: &ChunkManager, x, y, z| {
+            chunk_manager
+                .get_light(LightChannel::Sky, BlockPos::new(x, y, z))
+                .unwrap()
+        };
+        assert_eq!(light(&chunk_manager, 8, 12, 8), 15);
+        assert_eq!(light(&chunk_manager, 8, 10, 8), 0);
+        assert_eq!(light(&chunk_manager, 8, 5, 8), 7); //from the open chunk
+        assert_eq!(light(&chunk_manager, 20, 0, 8), 15);
+        let chunk = chunk_manager.get_chunk(ChunkPos::X).unwrap();
+        assert_eq!(
+            chunk.light().array(LightChannel::Sky).uniform_level(),
+            Some(15)
+        );
+
+        //a hole in the roof
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(2, 10, 2, AIR);
+        chunk_manager.update_light(&table);
+        assert_eq!(light(&chunk_manager, 2, 0, 2), 15);
+        assert_eq!(light(&chunk_manager, 3, 0, 2), 14);
+
+        //a chunk above the roof cuts the sky
+        chunk_manager.insert_chunk(Chunk::new_uniform(ChunkPos::Y, STONE));
+        chunk_manager.update_light(&table);
+        assert_eq!(light(&chunk_manager, 8, 12, 8), 7);
+        assert_eq!(light(&chunk_manager, 2, 0, 2), 1);
+        assert_eq!(light(&chunk_manager, 20, 0, 8), 15);
+
+        chunk_manager.remove_chunk(ChunkPos::Y);
+        chunk_manager.update_light(&table);
+        assert_eq!(light(&chunk_manager, 8, 12, 8), 15);
+        assert_eq!(light(&chunk_manager, 2, 0, 2), 15);
+    }
+
+    #[test]
+    pub fn inserted_and_dirty_chunks() {
+        let table = light_table();
+        let mut chunk_manager = chunk_manager();
+        chunk_manager.update_light(&table);
+
+        //a new chunk is lit by its own sources and lights its neighbours
+        let mut chunk = Chunk::new(ChunkPos::NEG_X);
+        chunk.set_block_at(15, 5, 8, TORCH);
+        chunk_manager.insert_chunk(chunk);
+        let modified = chunk_manager.update_light(&table);
+        assert_eq!(modified, vec![ChunkPos::NEG_X, ChunkPos::ZERO]);
+        let light = |chunk_manager: &ChunkManager, channel, x, y, z| {
+            chunk_manager
+                .get_light(channel, BlockPos::new(x, y, z))
+                .unwrap()
+        };
+        assert_eq!(light(&chunk_manager, LightChannel::Block, -1, 5, 8), 14);
+        assert_eq!(light(&chunk_manager, LightChannel::Block, 1, 5, 8), 12);
+        assert_eq!(light(&chunk_manager, LightChannel::Sky, -8, 0, 8), 15);
+        assert_eq!(light(&chunk_manager, LightChannel::Sky, -1, 5, 8), 15);
+        assert_eq!(light(&chunk_manager, LightChannel::Sky, 1, 5, 8), 13); //under the roof, lit from the side
+
+        //the light the neighbours got from the previous blocks of a dirty chunk is removed
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::NEG_X).unwrap();
+        chunk.set_blocks(&[STONE; Chunk::BLOCK_COUNT]);
+        chunk_manager.update_light(&table);
+        assert_eq!(light(&chunk_manager, LightChannel::Block, -1, 5, 8), 0);
+        assert_eq!(light(&chunk_manager, LightChannel::Block, 1, 5, 8), 0);
+        assert_eq!(light(&chunk_manager, LightChannel::Sky, -8, 15, 8), 0);
+        assert_eq!(light(&chunk_manager, LightChannel::Sky, 1, 5, 8), 0); //too far from the other side
+    }
+}
@@ -1,12 +1,37 @@
+mod biomes;
+mod block_entities;
+mod block_journal;
+mod chunk_guard;
+mod concurrent;
+mod eviction;
+mod fluids;
+mod heightmaps;
+mod light_engine;
+mod pathfinding;
+mod physics;
+mod raycast;
+mod snapshot;
+mod ticks;
+
+use crate::heightmap::HeightmapRules;
 use crate::Chunk;
+use chunk_guard::ChangeTracker;
 use math::aabb::AABB;
 use math::positions::ChunkPos;
-use math::{I16Vec3, IVec3};
-use std::collections::HashMap;
+use math::{I16Vec3, IVec2, IVec3};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use utils::array_utils::ArrayUtils;
-use utils::spare_set::{Id, IdTracker};
+use utils::spare_set::{Id, IdTracker, SparseSet};
 
-const NODE_SUBDIVISION: i32 = 8; //power of 2 are nice because they can be optimized by the compiler, this value couldn't really be changed without rewriting the tree_index_iterator function (which is a bit ugly)
+pub use block_journal::{BlockJournal, ChunkChanges, ChunkUpdate};
+pub use chunk_guard::{BlockChange, ChunkGuard};
+pub use concurrent::ConcurrentChunkManager;
+pub use eviction::{EvictionPolicy, MemoryBudget};
+pub use raycast::{MissingChunks, RaycastHit};
+pub use snapshot::WorldSnapshot;
+
+pub(crate) const NODE_SUBDIVISION: i32 = 8; //power of 2 are nice because they can be optimized by the compiler, this value couldn't really be changed without rewriting the tree_index_iterator function (which is a bit ugly)
 
 ///a node in the octree, it can be a leaf or a branch
 trait Node {
@@ -21,11 +46,28 @@ trait Node {
 
     ///return the child at a given position, this position should be in the range [0, 8 * 2^level[
     fn get_chunk(&self, pos: IVec3) -> Option<&Chunk>;
-    fn get_chunk_mut(&mut self, pos: IVec3) -> Option<&mut Chunk>;
+    ///same as get_chunk but with mutable capabilities, the id is given to track the modifications
+    fn get_chunk_mut(&mut self, pos: IVec3) -> Option<(Id, &mut Chunk)>;
 
     ///emplace a chunk at a given position, this position should be in the range [0, 8 * 2^level[
     fn emplace_chunk(&mut self, chunk: Chunk, pos: IVec3, id_tracker: &mut IdTracker) -> Id;
 
+    ///remove the chunk at a given position and free its id, this position should be in the range [0, 8 * 2^level[
+    ///empty children are dropped on the way back up
+    fn remove_chunk(&mut self, pos: IVec3, id_tracker: &mut IdTracker) -> Option<(Id, Chunk)>;
+
+    ///remove all loaded chunks that intersect the given AABB, free their ids and give them to the out func
+    ///empty children are dropped on the way back up
+    fn remove_chunks_in(
+        &mut self,
+        global_aabb: AABB,
+        id_tracker: &mut IdTracker,
+        out_func: &mut impl FnMut(Id, Chunk),
+    );
+
+    ///return true if the node doesn't contain any chunk anymore, in this case it can be dropped by its parent
+    fn is_empty(&self) -> bool;
+
     ///put all loaded chunks that intersect the given AABB in the out vec
     fn for_chunk_in<'a>(&'a self, global_aabb: AABB, out_func: &mut impl FnMut(Id, &'a Chunk));
 
@@ -47,10 +89,13 @@ trait Node {
 
     ///put all loaded chunks in the node in the out vec
     fn for_all_chunks<'a>(&'a self, out_func: &mut impl FnMut(Id, &'a Chunk));
+
+    ///call the out func for all loaded chunks in the node with mutable capabilities
+    fn for_all_chunks_mut(&mut self, out_func: &mut impl FnMut(Id, &mut Chunk));
 }
 
 ///get the index of the child with local position
-fn get_index_from_pos(pos: IVec3) -> usize {
+pub(crate) fn get_index_from_pos(pos: IVec3) -> usize {
     debug_assert!(pos.x < NODE_SUBDIVISION, "x to big");
     debug_assert!(pos.y < NODE_SUBDIVISION, "y to big");
     debug_assert!(pos.z < NODE_SUBDIVISION, "z to big");
@@ -147,6 +192,7 @@ struct Leaf {
 struct Level1 {
     global_pos: IVec3,
     children: [Option<Leaf>; NODE_SUBDIVISION.pow(3) as usize],
+    chunk_count: u16, //number of children that are Some, avoid scanning the whole array to know if the node is empty
 }
 
 impl Level1 {
@@ -160,6 +206,7 @@ impl Node for Level1 {
         Self {
             global_pos,
             children: [Self::INIT; NODE_SUBDIVISION.pow(3) as usize],
+            chunk_count: 0,
         }
     }
 
@@ -175,19 +222,59 @@ impl Node for Level1 {
         leaf.as_ref().map(|x| &x.chunk)
     }
 
-    fn get_chunk_mut(&mut self, pos: IVec3) -> Option<&mut Chunk> {
+    fn get_chunk_mut(&mut self, pos: IVec3) -> Option<(Id, &mut Chunk)> {
         let index = get_index_from_pos(pos);
         let leaf = &mut self.children[index];
-        leaf.as_mut().map(|x| &mut x.chunk)
+        leaf.as_mut().map(|x| (x.id, &mut x.chunk))
     }
 
     fn emplace_chunk(&mut self, chunk: Chunk, pos: IVec3, id_tracker: &mut IdTracker) -> Id {
         let index = get_index_from_pos(pos);
         let id = id_tracker.alloc();
-        self.children[index] = Some(Leaf { chunk, id });
+        if let Some(old_leaf) = self.children[index].replace(Leaf { chunk, id }) {
+            id_tracker.free(old_leaf.id); //the old chunk is dropped, its id can be reused
+        } else {
+            self.chunk_count += 1;
+        }
         id
     }
 
+    fn remove_chunk(&mut self, pos: IVec3, id_tracker: &mut IdTracker) -> Option<(Id, Chunk)> {
+        let index = get_index_from_pos(pos);
+        let leaf = self.children[index].take()?;
+        self.chunk_count -= 1;
+        id_tracker.free(leaf.id);
+        Some((leaf.id, leaf.chunk))
+    }
+
+    fn remove_chunks_in(
+        &mut self,
+        global_aabb: AABB,
+        id_tracker: &mut IdTracker,
+        out_func: &mut impl FnMut(Id, Chunk),
+    ) {
+        if !global_aabb.intersects(&self.get_aabb()) {
+            return;
+        }
+
+        for child in &mut self.children {
+            let intersects = child.as_ref().is_some_and(|leaf| {
+                let position = leaf.chunk.position();
+                global_aabb.intersects(&AABB::new(position, position + IVec3::ONE))
+            });
+            if intersects {
+                let leaf = child.take().unwrap();
+                self.chunk_count -= 1;
+                id_tracker.free(leaf.id);
+                out_func(leaf.id, leaf.chunk);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.chunk_count == 0
+    }
+
     fn for_chunk_in<'a>(&'a self, global_aabb: AABB, out_func: &mut impl FnMut(Id, &'a Chunk)) {
         let this_aabb = self.get_aabb();
 
@@ -263,11 +350,18 @@ impl Node for Level1 {
             }
         }
     }
+
+    fn for_all_chunks_mut(&mut self, out_func: &mut impl FnMut(Id, &mut Chunk)) {
+        for leaf in self.children.iter_mut().flatten() {
+            out_func(leaf.id, &mut leaf.chunk);
+        }
+    }
 }
 
 struct LevelN<CHILD: Node> {
     global_pos: IVec3,
     children: [Option<Box<CHILD>>; NODE_SUBDIVISION.pow(3) as usize],
+    child_count: u16, //number of children that are Some
 }
 
 impl<T: Node> LevelN<T> {
@@ -288,6 +382,7 @@ impl<T: Node> Node for LevelN<T> {
         Self {
             global_pos,
             children: [Self::INIT; NODE_SUBDIVISION.pow(3) as usize],
+            child_count: 0,
         }
     }
 
@@ -305,7 +400,7 @@ impl<T: Node> Node for LevelN<T> {
             .and_then(|child| child.get_chunk(pos_in_child))
     }
 
-    fn get_chunk_mut(&mut self, pos: IVec3) -> Option<&mut Chunk> {
+    fn get_chunk_mut(&mut self, pos: IVec3) -> Option<(Id, &mut Chunk)> {
         let (local_pos, pos_in_child) = Self::split_pos(pos);
         let index = get_index_from_pos(local_pos);
         self.children[index]
@@ -324,10 +419,49 @@ impl<T: Node> Node for LevelN<T> {
             let mut child = Box::new(T::new(global_pos));
             let id = child.emplace_chunk(chunk, pos_in_child, id_tracker);
             self.children[index] = Some(child);
+            self.child_count += 1;
             id
         }
     }
 
+    fn remove_chunk(&mut self, pos: IVec3, id_tracker: &mut IdTracker) -> Option<(Id, Chunk)> {
+        let (local_pos, pos_in_child) = Self::split_pos(pos);
+        let index = get_index_from_pos(local_pos);
+
+        let child = self.children[index].as_mut()?;
+        let removed = child.remove_chunk(pos_in_child, id_tracker);
+        if child.is_empty() {
+            self.children[index] = None;
+            self.child_count -= 1;
+        }
+        removed
+    }
+
+    fn remove_chunks_in(
+        &mut self,
+        global_aabb: AABB,
+        id_tracker: &mut IdTracker,
+        out_func: &mut impl FnMut(Id, Chunk),
+    ) {
+        if !global_aabb.intersects(&self.get_aabb()) {
+            return;
+        }
+
+        for child_slot in &mut self.children {
+            if let Some(child) = child_slot {
+                child.remove_chunks_in(global_aabb, id_tracker, out_func);
+                if child.is_empty() {
+                    *child_slot = None;
+                    self.child_count -= 1;
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.child_count == 0
+    }
+
     fn for_chunk_in<'a>(&'a self, global_aabb: AABB, out_func: &mut impl FnMut(Id, &'a Chunk)) {
         //if the local_aabb totally contains the node, we can put all the chunks in the out vec
         let this_aabb = self.get_aabb();
@@ -401,6 +535,12 @@ impl<T: Node> Node for LevelN<T> {
             }
         }
     }
+
+    fn for_all_chunks_mut(&mut self, out_func: &mut impl FnMut(Id, &mut Chunk)) {
+        for child in self.children.iter_mut().flatten() {
+            child.for_all_chunks_mut(out_func);
+        }
+    }
 }
 
 type Level2 = LevelN<Level1>;
@@ -410,6 +550,9 @@ type Level3 = LevelN<Level2>;
 ///a section is a 512 chunks wide cube
 type Section = Level3; //also works with Level3
 
+///the depth of a section, the other spatial indexes of the world split their sections the same way
+pub(crate) const SECTION_LEVEL: u32 = Section::LEVEL;
+
 ///this chunks manager cut the world in section of 4096 chunks, it has some cool properties:
 ///for all 32bits blockState position, there is a unique 16 bits region position, because :
 /// WorldSize / (ChunkSize * RegionSize) = 2^32 / (2^4 * 2^16) = 2^16
@@ -426,7 +569,15 @@ type Section = Level3; //also works with Level3
 pub struct ChunkManager {
     section_map: HashMap<I16Vec3, Section>, //using an octree to store the entire world would require 11 level of depth, which is a lot, the hashmap skip 6 level of depth, where the nodes are sparse and the hashmap is more efficient
     chunk_id_tracker: IdTracker,            //attribute an unique ID to each chunk
-    chunk_modified: Vec<Id>, //track all the chunks that have been modified, this tick, for various purpose, like caching meshes or packets, or for saving the world
+    changes: ChangeTracker, //track all the chunks and blocks that have been modified, this tick, for various purpose, like caching meshes or packets, or for saving the world
+    chunk_positions: SparseSet<ChunkPos>, //the position of each loaded chunk by id, so the ids of the modified chunks can be resolved
+    columns: HashMap<IVec2, BTreeSet<i32>>, //the y of the loaded chunks of each column, so the heightmaps can be stacked without walking the octree vertically
+    heightmap_rules: HeightmapRules,
+    memory_budget: Option<MemoryBudget>,
+    access_clock: AtomicU64, //incremented at each access to a chunk, see Chunk::last_access
+    ticking_chunks: HashSet<ChunkPos>, //the loaded chunks with scheduled ticks, so run_scheduled_ticks doesn't walk over all the chunks
+    tick_order: u64, //the scheduling order of the next tick, the ticks due the same tick run in this order
+    current_tick: u64, //the tick of the last run_scheduled_ticks, the saved ticks are relative to it
 }
 
 impl ChunkManager {
@@ -434,13 +585,44 @@ impl ChunkManager {
         Self {
             section_map: HashMap::new(),
             chunk_id_tracker: IdTracker::new(),
-            chunk_modified: Vec::new(),
+            changes: ChangeTracker::default(),
+            chunk_positions: SparseSet::new(),
+            columns: HashMap::new(),
+            heightmap_rules: HeightmapRules::new(),
+            memory_budget: None,
+            access_clock: AtomicU64::new(1),
+            ticking_chunks: HashSet::new(),
+            tick_order: 0,
+            current_tick: 0,
         }
     }
 
+    fn next_access_time(&self) -> u64 {
+        self.access_clock.fetch_add(1, Ordering::Relaxed)
+    }
+
     ///register a chunk in the World, this function mark the chunk as modified this tick
+    ///a chunk already loaded at the same position is dropped
     pub fn insert_chunk(&mut self, chunk: Chunk) {
+        let id = self.emplace_chunk(chunk);
+        self.make_dirty(id);
+    }
+
+    ///register a chunk without marking it as modified, like a chunk read from the storage that is the same as the saved one
+    ///its heightmaps and its light are still computed since they aren't stored with the chunk
+    pub(crate) fn insert_chunk_untracked(&mut self, chunk: Chunk) {
+        let pos = chunk.position();
+        self.emplace_chunk(chunk);
+        self.changes.light.push_chunk(pos);
+        self.reset_heightmaps(pos);
+    }
+
+    ///store the chunk in the octree and the bookkeeping of the positions, without marking it as modified
+    fn emplace_chunk(&mut self, mut chunk: Chunk) -> Id {
         let pos = chunk.position();
+        self.remove_chunk(pos); //the old chunk's id must be forgotten everywhere
+        self.adopt_ticks(&mut chunk);
+        chunk.touch(self.next_access_time());
         let region_pos = pos
             .div_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT))
             .as_i16vec3(); //euclid division is important here, else the sign of the number will be wrong
@@ -456,35 +638,120 @@ impl ChunkManager {
             id
         };
 
-        self.make_dirty(id);
+        self.chunk_positions.insert(id, pos);
+        self.add_to_column(pos);
+        id
     }
 
-    ///get a chunk in the world, this function doesn't mark the chunk as modified
-    pub fn get_chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+    ///unregister the chunk at the given position and give it back, its id is freed and may be reused by the next inserted chunk
+    ///the nodes and the section that become empty are dropped
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> Option<Chunk> {
         let region_pos = pos
             .div_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT))
             .as_i16vec3();
         let local_pos = pos.rem_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT));
-        if let Some(section) = self.section_map.get(&region_pos) {
-            section.get_chunk(local_pos)
-        } else {
-            None
+
+        let section = self.section_map.get_mut(&region_pos)?;
+        let removed = section.remove_chunk(local_pos, &mut self.chunk_id_tracker);
+        if section.is_empty() {
+            self.section_map.remove(&region_pos);
         }
+
+        let (id, chunk) = removed?;
+        self.chunk_positions.remove(id);
+        self.forget_dirty(id);
+        self.remove_from_column(pos);
+        self.ticking_chunks.remove(&pos);
+        self.changes.light.push_removed_chunk(pos);
+        Some(chunk)
     }
 
-    ///get a chunk in the world with mutable capabilities
-    pub fn get_chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+    ///unregister all loaded chunks in the given AABB and give them back, their ids are freed
+    ///the nodes and the sections that become empty are dropped
+    pub fn remove_chunks_in(&mut self, chunk_aabb: AABB) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut removed_ids = HashSet::new();
+        let out_func = &mut |id, chunk| {
+            removed_ids.insert(id);
+            chunks.push(chunk);
+        };
+
+        let chunk_id_tracker = &mut self.chunk_id_tracker;
+        self.section_map.retain(|pos, section| {
+            let section_aabb = AABB::new(
+                pos.as_ivec3() * Section::SIDE_CHUNK_COUNT,
+                (pos.as_ivec3() + IVec3::ONE) * Section::SIDE_CHUNK_COUNT,
+            );
+            if let Some(intersection) = chunk_aabb.get_intersection(&section_aabb) {
+                section.remove_chunks_in(intersection, chunk_id_tracker, out_func);
+            }
+            !section.is_empty()
+        });
+
+        self.changes.forget(|id| removed_ids.contains(&id));
+        for id in removed_ids {
+            self.chunk_positions.remove(id);
+        }
+        for chunk in &chunks {
+            self.remove_from_column(chunk.position());
+            self.ticking_chunks.remove(&chunk.position());
+            self.changes.light.push_removed_chunk(chunk.position());
+        }
+        chunks
+    }
+
+    ///get a chunk in the world, this function doesn't mark the chunk as modified but counts as an access, see EvictionPolicy::LeastRecentlyUsed
+    pub fn get_chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        let chunk = self.get_chunk_untracked(pos)?;
+        chunk.touch(self.next_access_time());
+        Some(chunk)
+    }
+
+    ///get a chunk without counting it as an access, for the reads made by the ChunkManager itself, like update_light or find_path
+    pub(crate) fn get_chunk_untracked(&self, pos: ChunkPos) -> Option<&Chunk> {
         let region_pos = pos
             .div_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT))
             .as_i16vec3();
         let local_pos = pos.rem_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT));
-        let (section_map, _) = (&mut self.section_map, &mut self.chunk_modified);
-        if let Some(section) = section_map.get_mut(&region_pos) {
-            section.get_chunk_mut(local_pos)
-            //TODO: mark the chunk as modified
-        } else {
-            None
-        }
+        self.section_map.get(&region_pos)?.get_chunk(local_pos)
+    }
+
+    ///get the position of a loaded chunk from its id, useful to resolve the ids given by on_process_modified_chunks
+    pub fn get_chunk_position(&self, id: Id) -> Option<ChunkPos> {
+        self.chunk_positions.get(id).copied()
+    }
+
+    ///get a loaded chunk from its id, this function doesn't mark the chunk as modified
+    pub fn get_chunk_by_id(&self, id: Id) -> Option<&Chunk> {
+        self.get_chunk(self.get_chunk_position(id)?)
+    }
+
+    ///get a chunk in the world with mutable capabilities, the chunk is marked as modified when a block is changed through the guard
+    pub fn get_chunk_mut(&mut self, pos: ChunkPos) -> Option<ChunkGuard<'_>> {
+        let region_pos = pos
+            .div_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT))
+            .as_i16vec3();
+        let local_pos = pos.rem_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT));
+        let (section_map, changes) = (&mut self.section_map, &mut self.changes);
+        let (id, chunk) = section_map.get_mut(&region_pos)?.get_chunk_mut(local_pos)?;
+        chunk.touch(self.access_clock.fetch_add(1, Ordering::Relaxed)); //self is partially borrowed
+        Some(ChunkGuard::new(chunk, id, changes, &self.heightmap_rules))
+    }
+
+    ///get a chunk with mutable capabilities without marking it as modified, only for the data that isn't tracked, like the light
+    fn get_chunk_untracked_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.get_chunk_entry_mut(pos).map(|(_, chunk)| chunk)
+    }
+
+    ///same as get_chunk_untracked_mut with the id of the chunk, so the caller can mark it as modified itself
+    fn get_chunk_entry_mut(&mut self, pos: ChunkPos) -> Option<(Id, &mut Chunk)> {
+        let region_pos = pos
+            .div_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT))
+            .as_i16vec3();
+        let local_pos = pos.rem_euclid(IVec3::splat(Section::SIDE_CHUNK_COUNT));
+        self.section_map
+            .get_mut(&region_pos)?
+            .get_chunk_mut(local_pos)
     }
 
     ///get all loaded chunks in the given AABB, this function doesn't mark the chunks as modified
@@ -542,16 +809,18 @@ impl ChunkManager {
         chunks
     }
 
-    ///return all loaded chunks that intersect the given AABB
-    /// WARNING: right now this function doesn't mark the chunks as modified to avoid useless update, but it should
-    /// you should mark the chunk you modify as modified
-    pub fn get_chunk_with_predicate_mut<'a>(
-        &'a mut self,
+    ///call the out func for all loaded chunks that intersect the given AABB and that satisfy the predicate, with mutable capabilities
+    ///the chunks are given through a guard, so only the chunks where a block is changed are marked as modified
+    pub fn foreach_chunk_with_predicate_mut(
+        &mut self,
         chunk_aabb: AABB,
         predicate: impl Fn(AABB) -> bool + Copy,
-    ) -> Vec<&mut Chunk> {
-        let mut chunks = Vec::with_capacity(chunk_aabb.get_volume() as usize);
-        let out_func = &mut |_, chunk: &'a mut Chunk| chunks.push(chunk);
+        mut out_func: impl FnMut(ChunkGuard<'_>),
+    ) {
+        let (changes, heightmap_rules) = (&mut self.changes, &self.heightmap_rules);
+        let out_func = &mut |id, chunk: &mut Chunk| {
+            out_func(ChunkGuard::new(chunk, id, changes, heightmap_rules))
+        };
 
         self.section_map.iter_mut().for_each(|(pos, section)| {
             let section_aabb = AABB::new(
@@ -562,19 +831,190 @@ impl ChunkManager {
                 section.for_chunk_with_predicate_mut(intersection, predicate, out_func);
             }
         });
-        chunks
     }
 
-    ///get a slice of all the chunks that have been modified this tick, it will also clear the list,
-    pub fn on_process_modified_chunks(&mut self, func: impl FnOnce(&[Id])) {
-        self.chunk_modified.sort_by(|a, b| a.raw().cmp(&b.raw()));
-        self.chunk_modified.dedup();
-        func(&self.chunk_modified);
-        self.chunk_modified.clear();
+    ///maintenance pass that demote every loaded chunk to the smallest format that can hold its blocks, see Chunk::compact
+    ///it walks over all the chunks, so it should be run from time to time rather than each tick
+    ///the chunks aren't marked as modified since their content doesn't change, return the number of demoted chunks
+    pub fn compact_chunks(&mut self) -> usize {
+        let mut demoted = 0;
+        for section in self.section_map.values_mut() {
+            section.for_all_chunks_mut(&mut |_, chunk| {
+                if chunk.compact() {
+                    demoted += 1;
+                }
+            });
+        }
+        demoted
+    }
+
+    ///get a slice of all the chunks that have been modified this tick and the journal of the blocks changed through a ChunkGuard
+    ///it will also clear both. The chunks inserted or marked with make_dirty need a full resend, see BlockJournal::updates
+    pub fn on_process_modified_chunks(&mut self, func: impl FnOnce(&[Id], &BlockJournal)) {
+        let changes = &mut self.changes;
+        changes.chunk_modified.sort_by(|a, b| a.raw().cmp(&b.raw()));
+        changes.chunk_modified.dedup();
+        changes.journal.coalesce();
+        func(&changes.chunk_modified, &changes.journal);
+        changes.chunk_modified.clear();
+        changes.journal.clear();
     }
 
     ///mark a chunk as modified, calling this function will likely refresh all caches that depend on the chunk
+    ///the journal can't know what changed, so the chunk will need a full resend, its light and its heightmaps will be computed again
+    ///by the next call to update_light and the next query of get_height
     pub fn make_dirty(&mut self, id: Id) {
-        self.chunk_modified.push(id);
+        self.changes.chunk_modified.push(id);
+        if let Some(pos) = self.chunk_positions.get(id).copied() {
+            self.changes.journal.mark_full_resend(id, pos);
+            self.changes.light.push_chunk(pos);
+            self.reset_heightmaps(pos);
+        }
+    }
+
+    ///report a chunk as modified without a full resend, for the changes that aren't in the journal, like a failed save
+    pub(crate) fn mark_modified(&mut self, id: Id) {
+        self.changes.chunk_modified.push(id);
+    }
+
+    ///a removed chunk shouldn't be reported as modified, its id could already belong to another chunk
+    fn forget_dirty(&mut self, id: Id) {
+        self.changes.forget(|removed| removed == id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::{BlockState, AIR};
+    use math::positions::BlockPos;
+
+    #[test]
+    pub fn remove_chunks() {
+        let mut chunk_manager = ChunkManager::new();
+        for x in -4..4 {
+            for y in -4..4 {
+                chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(x * 100, y, 3)));
+            }
+        }
+        assert_eq!(chunk_manager.section_map.len(), 4);
+
+        let chunk = chunk_manager
+            .remove_chunk(ChunkPos::new(-100, 2, 3))
+            .unwrap();
+        assert_eq!(chunk.position(), ChunkPos::new(-100, 2, 3));
+        assert!(chunk_manager.get_chunk(ChunkPos::new(-100, 2, 3)).is_none());
+        assert!(chunk_manager
+            .remove_chunk(ChunkPos::new(-100, 2, 3))
+            .is_none());
+
+        let removed = chunk_manager.remove_chunks_in(AABB::new(
+            IVec3::new(-1000, -1000, -1000),
+            IVec3::new(0, 1000, 1000),
+        ));
+        assert_eq!(removed.len(), 4 * 8 - 1);
+        assert_eq!(chunk_manager.section_map.len(), 2); //the sections with negative x are dropped
+
+        //the freed ids are reused
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(0, 0, 0)));
+        let mut max_id = 0;
+        chunk_manager.foreach_chunk_in(
+            AABB::new(IVec3::splat(-1000), IVec3::splat(1000)),
+            &mut |id, _| max_id = max_id.max(id.raw()),
+        );
+        assert!(max_id < 64);
+
+        chunk_manager.remove_chunks_in(AABB::new(IVec3::splat(-1000), IVec3::splat(1000)));
+        assert!(chunk_manager.section_map.is_empty());
+        chunk_manager.on_process_modified_chunks(|ids, _| assert!(ids.is_empty()));
+    }
+
+    #[test]
+    pub fn track_changes() {
+        let stone = BlockState::from_raw(1);
+        let mut chunk_manager = ChunkManager::new();
+        for x in 0..4 {
+            chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(x, 0, 0)));
+        }
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 4);
+            assert!(journal
+                .updates(16)
+                .all(|(_, update)| update == ChunkUpdate::Full));
+        });
+
+        //setting a block to its current state isn't a change
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(1, 2, 3, AIR);
+        chunk_manager.on_process_modified_chunks(|ids, _| assert!(ids.is_empty()));
+
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        let id = chunk.id();
+        chunk.set_block_at(1, 2, 3, stone);
+        chunk.set_block_at(1, 2, 4, stone);
+        chunk_manager.foreach_chunk_with_predicate_mut(
+            AABB::new(IVec3::new(2, 0, 0), IVec3::new(4, 1, 1)),
+            |_| true,
+            |mut chunk| {
+                if chunk.position().x == 3 {
+                    chunk.set_block_at(0, 0, 0, stone);
+                }
+            },
+        );
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 2);
+            assert!(ids.contains(&id));
+            let changes = journal.get(id).unwrap().changes();
+            assert_eq!(changes.len(), 2);
+            assert_eq!(changes[0].pos, BlockPos::new(1, 2, 3));
+            assert_eq!(changes[0].old_state, AIR);
+            assert_eq!(changes[0].new_state, stone);
+            assert_eq!(journal.chunks()[1].chunk_pos(), ChunkPos::new(3, 0, 0));
+        });
+    }
+
+    #[test]
+    pub fn block_journal() {
+        let stone = BlockState::from_raw(1);
+        let dirt = BlockState::from_raw(2);
+        let mut chunk_manager = ChunkManager::new();
+        for x in 0..3 {
+            chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(x, 0, 0)));
+        }
+        chunk_manager.on_process_modified_chunks(|_, _| {});
+
+        //the same block changed several times is coalesced
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::new(0, 0, 0)).unwrap();
+        chunk.set_block_at(1, 1, 1, stone);
+        chunk.set_block_at(1, 1, 1, dirt);
+        chunk.set_block_at(2, 2, 2, stone);
+
+        //a block that goes back to its first state isn't a change anymore
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::new(1, 0, 0)).unwrap();
+        chunk.set_block_at(1, 1, 1, stone);
+        chunk.set_block_at(1, 1, 1, AIR);
+
+        //too many changes, the whole chunk is resent
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::new(2, 0, 0)).unwrap();
+        for x in 0..10 {
+            chunk.set_block_at(x, 0, 0, stone);
+        }
+
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 3);
+            let updates = journal.updates(8).collect::<Vec<_>>();
+            assert_eq!(updates.len(), 2);
+
+            assert_eq!(updates[0].0.chunk_pos(), ChunkPos::new(0, 0, 0));
+            let ChunkUpdate::Delta(changes) = updates[0].1 else {
+                panic!("expected a delta");
+            };
+            assert_eq!(changes.len(), 2);
+            assert_eq!(changes[0].old_state, AIR);
+            assert_eq!(changes[0].new_state, dirt);
+
+            assert_eq!(updates[1].0.chunk_pos(), ChunkPos::new(2, 0, 0));
+            assert_eq!(updates[1].1, ChunkUpdate::Full);
+        });
     }
 }
@@ -0,0 +1,385 @@
+use crate::block_state::BlockState;
+use crate::pathfinding::{MoveKind, Path, PathMove, PathOptions};
+use crate::physics::CollisionShapes;
+use crate::{Chunk, ChunkManager};
+use math::positions::{BlockPos, ChunkPos};
+use math::IVec3;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+const DIRECTIONS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+///the chunks read by a search, the same few chunks are read over and over so each one is looked up in the octree once
+struct ChunkReader<'a> {
+    chunk_manager: &'a ChunkManager,
+    shapes: &'a CollisionShapes,
+    chunks: HashMap<ChunkPos, Option<&'a Chunk>>,
+}
+
+impl<'a> ChunkReader<'a> {
+    ///return None if the chunk of the block isn't loaded
+    fn get_block(&mut self, pos: BlockPos) -> Option<BlockState> {
+        let chunk_pos = pos.div_euclid(IVec3::splat(Chunk::SIZE));
+        let chunk = *self
+            .chunks
+            .entry(chunk_pos)
+            .or_insert_with(|| self.chunk_manager.get_chunk_untracked(chunk_pos));
+        chunk.map(|chunk| chunk.get_block(pos.rem_euclid(IVec3::splat(Chunk::SIZE))))
+    }
+
+    ///a block without collision shape, the blocks of the missing chunks are neither free nor ground
+    fn is_free(&mut self, pos: BlockPos) -> bool {
+        matches!(self.get_block(pos), Some(state) if self.shapes.get_shape(state).is_empty())
+    }
+
+    fn is_ground(&mut self, pos: BlockPos) -> bool {
+        matches!(self.get_block(pos), Some(state) if !self.shapes.get_shape(state).is_empty())
+    }
+}
+
+///find the moves an entity can do from a position
+struct Walker<'a> {
+    reader: ChunkReader<'a>,
+    options: &'a PathOptions,
+    width: i32,
+    height: i32,
+}
+
+impl<'a> Walker<'a> {
+    ///return true if the blocks taken by the entity from feet up to height are free
+    fn is_free(&mut self, feet: BlockPos, height: i32) -> bool {
+        for y in 0..height {
+            for x in 0..self.width {
+                for z in 0..self.width {
+                    if !self.reader.is_free(feet + IVec3::new(x, y, z)) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    ///return true if at least one block under the entity can hold it
+    fn is_supported(&mut self, feet: BlockPos) -> bool {
+        for x in 0..self.width {
+            for z in 0..self.width {
+                if self.reader.is_ground(feet + IVec3::new(x, -1, z)) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn can_stand(&mut self, feet: BlockPos) -> bool {
+        self.is_free(feet, self.height) && self.is_supported(feet)
+    }
+
+    fn neighbours(&mut self, pos: BlockPos, out: &mut Vec<(BlockPos, MoveKind)>) {
+        let climb = self.options.max_step.max(self.options.max_jump);
+        for direction in DIRECTIONS {
+            let next = pos + direction;
+            if self.is_free(next, self.height) {
+                if self.is_supported(next) {
+                    out.push((next, MoveKind::Walk));
+                    continue;
+                }
+                //fall until something holds the entity
+                for drop in 1..=self.options.max_drop {
+                    let below = next - IVec3::Y * drop;
+                    if !self.is_free(below, 1) {
+                        break;
+                    }
+                    if self.is_supported(below) {
+                        out.push((below, MoveKind::Drop));
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            for rise in 1..=climb {
+                //the entity needs room above its head to go up
+                if !self.is_free(pos + IVec3::Y * (self.height + rise - 1), 1) {
+                    break;
+                }
+                let above = next + IVec3::Y * rise;
+                if self.can_stand(above) {
+                    let kind = match rise <= self.options.max_step {
+                        true => MoveKind::Step,
+                        false => MoveKind::Jump,
+                    };
+                    out.push((above, kind));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+struct Node {
+    cost: f32,
+    parent: Option<(BlockPos, MoveKind)>,
+    closed: bool,
+}
+
+///a position waiting to be explored, the heap gives the lowest estimate first
+struct OpenNode {
+    estimate: f32,
+    distance: f32,
+    order: u64, //the positions with the same estimate are explored in the order they were found, so the result is deterministic
+    pos: BlockPos,
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(other.distance.total_cmp(&self.distance))
+            .then(other.order.cmp(&self.order))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl ChunkManager {
+    ///find a walkable path between the feet positions start and goal with A*, see pathfinding::default_cost for the requirements of the cost function
+    ///the blocks without collision shape are walked through, the missing chunks are avoided
+    ///return None if the entity can't stand at start, else the path to the goal or a partial path if the goal can't be reached within options.max_visited positions
+    pub fn find_path(
+        &self,
+        start: BlockPos,
+        goal: BlockPos,
+        options: &PathOptions,
+        shapes: &CollisionShapes,
+        mut cost: impl FnMut(&PathMove) -> Option<f32>,
+    ) -> Option<Path> {
+        let mut walker = Walker {
+            reader: ChunkReader {
+                chunk_manager: self,
+                shapes,
+                chunks: HashMap::new(),
+            },
+            options,
+            width: options.block_width(),
+            height: options.block_height(),
+        };
+        if !walker.can_stand(start) {
+            return None;
+        }
+
+        //each move goes one block further horizontally for a cost of at least 1, so this never overestimates
+        let heuristic = |pos: BlockPos| {
+            let delta = (goal - pos).abs();
+            (delta.x + delta.z) as f32
+        };
+        let distance = |pos: BlockPos| (goal - pos).abs().element_sum();
+
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            start,
+            Node {
+                cost: 0.0,
+                parent: None,
+                closed: false,
+            },
+        );
+        let mut open = BinaryHeap::new();
+        open.push(OpenNode {
+            estimate: heuristic(start),
+            distance: heuristic(start),
+            order: 0,
+            pos: start,
+        });
+        let mut order = 0;
+        let mut closest = start; //the explored position the closest to the goal, the end of the partial path
+        let mut visited = 0;
+        let mut neighbours = Vec::new();
+
+        while let Some(OpenNode { pos, .. }) = open.pop() {
+            let node = nodes.get_mut(&pos).unwrap();
+            if node.closed {
+                continue; //already reached with a lower cost
+            }
+            node.closed = true;
+            let node_cost = node.cost;
+            visited += 1;
+
+            let closest_cost = nodes[&closest].cost;
+            if (distance(pos), node_cost) < (distance(closest), closest_cost) {
+                closest = pos;
+            }
+            if pos == goal || visited >= options.max_visited {
+                break;
+            }
+
+            walker.neighbours(pos, &mut neighbours);
+            for (next, kind) in neighbours.drain(..) {
+                let path_move = PathMove {
+                    from: pos,
+                    to: next,
+                    kind,
+                };
+                let Some(move_cost) = cost(&path_move) else {
+                    continue;
+                };
+                let next_cost = node_cost + move_cost;
+                if matches!(nodes.get(&next), Some(node) if node.closed || node.cost <= next_cost) {
+                    continue;
+                }
+                nodes.insert(
+                    next,
+                    Node {
+                        cost: next_cost,
+                        parent: Some((pos, kind)),
+                        closed: false,
+                    },
+                );
+                order += 1;
+                open.push(OpenNode {
+                    estimate: next_cost + heuristic(next),
+                    distance: heuristic(next),
+                    order,
+                    pos: next,
+                });
+            }
+        }
+
+        //walk back from the end of the path
+        let mut positions = vec![closest];
+        let mut moves = Vec::new();
+        while let Some((parent, kind)) = nodes[positions.last().unwrap()].parent {
+            positions.push(parent);
+            moves.push(kind);
+        }
+        positions.reverse();
+        moves.reverse();
+        Some(Path {
+            positions,
+            moves,
+            complete: closest == goal,
+            cost: nodes[&closest].cost,
+            visited,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pathfinding::default_cost;
+
+    fn stone() -> BlockState {
+        BlockState::from_raw(1)
+    }
+
+    fn chunk_manager() -> ChunkManager {
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        for x in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                chunk.set_block_at(x, 4, z, stone()); //the floor
+            }
+        }
+        for z in 0..=12 {
+            for y in 5..8 {
+                chunk.set_block_at(8, y, z, stone()); //a wall too high to jump over
+            }
+        }
+        chunk.set_block_at(12, 5, 12, stone()); //a pillar
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(chunk);
+        chunk_manager
+    }
+
+    #[test]
+    pub fn walk_around_wall() {
+        let chunk_manager = chunk_manager();
+        let shapes = CollisionShapes::new();
+        let options = PathOptions::default();
+        let start = BlockPos::new(2, 5, 2);
+        let goal = BlockPos::new(12, 5, 2);
+
+        let path = chunk_manager
+            .find_path(start, goal, &options, &shapes, default_cost)
+            .unwrap();
+        assert!(path.complete);
+        assert_eq!(path.positions.first(), Some(&start));
+        assert_eq!(path.end(), goal);
+        assert_eq!(path.positions.len(), 33); //around the end of the wall at z = 13
+        assert_eq!(path.cost, 32.0);
+        for pair in path.positions.windows(2) {
+            assert_eq!((pair[1] - pair[0]).abs().element_sum(), 1);
+        }
+
+        //the start must be walkable
+        assert!(chunk_manager
+            .find_path(
+                BlockPos::new(8, 5, 2),
+                goal,
+                &options,
+                &shapes,
+                default_cost
+            )
+            .is_none());
+    }
+
+    #[test]
+    pub fn climb_and_partial() {
+        let chunk_manager = chunk_manager();
+        let shapes = CollisionShapes::new();
+        let mut options = PathOptions::default();
+        let start = BlockPos::new(10, 5, 14);
+        let goal = BlockPos::new(12, 6, 12); //on the pillar
+
+        let path = chunk_manager
+            .find_path(start, goal, &options, &shapes, default_cost)
+            .unwrap();
+        assert!(path.complete);
+        assert_eq!(path.moves.last(), Some(&MoveKind::Jump));
+
+        //and down again
+        let path = chunk_manager
+            .find_path(goal, start, &options, &shapes, default_cost)
+            .unwrap();
+        assert!(path.complete);
+        assert_eq!(path.moves.first(), Some(&MoveKind::Drop));
+
+        //the cost function can forbid the moves
+        let path = chunk_manager
+            .find_path(
+                start,
+                goal,
+                &options,
+                &shapes,
+                |path_move| match path_move.kind {
+                    MoveKind::Jump => None,
+                    _ => default_cost(path_move),
+                },
+            )
+            .unwrap();
+        assert!(!path.complete);
+        assert_eq!((goal - path.end()).abs().element_sum(), 2); //next to the pillar
+
+        options.max_visited = 5;
+        let path = chunk_manager
+            .find_path(start, goal, &options, &shapes, default_cost)
+            .unwrap();
+        assert!(!path.complete);
+        assert_eq!(path.visited, 5);
+        assert!((goal - path.end()).abs().element_sum() < (goal - start).abs().element_sum());
+    }
+}
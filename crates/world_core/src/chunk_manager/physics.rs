@@ -0,0 +1,187 @@
+use super::raycast::ChunkCache;
+use crate::physics::{resolve_motion, Collision, CollisionShapes};
+use crate::{Chunk, ChunkManager, MissingChunks};
+use math::aabb::FloatAABB;
+use math::positions::EntityPos;
+use math::{IVec3, Vec3};
+
+impl ChunkManager {
+    ///move an AABB placed relatively to position by the velocity, against the collision shapes of the loaded blocks, see physics::resolve_motion
+    ///the sweep is done relatively to the chunk of the position to keep the precision of the f32 far from the world origin
+    ///every block around the whole motion is looked up, so the velocity should stay in the order of a few blocks per tick
+    pub fn sweep_aabb(
+        &self,
+        position: EntityPos,
+        aabb: FloatAABB,
+        velocity: Vec3,
+        step_height: f32,
+        shapes: &CollisionShapes,
+        missing_chunks: MissingChunks,
+    ) -> Collision {
+        let position = position.shrink();
+        let base = position.chunk_pos * Chunk::SIZE; //the world position of the block at the origin of the relative space
+        let aabb = aabb.offset(position.relative_pos);
+
+        let reach =
+            aabb.expand_towards(velocity)
+                .expand_towards(Vec3::new(0.0, step_height.max(0.0), 0.0));
+        let min = reach.min().floor().as_ivec3() - IVec3::Y; //the shapes of the blocks below can be up to 2 blocks high
+        let max = reach.max().floor().as_ivec3();
+
+        let mut chunks = ChunkCache::new(self, position.chunk_pos);
+        let mut colliders = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let block = IVec3::new(x, y, z);
+                    let shape: &[FloatAABB] = match chunks.get_block(base + block) {
+                        Some(state) => shapes.get_shape(state),
+                        None if missing_chunks == MissingChunks::Solid => &[FloatAABB::UNIT],
+                        None => &[],
+                    };
+                    colliders.extend(
+                        shape
+                            .iter()
+                            .map(|collider| collider.offset(block.as_vec3())),
+                    );
+                }
+            }
+        }
+
+        resolve_motion(&colliders, aabb, velocity, step_height)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::BlockState;
+    use math::positions::ChunkPos;
+
+    fn stone() -> BlockState {
+        BlockState::from_raw(1)
+    }
+
+    fn slab() -> BlockState {
+        BlockState::from_raw(2)
+    }
+
+    fn setup() -> (ChunkManager, CollisionShapes) {
+        let mut chunk = Chunk::new(ChunkPos::ZERO);
+        for x in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                chunk.set_block_at(x, 4, z, stone()); //the floor
+            }
+        }
+        chunk.set_block_at(8, 5, 2, stone()); //a wall
+        chunk.set_block_at(8, 6, 2, stone());
+        chunk.set_block_at(4, 5, 2, slab());
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(chunk);
+
+        let mut shapes = CollisionShapes::new();
+        shapes.set_shape(
+            slab(),
+            vec![FloatAABB::new(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))],
+        );
+        (chunk_manager, shapes)
+    }
+
+    #[test]
+    pub fn fall_and_walls() {
+        let (chunk_manager, shapes) = setup();
+        let hitbox = FloatAABB::from_feet(0.6, 1.8);
+        let sweep = |relative_pos, velocity| {
+            let position = EntityPos::new(ChunkPos::ZERO, relative_pos);
+            chunk_manager.sweep_aabb(
+                position,
+                hitbox,
+                velocity,
+                0.0,
+                &shapes,
+                MissingChunks::Empty,
+            )
+        };
+
+        let collision = sweep(Vec3::new(2.5, 7.0, 2.5), Vec3::new(0.0, -5.0, 0.0));
+        assert!((collision.motion.y + 2.0).abs() < 1e-4);
+        assert!(collision.grounded);
+        assert!(collision.collided.y && !collision.collided.x);
+
+        //slide along the wall, the motion on the other axes is kept
+        let collision = sweep(Vec3::new(7.0, 5.0, 2.5), Vec3::new(2.0, -0.1, 0.5));
+        assert!((collision.motion.x - 0.7).abs() < 1e-4);
+        assert_eq!(collision.motion.z, 0.5);
+        assert!(collision.grounded && collision.collided.x && !collision.collided.z);
+
+        //nothing stops the box in the air
+        let collision = sweep(Vec3::new(2.5, 7.0, 2.5), Vec3::new(0.5, 0.5, 0.5));
+        assert_eq!(collision.motion, Vec3::splat(0.5));
+        assert!(!collision.grounded && !collision.collided.any());
+    }
+
+    #[test]
+    pub fn step_up() {
+        let (chunk_manager, shapes) = setup();
+        let hitbox = FloatAABB::from_feet(0.6, 1.8);
+        let position = EntityPos::new(ChunkPos::ZERO, Vec3::new(3.5, 5.0, 2.5));
+        let velocity = Vec3::new(0.5, -0.1, 0.0);
+
+        let blocked = chunk_manager.sweep_aabb(
+            position,
+            hitbox,
+            velocity,
+            0.0,
+            &shapes,
+            MissingChunks::Empty,
+        );
+        assert!((blocked.motion.x - 0.2).abs() < 1e-4);
+        assert!(blocked.collided.x);
+
+        let stepped = chunk_manager.sweep_aabb(
+            position,
+            hitbox,
+            velocity,
+            0.6,
+            &shapes,
+            MissingChunks::Empty,
+        );
+        assert!((stepped.motion - Vec3::new(0.5, 0.5, 0.0)).length() < 1e-4);
+        assert!(stepped.grounded && !stepped.collided.x);
+
+        //a full block is too high
+        let position = EntityPos::new(ChunkPos::ZERO, Vec3::new(7.5, 5.0, 2.5));
+        let collision = chunk_manager.sweep_aabb(
+            position,
+            hitbox,
+            velocity,
+            0.6,
+            &shapes,
+            MissingChunks::Empty,
+        );
+        assert!((collision.motion.x - 0.2).abs() < 1e-4);
+        assert!(collision.motion.y.abs() < 1e-4);
+
+        //the missing chunks can block like the raycasts
+        let position = EntityPos::new(ChunkPos::ZERO, Vec3::new(0.5, 5.0, 2.5));
+        let velocity = Vec3::new(-1.0, 0.0, 0.0);
+        let collision = chunk_manager.sweep_aabb(
+            position,
+            hitbox,
+            velocity,
+            0.0,
+            &shapes,
+            MissingChunks::Solid,
+        );
+        assert!((collision.motion.x + 0.2).abs() < 1e-4);
+        let collision = chunk_manager.sweep_aabb(
+            position,
+            hitbox,
+            velocity,
+            0.0,
+            &shapes,
+            MissingChunks::Empty,
+        );
+        assert_eq!(collision.motion.x, -1.0);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::block_state::{BlockState, AIR};
+use crate::{Chunk, ChunkManager};
+use math::positions::{BlockPos, ChunkPos, EntityPos};
+use math::{IVec3, Vec3};
+
+///how a raycast should handle the chunks that aren't loaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingChunks {
+    ///stop the ray on the first block of a missing chunk, useful on the server to not let a player reach through unloaded terrain
+    Solid,
+    ///go through missing chunks like they were full of air
+    Empty,
+}
+
+///the first block hit by a raycast
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    ///the position of the block in the world
+    pub block_pos: BlockPos,
+    ///the state of the block, None if the ray stopped in a missing chunk
+    pub state: Option<BlockState>,
+    ///the normal of the face hit by the ray, zero if the ray started inside the block
+    pub normal: IVec3,
+    ///the point where the ray enters the block
+    pub point: EntityPos,
+    ///the distance between the origin of the ray and the point
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    ///the position of the block on the other side of the hit face, where a block would be placed
+    pub fn adjacent_pos(&self) -> BlockPos {
+        self.block_pos + self.normal
+    }
+}
+
+///keep the last chunk used by the ray, most steps stay in the same chunk so the octree is rarely walked
+pub(super) struct ChunkCache<'a> {
+    chunk_manager: &'a ChunkManager,
+    chunk_pos: ChunkPos,
+    chunk: Option<&'a Chunk>,
+}
+
+impl<'a> ChunkCache<'a> {
+    pub(super) fn new(chunk_manager: &'a ChunkManager, chunk_pos: ChunkPos) -> Self {
+        Self {
+            chunk_manager,
+            chunk_pos,
+            chunk: chunk_manager.get_chunk_untracked(chunk_pos),
+        }
+    }
+
+    ///return None if the chunk of the block isn't loaded
+    pub(super) fn get_block(&mut self, block_pos: BlockPos) -> Option<BlockState> {
+        let chunk_pos = block_pos.div_euclid(IVec3::splat(Chunk::SIZE));
+        if chunk_pos != self.chunk_pos {
+            self.chunk_pos = chunk_pos;
+            self.chunk = self.chunk_manager.get_chunk_untracked(chunk_pos);
+        }
+        let local_pos = block_pos.rem_euclid(IVec3::splat(Chunk::SIZE));
+        self.chunk.map(|chunk| chunk.get_block(local_pos))
+    }
+}
+
+impl ChunkManager {
+    ///walk the blocks crossed by a ray (Amanatides & Woo voxel traversal) and return the first non-air block, or None if there is no block before max_distance
+    ///return None if max_distance is negative or isn't finite, an infinite ray would never end with MissingChunks::Empty
+    ///the direction doesn't need to be normalized, the traversal is done relatively to the chunk of the origin to keep the precision of the f32 far from the world origin
+    pub fn raycast(
+        &self,
+        origin: EntityPos,
+        direction: Vec3,
+        max_distance: f32,
+        missing_chunks: MissingChunks,
+    ) -> Option<RaycastHit> {
+        if !max_distance.is_finite() || max_distance < 0.0 {
+            return None;
+        }
+        let direction = direction.try_normalize()?;
+        let origin = origin.shrink();
+        let base = origin.chunk_pos * Chunk::SIZE; //the world position of the block at the origin of the relative space
+        let start = origin.relative_pos;
+
+        let mut voxel = start.floor().as_ivec3();
+        let step = IVec3::new(sign(direction.x), sign(direction.y), sign(direction.z));
+        let t_delta = direction.recip().abs(); //the distance needed to cross a whole block on each axis, infinite if the ray is parallel to the axis
+        let mut t_max = Vec3::ZERO; //the distance at which the ray crosses the next block boundary on each axis
+        for axis in 0..3 {
+            t_max[axis] = match step[axis] {
+                1 => (voxel[axis] as f32 + 1.0 - start[axis]) * t_delta[axis],
+                -1 => (start[axis] - voxel[axis] as f32) * t_delta[axis],
+                _ => f32::INFINITY,
+            };
+        }
+
+        let mut chunks = ChunkCache::new(self, origin.chunk_pos);
+        let mut normal = IVec3::ZERO;
+        let mut distance = 0.0;
+        loop {
+            let block_pos = base + voxel;
+            let hit = match chunks.get_block(block_pos) {
+                Some(AIR) => None,
+                Some(state) => Some(Some(state)),
+                None if missing_chunks == MissingChunks::Solid => Some(None),
+                None => None,
+            };
+            if let Some(state) = hit {
+                return Some(RaycastHit {
+                    block_pos,
+                    state,
+                    normal,
+                    point: EntityPos::new(origin.chunk_pos, start + direction * distance).shrink(),
+                    distance,
+                });
+            }
+
+            //cross the nearest block boundary
+            let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+                0
+            } else if t_max.y < t_max.z {
+                1
+            } else {
+                2
+            };
+            distance = t_max[axis];
+            if distance > max_distance {
+                return None;
+            }
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+    }
+}
+
+fn sign(value: f32) -> i32 {
+    if value > 0.0 {
+        1
+    } else if value < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stone() -> BlockState {
+        BlockState::from_raw(1)
+    }
+
+    fn chunk_manager() -> ChunkManager {
+        let mut chunk_manager = ChunkManager::new();
+        for x in -1..=1 {
+            chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(x, 0, 0)));
+        }
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::new(1, 0, 0)).unwrap();
+        chunk.set_block_at(4, 5, 5, stone()); //the block x = 20 in the world
+        let mut chunk = chunk_manager
+            .get_chunk_mut(ChunkPos::new(-1, 0, 0))
+            .unwrap();
+        chunk.set_block_at(15, 0, 0, stone()); //the block x = -1 in the world
+        chunk_manager
+    }
+
+    #[test]
+    pub fn hit_across_chunks() {
+        let chunk_manager = chunk_manager();
+        let origin = EntityPos::new(ChunkPos::ZERO, Vec3::new(2.5, 5.5, 5.5));
+
+        let hit = chunk_manager
+            .raycast(origin, Vec3::X, 100.0, MissingChunks::Empty)
+            .unwrap();
+        assert_eq!(hit.block_pos, BlockPos::new(20, 5, 5));
+        assert_eq!(hit.state, Some(stone()));
+        assert_eq!(hit.normal, IVec3::NEG_X);
+        assert_eq!(hit.adjacent_pos(), BlockPos::new(19, 5, 5));
+        assert!((hit.distance - 17.5).abs() < 1e-4);
+        assert_eq!(hit.point.chunk_pos, ChunkPos::new(1, 0, 0));
+        assert!((hit.point.relative_pos - Vec3::new(4.0, 5.5, 5.5)).length() < 1e-4);
+
+        //too short
+        assert!(chunk_manager
+            .raycast(origin, Vec3::X, 17.0, MissingChunks::Empty)
+            .is_none());
+
+        //diagonal ray toward the negative side, the origin isn't shrunk
+        let origin = EntityPos::new(ChunkPos::new(1, 0, 0), Vec3::new(-13.5, 0.5, 0.5));
+        let hit = chunk_manager
+            .raycast(
+                origin,
+                Vec3::new(-1.0, -0.1, -0.1),
+                10.0,
+                MissingChunks::Empty,
+            )
+            .unwrap();
+        assert_eq!(hit.block_pos, BlockPos::new(-1, 0, 0));
+        assert_eq!(hit.normal, IVec3::X);
+    }
+
+    #[test]
+    pub fn missing_chunks() {
+        let chunk_manager = chunk_manager();
+        let origin = EntityPos::new(ChunkPos::ZERO, Vec3::new(8.5, 8.5, 8.5));
+
+        assert!(chunk_manager
+            .raycast(origin, Vec3::Z, 100.0, MissingChunks::Empty)
+            .is_none());
+        let hit = chunk_manager
+            .raycast(origin, Vec3::Z, 100.0, MissingChunks::Solid)
+            .unwrap();
+        assert_eq!(hit.block_pos, BlockPos::new(8, 8, 16));
+        assert_eq!(hit.state, None);
+        assert_eq!(hit.normal, IVec3::NEG_Z);
+
+        //the ray starts inside a block
+        let origin = EntityPos::new(ChunkPos::new(1, 0, 0), Vec3::new(4.5, 5.5, 5.5));
+        let hit = chunk_manager
+            .raycast(origin, Vec3::Y, 10.0, MissingChunks::Empty)
+            .unwrap();
+        assert_eq!(hit.normal, IVec3::ZERO);
+        assert_eq!(hit.distance, 0.0);
+        assert!(chunk_manager
+            .raycast(origin, Vec3::ZERO, 10.0, MissingChunks::Empty)
+            .is_none());
+    }
+
+    #[test]
+    pub fn invalid_max_distance() {
+        let chunk_manager = chunk_manager();
+        let origin = EntityPos::new(ChunkPos::ZERO, Vec3::new(8.5, 8.5, 8.5));
+        for max_distance in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, -1.0] {
+            assert!(chunk_manager
+                .raycast(origin, Vec3::Z, max_distance, MissingChunks::Empty)
+                .is_none());
+        }
+        //the ray starts inside the block, so a distance of 0 still hits it
+        let origin = EntityPos::new(ChunkPos::new(1, 0, 0), Vec3::new(4.5, 5.5, 5.5));
+        assert!(chunk_manager
+            .raycast(origin, Vec3::Y, 0.0, MissingChunks::Empty)
+            .is_some());
+    }
+}
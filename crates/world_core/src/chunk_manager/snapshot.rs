@@ -0,0 +1,91 @@
+use crate::block_state::BlockState;
+use crate::{Chunk, ChunkManager, ChunkSnapshot};
+use math::aabb::AABB;
+use math::positions::{BlockPos, ChunkPos};
+use math::IVec3;
+use std::collections::HashMap;
+
+///an immutable copy of a region of the ChunkManager, it can be sent to worker threads while the ChunkManager keeps changing
+///each chunk shares its memory with the loaded one until the next modification, see Chunk::snapshot
+#[derive(Clone, Default)]
+pub struct WorldSnapshot {
+    chunks: HashMap<ChunkPos, ChunkSnapshot>,
+}
+
+impl WorldSnapshot {
+    pub fn get_chunk(&self, pos: ChunkPos) -> Option<&ChunkSnapshot> {
+        self.chunks.get(&pos)
+    }
+
+    ///get the blockstate at the given world position, None if its chunk isn't in the snapshot
+    pub fn get_block(&self, pos: BlockPos) -> Option<BlockState> {
+        let chunk = self.get_chunk(pos.div_euclid(IVec3::splat(Chunk::SIZE)))?;
+        Some(chunk.get_block(pos.rem_euclid(IVec3::splat(Chunk::SIZE))))
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &ChunkSnapshot> {
+        self.chunks.values()
+    }
+
+    ///the number of chunks in the snapshot
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    ///add the chunks of another snapshot, used to gather the snapshots of the shards of a ConcurrentChunkManager
+    pub(super) fn merge(&mut self, other: WorldSnapshot) {
+        self.chunks.extend(other.chunks);
+    }
+}
+
+impl ChunkManager {
+    ///take a snapshot of the loaded chunks in the given AABB, nothing is copied until the chunks are modified
+    ///this function doesn't mark the chunks as modified
+    pub fn snapshot(&self, chunk_aabb: AABB) -> WorldSnapshot {
+        let mut chunks = HashMap::new();
+        self.foreach_chunk_in(chunk_aabb, &mut |_, chunk| {
+            chunks.insert(chunk.position(), chunk.snapshot());
+        });
+        WorldSnapshot { chunks }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::AIR;
+
+    #[test]
+    pub fn snapshot_region() {
+        let stone = BlockState::from_raw(1);
+        let mut chunk_manager = ChunkManager::new();
+        for x in -2..2 {
+            let mut chunk = Chunk::new(ChunkPos::new(x, 0, 0));
+            chunk.set_block_at(0, 0, 0, stone);
+            chunk_manager.insert_chunk(chunk);
+        }
+
+        let snapshot =
+            chunk_manager.snapshot(AABB::new(ChunkPos::new(-1, 0, 0), ChunkPos::new(2, 1, 1)));
+        assert_eq!(snapshot.len(), 3);
+        assert!(snapshot.get_chunk(ChunkPos::new(-2, 0, 0)).is_none());
+
+        let mut chunk = chunk_manager
+            .get_chunk_mut(ChunkPos::new(-1, 0, 0))
+            .unwrap();
+        chunk.set_block_at(0, 0, 0, AIR);
+        let worker = std::thread::spawn(move || snapshot.get_block(BlockPos::new(-16, 0, 0)));
+        assert_eq!(worker.join().unwrap(), Some(stone));
+        assert_eq!(
+            chunk_manager
+                .get_chunk(ChunkPos::new(-1, 0, 0))
+                .unwrap()
+                .get_block_at(0, 0, 0),
+            AIR
+        );
+    }
+}
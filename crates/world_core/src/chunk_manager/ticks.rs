@@ -0,0 +1,251 @@
+use super::Node;
+use crate::block_state::{BlockState, AIR};
+use crate::tick::splitmix64;
+use crate::{Chunk, ChunkManager};
+use math::positions::{BlockPos, ChunkPos};
+use math::IVec3;
+
+impl ChunkManager {
+    ///schedule a block update at the given tick, it's run by run_scheduled_ticks and saved with the chunk
+    ///a block has at most one scheduled tick, the earliest is kept
+    ///return false if the chunk isn't loaded or if the block already has a tick due earlier or at the same tick
+    ///the chunk is marked as modified so the tick is saved, but it isn't sent again since its blocks didn't change
+    pub fn schedule_tick(&mut self, pos: BlockPos, due_tick: u64) -> bool {
+        let chunk_pos = pos.div_euclid(IVec3::splat(Chunk::SIZE));
+        let order = self.tick_order;
+        let Some((id, chunk)) = self.get_chunk_entry_mut(chunk_pos) else {
+            return false;
+        };
+        if !chunk.scheduled_ticks_mut().schedule(
+            pos.rem_euclid(IVec3::splat(Chunk::SIZE)),
+            due_tick,
+            order,
+        ) {
+            return false;
+        }
+        self.tick_order += 1;
+        self.ticking_chunks.insert(chunk_pos);
+        self.mark_modified(id);
+        true
+    }
+
+    ///return false if the block doesn't have a scheduled tick, like schedule_tick the chunk is marked as modified without a resend
+    pub fn cancel_tick(&mut self, pos: BlockPos) -> bool {
+        let chunk_pos = pos.div_euclid(IVec3::splat(Chunk::SIZE));
+        let Some((id, chunk)) = self.get_chunk_entry_mut(chunk_pos) else {
+            return false;
+        };
+        let ticks = chunk.scheduled_ticks_mut();
+        let cancelled = ticks.cancel(pos.rem_euclid(IVec3::splat(Chunk::SIZE)));
+        if ticks.is_empty() {
+            self.ticking_chunks.remove(&chunk_pos);
+        }
+        if cancelled {
+            self.mark_modified(id);
+        }
+        cancelled
+    }
+
+    ///get the due tick of the block, None if it doesn't have a scheduled tick or if its chunk isn't loaded
+    pub fn scheduled_tick(&self, pos: BlockPos) -> Option<u64> {
+        let chunk = self.get_chunk_untracked(pos.div_euclid(IVec3::splat(Chunk::SIZE)))?;
+        chunk
+            .scheduled_ticks()
+            .get(pos.rem_euclid(IVec3::splat(Chunk::SIZE)))
+    }
+
+    ///the tick given to the last call to run_scheduled_ticks, 0 before the first one
+    ///the ticks are saved as delays from this tick, so the tick counter given to run_scheduled_ticks can start again from 0 at each launch
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    ///run the ticks due at or before the current tick, by due tick then in the order they were scheduled
+    ///the func gets the world, so it can change blocks and schedule new ticks, the ticks it schedules for the current tick run at the next call
+    ///the tick of a block whose chunk has been unloaded by a previous func is dropped, return the number of ticks run
+    ///the chunks whose ticks ran are marked as modified, so the ticks aren't loaded again from a previous save
+    pub fn run_scheduled_ticks(
+        &mut self,
+        current_tick: u64,
+        mut func: impl FnMut(&mut ChunkManager, BlockPos, BlockState),
+    ) -> usize {
+        self.current_tick = current_tick;
+        let mut due = Vec::new();
+        let ticking_chunks: Vec<ChunkPos> = self.ticking_chunks.iter().copied().collect();
+        for chunk_pos in ticking_chunks {
+            let Some((id, chunk)) = self.get_chunk_entry_mut(chunk_pos) else {
+                self.ticking_chunks.remove(&chunk_pos);
+                continue;
+            };
+            let ticks = chunk.scheduled_ticks_mut();
+            match ticks.next_due_tick() {
+                Some(tick) if tick <= current_tick => (),
+                _ => continue,
+            }
+            let chunk_origin = chunk_pos * Chunk::SIZE;
+            for (due_tick, order, pos) in ticks.take_due(current_tick) {
+                due.push((due_tick, order, chunk_origin + pos));
+            }
+            if ticks.is_empty() {
+                self.ticking_chunks.remove(&chunk_pos);
+            }
+            self.mark_modified(id);
+        }
+        //the order doesn't depend on the order of the hash set, the position only matters for ticks loaded from different chunks
+        due.sort_unstable_by_key(|(due_tick, order, pos)| (*due_tick, *order, pos.to_array()));
+
+        let mut run = 0;
+        for (_, _, pos) in due {
+            let chunk_pos = pos.div_euclid(IVec3::splat(Chunk::SIZE));
+            let Some(chunk) = self.get_chunk_untracked(chunk_pos) else {
+                continue;
+            };
+            let state = chunk.get_block(pos.rem_euclid(IVec3::splat(Chunk::SIZE)));
+            func(self, pos, state);
+            run += 1;
+        }
+        run
+    }
+
+    ///pick random_tick_speed random blocks in each loaded chunk and call the func with the ones that aren't air, like the random ticks of vanilla
+    ///the blocks only depend on the seed, the current tick and the loaded chunks, so a replay gives the same result
+    ///the chunks aren't counted as accessed, return the number of calls to the func
+    pub fn run_random_ticks(
+        &mut self,
+        current_tick: u64,
+        random_tick_speed: u32,
+        seed: u64,
+        mut func: impl FnMut(&mut ChunkManager, BlockPos, BlockState),
+    ) -> usize {
+        if random_tick_speed == 0 {
+            return 0;
+        }
+        let mut chunks = Vec::new();
+        for section in self.section_map.values() {
+            section.for_all_chunks(&mut |_, chunk| {
+                if !chunk.is_empty() {
+                    chunks.push(chunk.position());
+                }
+            });
+        }
+        chunks.sort_unstable_by_key(|pos| pos.to_array());
+
+        let tick_seed = splitmix64(seed ^ splitmix64(current_tick));
+        let mut run = 0;
+        for chunk_pos in chunks {
+            let mut random = tick_seed;
+            for coord in chunk_pos.to_array() {
+                random = splitmix64(random ^ coord as u32 as u64);
+            }
+            for _ in 0..random_tick_speed {
+                random = splitmix64(random);
+                //a previous func may have unloaded or emptied the chunk
+                let Some(chunk) = self.get_chunk_untracked(chunk_pos) else {
+                    break;
+                };
+                let local_pos = crate::chunk::block_pos_of(random as usize % Chunk::BLOCK_COUNT);
+                let state = chunk.get_block(local_pos);
+                if state != AIR {
+                    func(self, chunk_pos * Chunk::SIZE + local_pos, state);
+                    run += 1;
+                }
+            }
+        }
+        run
+    }
+
+    ///give the ticks of a chunk about to be inserted an order after the ticks already scheduled, and track the chunk if it has some
+    pub(super) fn adopt_ticks(&mut self, chunk: &mut Chunk) {
+        let ticks = std::mem::take(chunk.scheduled_ticks_mut());
+        if ticks.is_empty() {
+            return;
+        }
+        for (pos, due_tick) in ticks.iter() {
+            chunk
+                .scheduled_ticks_mut()
+                .schedule(pos, due_tick, self.tick_order);
+            self.tick_order += 1;
+        }
+        self.ticking_chunks.insert(chunk.position());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn scheduled_ticks() {
+        let stone = BlockState::from_raw(1);
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new_uniform(ChunkPos::ZERO, stone));
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(-1, 0, 0)));
+        chunk_manager.on_process_modified_chunks(|_, _| ());
+
+        assert!(chunk_manager.schedule_tick(BlockPos::new(3, 4, 5), 10));
+        assert!(chunk_manager.schedule_tick(BlockPos::new(-3, 4, 5), 10));
+        assert!(chunk_manager.schedule_tick(BlockPos::new(1, 1, 1), 5));
+        assert!(!chunk_manager.schedule_tick(BlockPos::new(1, 1, 1), 8));
+        assert!(!chunk_manager.schedule_tick(BlockPos::new(100, 0, 0), 1)); //not loaded
+        assert_eq!(
+            chunk_manager.scheduled_tick(BlockPos::new(1, 1, 1)),
+            Some(5)
+        );
+
+        //the chunks are marked as modified without a resend
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 2);
+            assert!(journal.is_empty());
+        });
+
+        let mut run = Vec::new();
+        let count = chunk_manager.run_scheduled_ticks(5, |_, pos, state| run.push((pos, state)));
+        chunk_manager.on_process_modified_chunks(|ids, _| assert_eq!(ids.len(), 1));
+        assert_eq!(count, 1);
+        assert_eq!(run, vec![(BlockPos::new(1, 1, 1), stone)]);
+
+        //a tick scheduled while ticking waits for the next call
+        run.clear();
+        chunk_manager.run_scheduled_ticks(10, |chunk_manager, pos, state| {
+            chunk_manager.schedule_tick(pos + BlockPos::Y, 10);
+            run.push((pos, state));
+        });
+        assert_eq!(
+            run,
+            vec![
+                (BlockPos::new(3, 4, 5), stone),
+                (BlockPos::new(-3, 4, 5), AIR)
+            ]
+        );
+        assert!(chunk_manager.cancel_tick(BlockPos::new(3, 5, 5)));
+        assert_eq!(chunk_manager.run_scheduled_ticks(10, |_, _, _| ()), 1);
+        assert_eq!(chunk_manager.run_scheduled_ticks(100, |_, _, _| ()), 0);
+
+        //the ticks are unloaded and loaded with the chunk
+        chunk_manager.schedule_tick(BlockPos::new(2, 2, 2), 200);
+        let chunk = chunk_manager.remove_chunk(ChunkPos::ZERO).unwrap();
+        assert_eq!(chunk_manager.run_scheduled_ticks(200, |_, _, _| ()), 0);
+        chunk_manager.insert_chunk(chunk);
+        assert_eq!(chunk_manager.run_scheduled_ticks(200, |_, _, _| ()), 1);
+    }
+
+    #[test]
+    pub fn random_ticks_are_deterministic() {
+        let stone = BlockState::from_raw(1);
+        let mut chunk_manager = ChunkManager::new();
+        for x in 0..4 {
+            chunk_manager.insert_chunk(Chunk::new_uniform(ChunkPos::new(x, 0, 0), stone));
+        }
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(0, 1, 0)));
+
+        let run = |chunk_manager: &mut ChunkManager, tick| {
+            let mut positions = Vec::new();
+            chunk_manager.run_random_ticks(tick, 3, 42, |_, pos, _| positions.push(pos));
+            positions
+        };
+        let first = run(&mut chunk_manager, 7);
+        assert_eq!(first.len(), 4 * 3);
+        assert_eq!(first, run(&mut chunk_manager, 7));
+        assert_ne!(first, run(&mut chunk_manager, 8));
+    }
+}
@@ -0,0 +1,415 @@
+use crate::chunk_manager::{get_index_from_pos, NODE_SUBDIVISION, SECTION_LEVEL};
+use math::aabb::AABB;
+use math::consts::CHUNK_SIZE_D;
+use math::positions::{ChunkPos, EntityPos};
+use math::{DVec3, I16Vec3, IVec3};
+use std::collections::HashMap;
+use utils::spare_set::{Id, IdTracker, SparseSet};
+
+///the identifier of an entity in an EntityStore, it can be reused once the entity is removed
+pub type EntityId = Id;
+
+const SECTION_SIDE_CHUNK_COUNT: i32 = NODE_SUBDIVISION.pow(SECTION_LEVEL);
+
+///a node of the entity octree, it has the same layout as the nodes of the ChunkManager but holds the entities of each chunk instead of the chunk
+struct EntityNode {
+    global_pos: IVec3,
+    level: u32, //a level 1 node holds the entities of 8^3 chunks, like the Level1 of the ChunkManager
+    entity_count: usize, //number of entities in the whole node, so the empty nodes can be dropped
+    children: NodeChildren,
+}
+
+enum NodeChildren {
+    Nodes(Box<[Option<EntityNode>]>),
+    Chunks(Box<[Vec<EntityId>]>), //only for the level 1 nodes, the entities of each chunk
+}
+
+impl EntityNode {
+    fn new(global_pos: IVec3, level: u32) -> Self {
+        let child_count = NODE_SUBDIVISION.pow(3) as usize;
+        let children = if level == 1 {
+            NodeChildren::Chunks((0..child_count).map(|_| Vec::new()).collect())
+        } else {
+            NodeChildren::Nodes((0..child_count).map(|_| None).collect())
+        };
+        Self {
+            global_pos,
+            level,
+            entity_count: 0,
+            children,
+        }
+    }
+
+    ///the number of chunks in a side of a child
+    fn child_side_chunk_count(&self) -> i32 {
+        NODE_SUBDIVISION.pow(self.level - 1)
+    }
+
+    ///the local position of the child containing the chunk, the chunk must be in the node
+    fn child_pos(&self, pos: ChunkPos) -> IVec3 {
+        (pos - self.global_pos) / self.child_side_chunk_count()
+    }
+
+    fn insert(&mut self, pos: ChunkPos, id: EntityId) {
+        let child_pos = self.child_pos(pos);
+        let child_side = self.child_side_chunk_count();
+        let index = get_index_from_pos(child_pos);
+        match &mut self.children {
+            NodeChildren::Nodes(nodes) => nodes[index]
+                .get_or_insert_with(|| {
+                    EntityNode::new(self.global_pos + child_pos * child_side, self.level - 1)
+                })
+                .insert(pos, id),
+            NodeChildren::Chunks(chunks) => chunks[index].push(id),
+        }
+        self.entity_count += 1;
+    }
+
+    ///return false if the entity wasn't in the chunk
+    fn remove(&mut self, pos: ChunkPos, id: EntityId) -> bool {
+        let index = get_index_from_pos(self.child_pos(pos));
+        let removed = match &mut self.children {
+            NodeChildren::Nodes(nodes) => match &mut nodes[index] {
+                Some(child) => {
+                    let removed = child.remove(pos, id);
+                    if child.entity_count == 0 {
+                        nodes[index] = None;
+                    }
+                    removed
+                }
+                None => false,
+            },
+            NodeChildren::Chunks(chunks) => {
+                let entities = &mut chunks[index];
+                match entities.iter().position(|entity| *entity == id) {
+                    Some(i) => {
+                        entities.swap_remove(i);
+                        if entities.is_empty() {
+                            *entities = Vec::new(); //release the memory of the crowded chunks
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            }
+        };
+        if removed {
+            self.entity_count -= 1;
+        }
+        removed
+    }
+
+    fn get_chunk_entities(&self, pos: ChunkPos) -> &[EntityId] {
+        let index = get_index_from_pos(self.child_pos(pos));
+        match &self.children {
+            NodeChildren::Nodes(nodes) => nodes[index]
+                .as_ref()
+                .map_or(&[], |child| child.get_chunk_entities(pos)),
+            NodeChildren::Chunks(chunks) => &chunks[index],
+        }
+    }
+
+    ///call the out func for all the entities in the chunks of the given AABB, only the children intersecting the AABB are visited
+    fn for_entities_in(&self, chunk_aabb: AABB, out_func: &mut impl FnMut(EntityId)) {
+        let child_side = IVec3::splat(self.child_side_chunk_count());
+        let last_child = IVec3::splat(NODE_SUBDIVISION - 1);
+        let min = (chunk_aabb.min() - self.global_pos)
+            .div_euclid(child_side)
+            .clamp(IVec3::ZERO, last_child);
+        let max = (chunk_aabb.max() - IVec3::ONE - self.global_pos)
+            .div_euclid(child_side)
+            .clamp(IVec3::ZERO, last_child);
+
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let index = get_index_from_pos(IVec3::new(x, y, z));
+                    match &self.children {
+                        NodeChildren::Nodes(nodes) => {
+                            if let Some(child) = &nodes[index] {
+                                child.for_entities_in(chunk_aabb, out_func);
+                            }
+                        }
+                        NodeChildren::Chunks(chunks) => {
+                            chunks[index].iter().for_each(|id| out_func(*id))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+///store the position of the entities and index them by chunk, with the same sections and octree as the ChunkManager
+///the positions are kept shrunk, so the chunk of an entity is always the chunk_pos of its position
+///the queries take world coordinates, the entities exactly on the boundary of the queried volume are included
+pub struct EntityStore {
+    section_map: HashMap<I16Vec3, EntityNode>,
+    id_tracker: IdTracker,
+    positions: SparseSet<EntityPos>,
+}
+
+impl Default for EntityStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntityStore {
+    pub fn new() -> Self {
+        Self {
+            section_map: HashMap::new(),
+            id_tracker: IdTracker::new(),
+            positions: SparseSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.len() == 0
+    }
+
+    ///add an entity at the given position and return its new id
+    pub fn insert(&mut self, pos: EntityPos) -> EntityId {
+        let pos = pos.shrink();
+        let id = self.id_tracker.alloc();
+        self.index(pos.chunk_pos, id);
+        self.positions.insert(id, pos);
+        id
+    }
+
+    ///remove the entity and return its last position, its id can be given to a new entity
+    pub fn remove(&mut self, id: EntityId) -> Option<EntityPos> {
+        let pos = self.positions.remove(id)?;
+        self.unindex(pos.chunk_pos, id);
+        self.id_tracker.free(id);
+        Some(pos)
+    }
+
+    pub fn get_position(&self, id: EntityId) -> Option<EntityPos> {
+        self.positions.get(id).copied()
+    }
+
+    ///move the entity, the chunk index is only updated when the entity crosses a chunk boundary
+    ///return false if the entity doesn't exist
+    pub fn set_position(&mut self, id: EntityId, pos: EntityPos) -> bool {
+        let pos = pos.shrink();
+        let Some(old_pos) = self.positions.get_mut(id) else {
+            return false;
+        };
+        let old_chunk = std::mem::replace(old_pos, pos).chunk_pos;
+        if old_chunk != pos.chunk_pos {
+            self.unindex(old_chunk, id);
+            self.index(pos.chunk_pos, id);
+        }
+        true
+    }
+
+    ///iterate over all the entities and their position, the order is not specified
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, EntityPos)> + '_ {
+        self.positions.iter().map(|(id, pos)| (id, *pos))
+    }
+
+    ///the entities whose position is in the given chunk
+    pub fn get_chunk_entities(&self, pos: ChunkPos) -> &[EntityId] {
+        let (section_pos, _) = Self::section_of(pos);
+        self.section_map
+            .get(&section_pos)
+            .map_or(&[], |section| section.get_chunk_entities(pos))
+    }
+
+    ///call the out func for all the entities in the given chunks
+    pub fn foreach_entity_in_chunks(&self, chunk_aabb: AABB, out_func: &mut impl FnMut(EntityId)) {
+        self.section_map.iter().for_each(|(pos, section)| {
+            let section_aabb = AABB::new(
+                pos.as_ivec3() * SECTION_SIDE_CHUNK_COUNT,
+                (pos.as_ivec3() + IVec3::ONE) * SECTION_SIDE_CHUNK_COUNT,
+            );
+            if let Some(intersection) = chunk_aabb.get_intersection(&section_aabb) {
+                section.for_entities_in(intersection, out_func);
+            }
+        });
+    }
+
+    ///call the out func for all the entities between min and max, min must not be greater than max
+    pub fn foreach_entity_in(
+        &self,
+        min: DVec3,
+        max: DVec3,
+        out_func: &mut impl FnMut(EntityId, EntityPos),
+    ) {
+        debug_assert!(min.cmple(max).all(), "min is greater than max");
+        self.foreach_entity_in_chunks(Self::chunk_aabb_of(min, max), &mut |id| {
+            let pos = self.positions.get(id).copied().unwrap();
+            let world_pos = DVec3::from(pos);
+            if world_pos.cmpge(min).all() && world_pos.cmple(max).all() {
+                out_func(id, pos);
+            }
+        });
+    }
+
+    ///get the entities between min and max, the order is not specified
+    pub fn get_entities_in(&self, min: DVec3, max: DVec3) -> Vec<EntityId> {
+        let mut entities = Vec::new();
+        self.foreach_entity_in(min, max, &mut |id, _| entities.push(id));
+        entities
+    }
+
+    ///get the entities at most radius blocks away from the center, the order is not specified
+    pub fn get_entities_in_radius(&self, center: DVec3, radius: f64) -> Vec<EntityId> {
+        self.get_distances_in_radius(center, radius)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    }
+
+    ///get the count entities closest to the center, from the closest to the farthest
+    ///the entities at the same distance are sorted by id, so the result doesn't depend on the order of insertion
+    pub fn get_nearest_entities(&self, center: DVec3, count: usize) -> Vec<EntityId> {
+        //the radius is doubled until enough entities are found, every entity closer than the radius is then known
+        let mut radius = CHUNK_SIZE_D;
+        let mut found = loop {
+            if count >= self.len() || !radius.is_finite() {
+                break self
+                    .iter()
+                    .map(|(id, pos)| (DVec3::from(pos).distance_squared(center), id))
+                    .collect();
+            }
+            let found = self.get_distances_in_radius(center, radius);
+            if found.len() >= count {
+                break found;
+            }
+            radius *= 2.0;
+        };
+        found.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.raw().cmp(&b.1.raw())));
+        found.truncate(count);
+        found.into_iter().map(|(_, id)| id).collect()
+    }
+
+    ///the squared distance to the center of the entities at most radius blocks away
+    fn get_distances_in_radius(&self, center: DVec3, radius: f64) -> Vec<(f64, EntityId)> {
+        let mut found = Vec::new();
+        let extent = DVec3::splat(radius);
+        self.foreach_entity_in(center - extent, center + extent, &mut |id, pos| {
+            let distance = DVec3::from(pos).distance_squared(center);
+            if distance <= radius * radius {
+                found.push((distance, id));
+            }
+        });
+        found
+    }
+
+    ///the smallest chunk AABB containing the given world volume
+    fn chunk_aabb_of(min: DVec3, max: DVec3) -> AABB {
+        let min = (min / CHUNK_SIZE_D).floor().as_ivec3();
+        let max = (max / CHUNK_SIZE_D).floor().as_ivec3();
+        AABB::new(min, max.saturating_add(IVec3::ONE))
+    }
+
+    fn section_of(pos: ChunkPos) -> (I16Vec3, IVec3) {
+        let section_pos = pos
+            .div_euclid(IVec3::splat(SECTION_SIDE_CHUNK_COUNT))
+            .as_i16vec3(); //euclid division, like in ChunkManager::insert_chunk
+        (
+            section_pos,
+            section_pos.as_ivec3() * SECTION_SIDE_CHUNK_COUNT,
+        )
+    }
+
+    fn index(&mut self, pos: ChunkPos, id: EntityId) {
+        let (section_pos, global_pos) = Self::section_of(pos);
+        self.section_map
+            .entry(section_pos)
+            .or_insert_with(|| EntityNode::new(global_pos, SECTION_LEVEL))
+            .insert(pos, id);
+    }
+
+    fn unindex(&mut self, pos: ChunkPos, id: EntityId) {
+        let (section_pos, _) = Self::section_of(pos);
+        let section = self
+            .section_map
+            .get_mut(&section_pos)
+            .expect("the entity isn't indexed");
+        let removed = section.remove(pos, id);
+        debug_assert!(removed, "the entity isn't indexed in its chunk");
+        if section.entity_count == 0 {
+            self.section_map.remove(&section_pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use math::Vec3;
+
+    #[test]
+    pub fn move_between_chunks() {
+        let mut store = EntityStore::new();
+        let id = store.insert(EntityPos::new(IVec3::ZERO, Vec3::new(15.5, 2.0, 3.0)));
+        assert_eq!(store.get_chunk_entities(IVec3::ZERO), &[id]);
+
+        //moving inside the chunk doesn't touch the index
+        assert!(store.set_position(id, EntityPos::new(IVec3::ZERO, Vec3::new(1.0, 2.0, 3.0))));
+        assert_eq!(store.get_chunk_entities(IVec3::ZERO), &[id]);
+
+        //a relative position out of the chunk is shrunk, even across a section boundary
+        let far = EntityPos::new(
+            IVec3::new(0, 0, -SECTION_SIDE_CHUNK_COUNT),
+            Vec3::new(-1.0, 2.0, 3.0),
+        );
+        assert!(store.set_position(id, far));
+        let chunk = IVec3::new(-1, 0, -SECTION_SIDE_CHUNK_COUNT);
+        assert!(store.get_chunk_entities(IVec3::ZERO).is_empty());
+        assert_eq!(store.get_chunk_entities(chunk), &[id]);
+        assert_eq!(
+            store.get_position(id).unwrap().relative_pos,
+            Vec3::new(15.0, 2.0, 3.0)
+        );
+        assert_eq!(store.section_map.len(), 1); //the empty section is dropped
+
+        assert_eq!(store.remove(id).unwrap().chunk_pos, chunk);
+        assert!(store.is_empty() && store.section_map.is_empty());
+        assert!(!store.set_position(id, far));
+    }
+
+    #[test]
+    pub fn queries() {
+        let mut store = EntityStore::new();
+        let positions = [
+            DVec3::new(0.5, 0.0, 0.5),
+            DVec3::new(3.0, 0.0, 0.0),
+            DVec3::new(-20.0, 0.0, 0.0),
+            DVec3::new(100.0, 50.0, -100.0),
+            DVec3::new(-9000.0, 0.0, 0.0),
+        ];
+        let ids: Vec<_> = positions
+            .iter()
+            .map(|pos| store.insert((*pos).into()))
+            .collect();
+        let sorted = |mut entities: Vec<EntityId>| {
+            entities.sort_by_key(|id| id.raw());
+            entities
+        };
+
+        let in_aabb =
+            store.get_entities_in(DVec3::new(-20.0, -1.0, -1.0), DVec3::new(3.0, 1.0, 1.0));
+        assert_eq!(sorted(in_aabb), ids[..3]);
+        assert_eq!(store.get_entities_in_radius(DVec3::ZERO, 5.0).len(), 2);
+        assert_eq!(
+            store.get_entities_in_radius(DVec3::new(-9000.0, 0.0, 3.0), 3.0),
+            [ids[4]]
+        );
+
+        let nearest = store.get_nearest_entities(DVec3::new(-1.0, 0.0, 0.0), 3);
+        assert_eq!(nearest, [ids[0], ids[1], ids[2]]);
+        assert_eq!(
+            store.get_nearest_entities(DVec3::new(-9000.0, 0.0, 0.0), 1),
+            [ids[4]]
+        );
+        assert_eq!(store.get_nearest_entities(DVec3::ZERO, 10).len(), 5);
+    }
+}
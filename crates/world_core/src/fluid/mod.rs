@@ -0,0 +1,125 @@
+use crate::block_state::{BlockState, BlockStateRegistry};
+use std::collections::HashMap;
+
+///the number of levels of a fluid, like the level property of vanilla
+///0 is a source, 1 to 7 are flowing from the highest to the lowest and 8 to 15 are falling
+pub const FLUID_LEVEL_COUNT: usize = 16;
+pub const SOURCE_LEVEL: u8 = 0;
+///the level given to a fluid falling from above, the other falling levels are only read
+pub const FALLING_LEVEL: u8 = 8;
+
+///the amount of fluid in a block of the given level, 8 for a full block and 1 for the lowest flowing level
+pub fn fluid_amount(level: u8) -> u8 {
+    match level {
+        1..=7 => 8 - level,
+        _ => 8,
+    }
+}
+
+///a kind of fluid, the block state of each of its levels and how it spreads
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fluid {
+    states: [BlockState; FLUID_LEVEL_COUNT],
+    ///the number of ticks between two updates of a block
+    pub tick_delay: u64,
+    ///the amount lost by each block of horizontal flow
+    pub level_decrease: u8,
+    ///how far a fluid looks for a hole to flow toward
+    pub slope_distance: u8,
+    ///a block between two sources on solid ground becomes a source
+    pub infinite_sources: bool,
+}
+
+impl Fluid {
+    ///the states are indexed by level, see FLUID_LEVEL_COUNT
+    pub fn new(states: [BlockState; FLUID_LEVEL_COUNT]) -> Self {
+        Self {
+            states,
+            tick_delay: 5,
+            level_decrease: 1,
+            slope_distance: 4,
+            infinite_sources: true,
+        }
+    }
+
+    ///the vanilla water, None if the registry doesn't have a minecraft:water block with a level property
+    pub fn water(registry: &BlockStateRegistry) -> Option<Self> {
+        Some(Self::new(Self::states_from_registry(
+            registry,
+            "minecraft:water",
+        )?))
+    }
+
+    ///the vanilla lava of the overworld
+    pub fn lava(registry: &BlockStateRegistry) -> Option<Self> {
+        Some(Self {
+            tick_delay: 30,
+            level_decrease: 2,
+            slope_distance: 2,
+            infinite_sources: false,
+            ..Self::new(Self::states_from_registry(registry, "minecraft:lava")?)
+        })
+    }
+
+    ///the states of a block by the value of its level property
+    pub fn states_from_registry(
+        registry: &BlockStateRegistry,
+        ident: &str,
+    ) -> Option<[BlockState; FLUID_LEVEL_COUNT]> {
+        let block = registry.block(ident)?;
+        let mut states = [block.default_state(); FLUID_LEVEL_COUNT];
+        for (level, state) in states.iter_mut().enumerate() {
+            *state = block.with_property(block.default_state(), "level", &level.to_string())?;
+        }
+        Some(states)
+    }
+
+    pub fn state(&self, level: u8) -> BlockState {
+        self.states[level as usize]
+    }
+}
+
+///the fluids of the world, find the fluid and the level stored in a block state
+#[derive(Clone, Debug, Default)]
+pub struct Fluids {
+    fluids: Vec<Fluid>,
+    levels: HashMap<BlockState, (usize, u8)>, //the index of the fluid and the level of each state
+}
+
+impl Fluids {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///return false if a state of the fluid already belongs to another fluid, the fluid isn't added in this case
+    pub fn register(&mut self, fluid: Fluid) -> bool {
+        if fluid
+            .states
+            .iter()
+            .any(|state| self.levels.contains_key(state))
+        {
+            return false;
+        }
+        for (level, state) in fluid.states.iter().enumerate() {
+            self.levels.insert(*state, (self.fluids.len(), level as u8));
+        }
+        self.fluids.push(fluid);
+        true
+    }
+
+    ///get the fluid of a state and its level, None if the state isn't a fluid
+    pub fn get(&self, state: BlockState) -> Option<(&Fluid, u8)> {
+        let (index, level) = self.levels.get(&state)?;
+        Some((&self.fluids[*index], *level))
+    }
+
+    ///return true if the state is the given fluid at any level
+    pub fn is_fluid(&self, state: BlockState, fluid: &Fluid) -> bool {
+        matches!(self.get(state), Some((other, _)) if other.states[0] == fluid.states[0])
+        //the states belong to a single fluid
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fluids.is_empty()
+    }
+}
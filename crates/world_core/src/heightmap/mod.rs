@@ -0,0 +1,262 @@
+use crate::block_state::{BlockState, AIR};
+use crate::Chunk;
+use math::positions::BlockPos;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+
+const COLUMN_COUNT: usize = (Chunk::SIZE * Chunk::SIZE) as usize;
+
+///the kinds of heightmap maintained by the ChunkManager, like the vanilla ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HeightmapKind {
+    ///the highest non-air block
+    WorldSurface,
+    ///the highest block that blocks the motion, see HeightmapRules
+    MotionBlocking,
+}
+
+impl HeightmapKind {
+    pub const ALL: [HeightmapKind; 2] =
+        [HeightmapKind::WorldSurface, HeightmapKind::MotionBlocking];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+///choose the blocks counted by each heightmap, by default every non-air block blocks the motion
+#[derive(Clone, Debug, Default)]
+pub struct HeightmapRules {
+    non_motion_blocking: HashSet<BlockState>, //the non-air states that don't block the motion, like flowers or torches
+}
+
+impl HeightmapRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_motion_blocking(&mut self, state: BlockState, motion_blocking: bool) {
+        if motion_blocking {
+            self.non_motion_blocking.remove(&state);
+        } else {
+            self.non_motion_blocking.insert(state);
+        }
+    }
+
+    ///return true if the state is counted by the given heightmap
+    pub fn counts(&self, kind: HeightmapKind, state: BlockState) -> bool {
+        match kind {
+            HeightmapKind::WorldSurface => state != AIR,
+            HeightmapKind::MotionBlocking => {
+                state != AIR && !self.non_motion_blocking.contains(&state)
+            }
+        }
+    }
+}
+
+type Heights = Option<Arc<[[u8; COLUMN_COUNT]; 2]>>; //the local y of the highest block + 1 for each column, 0 if the column doesn't have any block
+
+///the heightmaps of a single chunk, the ChunkManager stacks them to answer the queries for a whole column
+///they are computed by the first query after the chunk is inserted or replaced, so a chunk that is never queried is never scanned
+///nothing is allocated for a chunk without any counted block, a clone shares the heights until one of them is modified
+#[derive(Clone, Default)]
+pub struct ChunkHeightmaps {
+    heights: OnceLock<Heights>, //empty until computed
+}
+
+impl ChunkHeightmaps {
+    ///scan the chunk to build its heightmaps
+    pub fn compute(chunk: &Chunk, rules: &HeightmapRules) -> Self {
+        Self {
+            heights: OnceLock::from(compute_heights(chunk, rules)),
+        }
+    }
+
+    ///return true if the heightmaps were computed, see ChunkHeightmaps::get_or_compute
+    pub fn is_computed(&self) -> bool {
+        self.heights.get().is_some()
+    }
+
+    ///get the local y of the highest block of the column counted by the heightmap, None if there is no block or if the heightmaps weren't computed yet
+    pub fn get(&self, kind: HeightmapKind, x: i32, z: i32) -> Option<i32> {
+        get_height(self.heights.get()?, kind, x, z)
+    }
+
+    ///same as get, but scan the chunk first if the heightmaps weren't computed yet, the chunk must be the one of the heightmaps
+    pub fn get_or_compute(
+        &self,
+        chunk: &Chunk,
+        rules: &HeightmapRules,
+        kind: HeightmapKind,
+        x: i32,
+        z: i32,
+    ) -> Option<i32> {
+        let heights = self.heights.get_or_init(|| compute_heights(chunk, rules));
+        get_height(heights, kind, x, z)
+    }
+
+    ///the memory used by the heights in bytes, 0 if nothing is allocated
+    pub fn memory_size(&self) -> usize {
+        self.heights
+            .get()
+            .and_then(|heights| heights.as_ref())
+            .map_or(0, |heights| std::mem::size_of_val(&**heights))
+    }
+
+    ///update the heightmaps of the chunk after a block change, only the column of the block is scanned
+    ///nothing is done if they weren't computed yet, they will see the new block when they are
+    pub(crate) fn on_block_changed(chunk: &mut Chunk, rules: &HeightmapRules, pos: BlockPos) {
+        if !chunk.heightmaps().is_computed() {
+            return;
+        }
+        let state = chunk.get_block(pos);
+        for kind in HeightmapKind::ALL {
+            let current = chunk.heightmaps().get(kind, pos.x, pos.z);
+            let height = if rules.counts(kind, state) {
+                current.max(Some(pos.y))
+            } else if current == Some(pos.y) {
+                scan_column(chunk, rules, kind, pos.x, pos.z, pos.y - 1) //the highest block was removed
+            } else {
+                current
+            };
+            if height != current {
+                if let Some(heights) = chunk.heightmaps_mut().heights.get_mut() {
+                    set_height(heights, kind, pos.x, pos.z, height);
+                }
+            }
+        }
+    }
+}
+
+fn column_index(x: i32, z: i32) -> usize {
+    (x + z * Chunk::SIZE) as usize
+}
+
+fn compute_heights(chunk: &Chunk, rules: &HeightmapRules) -> Heights {
+    let mut heights = None;
+    if chunk.is_empty() {
+        return heights;
+    }
+    for kind in HeightmapKind::ALL {
+        for x in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                let height = scan_column(chunk, rules, kind, x, z, Chunk::SIZE - 1);
+                set_height(&mut heights, kind, x, z, height);
+            }
+        }
+    }
+    heights
+}
+
+fn get_height(heights: &Heights, kind: HeightmapKind, x: i32, z: i32) -> Option<i32> {
+    match heights.as_ref()?[kind.index()][column_index(x, z)] {
+        0 => None,
+        height => Some(height as i32 - 1),
+    }
+}
+
+fn set_height(heights: &mut Heights, kind: HeightmapKind, x: i32, z: i32, y: Option<i32>) {
+    let height = y.map_or(0, |y| y as u8 + 1);
+    let heights = match heights {
+        Some(heights) => Arc::make_mut(heights),
+        None if height == 0 => return,
+        None => Arc::make_mut(heights.insert(Arc::new([[0; COLUMN_COUNT]; 2]))),
+    };
+    heights[kind.index()][column_index(x, z)] = height;
+}
+
+///find the highest counted block of the column, starting at the given local y
+fn scan_column(
+    chunk: &Chunk,
+    rules: &HeightmapRules,
+    kind: HeightmapKind,
+    x: i32,
+    z: i32,
+    from_y: i32,
+) -> Option<i32> {
+    (0..=from_y)
+        .rev()
+        .find(|y| rules.counts(kind, chunk.get_block_at(x, *y, z)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ChunkManager;
+    use math::positions::ChunkPos;
+
+    #[test]
+    pub fn column_heights() {
+        let stone = BlockState::from_raw(1);
+        let flower = BlockState::from_raw(2);
+        let mut rules = HeightmapRules::new();
+        rules.set_motion_blocking(flower, false);
+
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.set_heightmap_rules(rules);
+        chunk_manager.insert_chunk(Chunk::new_uniform(ChunkPos::new(0, -2, 0), stone));
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(0, 0, 0)));
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(0, 3, 0)));
+        let height =
+            |chunk_manager: &ChunkManager, kind, x, z| chunk_manager.get_height(kind, x, z);
+        let bottom = ChunkPos::new(0, -2, 0);
+        assert!(!chunk_manager
+            .get_chunk(bottom)
+            .unwrap()
+            .heightmaps()
+            .is_computed());
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::WorldSurface, 5, 5),
+            Some(-17)
+        );
+        assert!(chunk_manager
+            .get_chunk(bottom)
+            .unwrap()
+            .heightmaps()
+            .is_computed());
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::WorldSurface, 16, 5),
+            None
+        );
+
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(5, 3, 5, stone);
+        chunk.set_block_at(5, 4, 5, flower);
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::WorldSurface, 5, 5),
+            Some(4)
+        );
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::MotionBlocking, 5, 5),
+            Some(3)
+        );
+
+        //removing the highest block scans the column down, through the chunks
+        let mut chunk = chunk_manager.get_chunk_mut(ChunkPos::ZERO).unwrap();
+        chunk.set_block_at(5, 4, 5, AIR);
+        chunk.set_block_at(5, 3, 5, AIR);
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::WorldSurface, 5, 5),
+            Some(-17)
+        );
+
+        //the highest chunk of the column may be in another section
+        let mut top = Chunk::new(ChunkPos::new(0, 100, 0));
+        top.set_block_at(5, 0, 5, flower);
+        chunk_manager.insert_chunk(top);
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::WorldSurface, 5, 5),
+            Some(1600)
+        );
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::MotionBlocking, 5, 5),
+            Some(-17)
+        );
+        chunk_manager.remove_chunk(ChunkPos::new(0, 100, 0));
+        chunk_manager.remove_chunk(ChunkPos::new(0, -2, 0));
+        assert_eq!(
+            height(&chunk_manager, HeightmapKind::WorldSurface, 5, 5),
+            None
+        );
+    }
+}
@@ -1,7 +1,19 @@
 #![doc = include_str!("../README.md")]
+pub mod anvil;
+pub mod biome;
+pub mod block_entity;
 pub mod block_state;
 pub mod chunk;
 pub mod chunk_manager;
+pub mod entity;
+pub mod fluid;
+pub mod heightmap;
+pub mod light;
+pub mod pathfinding;
+pub mod physics;
+pub mod region;
+pub mod tick;
+pub mod world_edit;
 
 pub use chunk::*;
 pub use chunk_manager::*;
@@ -0,0 +1,107 @@
+use crate::light::MAX_LIGHT;
+use crate::Chunk;
+use math::positions::BlockPos;
+use std::sync::Arc;
+
+const BYTE_COUNT: usize = Chunk::BLOCK_COUNT / 2;
+
+///the light levels of a chunk stored in nibbles, like the light sections sent by vanilla
+///the array is only allocated when a level differs from the uniform level, a chunk in the open sky or deep underground never allocates anything
+///a clone shares the array until one of them is modified
+#[derive(Clone, Default)]
+pub struct LightArray {
+    data: Option<Arc<[u8; BYTE_COUNT]>>,
+    uniform: u8, //the level of every block when data isn't allocated
+}
+
+impl LightArray {
+    ///create an array where every block has the given level, it doesn't allocate anything
+    pub fn new_uniform(level: u8) -> Self {
+        Self {
+            data: None,
+            uniform: level.min(MAX_LIGHT),
+        }
+    }
+
+    ///the index of a block in the array, the order is y, z, x like vanilla so the bytes can be sent without conversion
+    pub fn index(pos: BlockPos) -> usize {
+        (pos.x + pos.z * Chunk::SIZE + pos.y * Chunk::SIZE * Chunk::SIZE) as usize
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        match &self.data {
+            Some(data) => (data[index / 2] >> ((index % 2) * 4)) & 0xF,
+            None => self.uniform,
+        }
+    }
+
+    pub fn set(&mut self, index: usize, level: u8) {
+        let level = level.min(MAX_LIGHT);
+        let data = match &mut self.data {
+            Some(data) => Arc::make_mut(data),
+            None if level == self.uniform => return,
+            None => Arc::make_mut(
+                self.data
+                    .insert(Arc::new([self.uniform | (self.uniform << 4); BYTE_COUNT])),
+            ),
+        };
+        let shift = (index % 2) * 4;
+        data[index / 2] = (data[index / 2] & !(0xF << shift)) | (level << shift);
+    }
+
+    ///set every block to the given level and free the memory
+    pub fn fill(&mut self, level: u8) {
+        self.data = None;
+        self.uniform = level.min(MAX_LIGHT);
+    }
+
+    ///return the nibbles if the array is allocated, the low nibble of a byte is the first block
+    pub fn as_bytes(&self) -> Option<&[u8; BYTE_COUNT]> {
+        self.data.as_deref()
+    }
+
+    ///return the level of every block if the array isn't allocated
+    pub fn uniform_level(&self) -> Option<u8> {
+        match self.data {
+            Some(_) => None,
+            None => Some(self.uniform),
+        }
+    }
+
+    ///free the array if every block has the same level, return true if the memory was freed
+    pub fn compact(&mut self) -> bool {
+        let Some(data) = &self.data else {
+            return false;
+        };
+        let first = data[0];
+        if first & 0xF != first >> 4 || data.iter().any(|byte| *byte != first) {
+            return false;
+        }
+        self.fill(first & 0xF);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn nibbles() {
+        let mut array = LightArray::new_uniform(15);
+        array.set(LightArray::index(BlockPos::new(3, 4, 5)), 15);
+        assert_eq!(array.uniform_level(), Some(15));
+
+        array.set(LightArray::index(BlockPos::new(3, 4, 5)), 7);
+        array.set(LightArray::index(BlockPos::new(4, 4, 5)), 2);
+        assert_eq!(array.get(LightArray::index(BlockPos::new(3, 4, 5))), 7);
+        assert_eq!(array.get(LightArray::index(BlockPos::new(4, 4, 5))), 2);
+        assert_eq!(array.get(LightArray::index(BlockPos::new(5, 4, 5))), 15);
+        assert!(!array.compact());
+
+        array.set(LightArray::index(BlockPos::new(3, 4, 5)), 15);
+        array.set(LightArray::index(BlockPos::new(4, 4, 5)), 15);
+        assert!(array.compact());
+        assert_eq!(array.uniform_level(), Some(15));
+    }
+}
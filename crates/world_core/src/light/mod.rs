@@ -0,0 +1,134 @@
+mod light_array;
+
+use crate::block_state::BlockState;
+use math::positions::BlockPos;
+
+pub use light_array::LightArray;
+
+///the maximum light level, light levels are stored in nibbles
+pub const MAX_LIGHT: u8 = 15;
+
+///the two kinds of light, they propagate the same way except that sky light goes down without being reduced
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+}
+
+///the light emitted and absorbed by each block state, indexed by the raw id of the state
+///the states that were never set are opaque and don't emit light, except air which is fully transparent
+#[derive(Clone, Debug)]
+pub struct LightTable {
+    emission: Vec<u8>,
+    opacity: Vec<u8>,
+}
+
+impl LightTable {
+    pub fn new() -> Self {
+        Self {
+            emission: vec![0],
+            opacity: vec![0], //air
+        }
+    }
+
+    ///the light level emitted by the state, from 0 to 15
+    pub fn emission(&self, state: BlockState) -> u8 {
+        self.emission
+            .get(state.raw() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    ///the light levels absorbed by the state, 0 for air and 15 for a full opaque block
+    ///light still lose at least one level per block, whatever the opacity is
+    pub fn opacity(&self, state: BlockState) -> u8 {
+        self.opacity
+            .get(state.raw() as usize)
+            .copied()
+            .unwrap_or(MAX_LIGHT)
+    }
+
+    pub fn set_emission(&mut self, state: BlockState, emission: u8) {
+        self.resize(state);
+        self.emission[state.raw() as usize] = emission.min(MAX_LIGHT);
+    }
+
+    pub fn set_opacity(&mut self, state: BlockState, opacity: u8) {
+        self.resize(state);
+        self.opacity[state.raw() as usize] = opacity.min(MAX_LIGHT);
+    }
+
+    fn resize(&mut self, state: BlockState) {
+        let len = (state.raw() as usize + 1).max(self.emission.len());
+        self.emission.resize(len, 0);
+        self.opacity.resize(len, MAX_LIGHT);
+    }
+}
+
+impl Default for LightTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///the sky light and the block light of a chunk, the arrays are only allocated when the light isn't the same everywhere in the chunk
+///the light is computed by ChunkManager::update_light, a chunk alone is dark
+#[derive(Clone, Default)]
+pub struct ChunkLight {
+    sky: LightArray,
+    block: LightArray,
+}
+
+impl ChunkLight {
+    pub fn array(&self, channel: LightChannel) -> &LightArray {
+        match channel {
+            LightChannel::Sky => &self.sky,
+            LightChannel::Block => &self.block,
+        }
+    }
+
+    pub fn array_mut(&mut self, channel: LightChannel) -> &mut LightArray {
+        match channel {
+            LightChannel::Sky => &mut self.sky,
+            LightChannel::Block => &mut self.block,
+        }
+    }
+
+    ///get the light level at the given position in the chunk
+    pub fn get(&self, channel: LightChannel, pos: BlockPos) -> u8 {
+        self.array(channel).get(LightArray::index(pos))
+    }
+
+    ///set the light level at the given position in the chunk, it doesn't propagate anything
+    pub fn set(&mut self, channel: LightChannel, pos: BlockPos, level: u8) {
+        self.array_mut(channel).set(LightArray::index(pos), level)
+    }
+
+    ///the memory used by the allocated arrays in bytes
+    pub fn memory_size(&self) -> usize {
+        LightChannel::ALL
+            .iter()
+            .filter_map(|channel| self.array(*channel).as_bytes())
+            .map(|bytes| bytes.len())
+            .sum()
+    }
+
+    ///free the arrays that hold a single light level, return true if some memory was freed
+    pub fn compact(&mut self) -> bool {
+        let sky = self.sky.compact();
+        let block = self.block.compact();
+        sky || block
+    }
+}
+
+///the light level left after entering a block with the given opacity
+pub(crate) fn attenuate(channel: LightChannel, level: u8, going_down: bool, opacity: u8) -> u8 {
+    if channel == LightChannel::Sky && going_down && level == MAX_LIGHT && opacity == 0 {
+        return MAX_LIGHT; //the sky light isn't reduced in a column of transparent blocks
+    }
+    level.saturating_sub(opacity.max(1))
+}
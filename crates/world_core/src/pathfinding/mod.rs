@@ -0,0 +1,101 @@
+use math::positions::BlockPos;
+
+///how a path goes from a block to a neighbour
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MoveKind {
+    ///to a neighbour at the same height
+    Walk,
+    ///up to PathOptions::max_step blocks higher, without jumping
+    Step,
+    ///up to PathOptions::max_jump blocks higher
+    Jump,
+    ///down to PathOptions::max_drop blocks lower
+    Drop,
+}
+
+///a move between two neighbour positions of a path, the positions are the block at the feet of the entity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathMove {
+    pub from: BlockPos,
+    pub to: BlockPos,
+    pub kind: MoveKind,
+}
+
+impl PathMove {
+    ///the signed difference of height of the move
+    pub fn height(&self) -> i32 {
+        self.to.y - self.from.y
+    }
+}
+
+///the shape and the abilities of the entity following the path
+///the heights are in blocks, the paths only go through the block positions
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathOptions {
+    ///the width of the entity, the entity takes the width rounded up blocks on the x and z axes from the positions of the path
+    pub width: f32,
+    ///the height of the entity, the blocks above the feet rounded up must be free
+    pub height: f32,
+    pub max_step: i32,
+    pub max_jump: i32,
+    pub max_drop: i32,
+    ///the maximum number of positions explored before giving up with a partial path
+    pub max_visited: usize,
+}
+
+impl Default for PathOptions {
+    ///a zombie-like entity
+    fn default() -> Self {
+        Self {
+            width: 0.6,
+            height: 1.8,
+            max_step: 0,
+            max_jump: 1,
+            max_drop: 3,
+            max_visited: 10_000,
+        }
+    }
+}
+
+impl PathOptions {
+    ///the number of blocks taken on the x and z axes
+    pub fn block_width(&self) -> i32 {
+        (self.width.ceil() as i32).max(1)
+    }
+
+    ///the number of free blocks needed above the feet, included
+    pub fn block_height(&self) -> i32 {
+        (self.height.ceil() as i32).max(1)
+    }
+}
+
+///the cost used when none is given, a cost function should never return less than 1 so the heuristic doesn't overestimate
+///return None to forbid a move, for example to avoid lava
+pub fn default_cost(path_move: &PathMove) -> Option<f32> {
+    Some(match path_move.kind {
+        MoveKind::Walk => 1.0,
+        MoveKind::Step => 1.0 + 0.5 * path_move.height() as f32,
+        MoveKind::Jump => 2.0 + path_move.height() as f32,
+        MoveKind::Drop => 1.0 + 0.5 * (-path_move.height()) as f32,
+    })
+}
+
+///the result of a search, if the goal can't be reached it leads to the explored position the closest to the goal
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    ///the positions of the feet from the start to the end of the path, both included
+    pub positions: Vec<BlockPos>,
+    ///the kind of move leading to each position but the start
+    pub moves: Vec<MoveKind>,
+    ///true if the path reaches the goal
+    pub complete: bool,
+    pub cost: f32,
+    ///the number of positions explored by the search
+    pub visited: usize,
+}
+
+impl Path {
+    pub fn end(&self) -> BlockPos {
+        *self.positions.last().unwrap()
+    }
+}
@@ -0,0 +1,116 @@
+use crate::block_state::{BlockState, AIR};
+use math::aabb::FloatAABB;
+use math::{BVec3, Vec3, Vec3Swizzles};
+use std::collections::HashMap;
+
+///the distance under which an AABB is considered touching a collider, it absorbs the rounding errors of the f32
+const EPSILON: f32 = 1e-5;
+
+const FULL_BLOCK: [FloatAABB; 1] = [FloatAABB::UNIT];
+
+///the collision shape of each block state, as boxes relative to the block, by default every non-air block is a full cube
+///a box may go out of the block by up to one block downward, like a fence
+#[derive(Clone, Debug, Default)]
+pub struct CollisionShapes {
+    shapes: HashMap<BlockState, Vec<FloatAABB>>, //the states that aren't full cubes, an empty shape doesn't collide at all
+}
+
+impl CollisionShapes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///set the boxes of a state, an empty vec makes the state go-through, like a flower
+    pub fn set_shape(&mut self, state: BlockState, shape: Vec<FloatAABB>) {
+        if state == AIR {
+            return; //air never collides
+        }
+        if shape == FULL_BLOCK {
+            self.shapes.remove(&state);
+        } else {
+            self.shapes.insert(state, shape);
+        }
+    }
+
+    pub fn get_shape(&self, state: BlockState) -> &[FloatAABB] {
+        if state == AIR {
+            return &[];
+        }
+        self.shapes.get(&state).map_or(&FULL_BLOCK, |shape| shape)
+    }
+}
+
+///the result of the movement of an AABB against the terrain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collision {
+    ///the motion that can be applied without entering a collider
+    pub motion: Vec3,
+    ///true if the AABB was moving down and stopped on a collider
+    pub grounded: bool,
+    ///the axes on which the motion was changed, by a collider or by a step
+    pub collided: BVec3,
+}
+
+///resolve the motion of the AABB against the colliders axis by axis, in the y, x, z order
+///when the AABB is blocked horizontally while landing, it tries to climb up to step_height and keeps the longest horizontal motion
+///the colliders already intersecting the AABB are ignored, so an AABB stuck in a block can still get out
+pub fn resolve_motion(
+    colliders: &[FloatAABB],
+    aabb: FloatAABB,
+    velocity: Vec3,
+    step_height: f32,
+) -> Collision {
+    let mut motion = clip(colliders, aabb, velocity);
+
+    let blocked = motion.x != velocity.x || motion.z != velocity.z;
+    let landed = velocity.y < 0.0 && motion.y != velocity.y;
+    if step_height > 0.0 && blocked && landed {
+        let up = clip_axis(colliders, aabb, 1, step_height);
+        let raised = aabb.offset(Vec3::new(0.0, up, 0.0));
+        let horizontal = clip(colliders, raised, Vec3::new(velocity.x, 0.0, velocity.z));
+        let down = clip_axis(colliders, raised.offset(horizontal), 1, velocity.y - up);
+        let stepped = Vec3::new(horizontal.x, up + down, horizontal.z);
+        if stepped.xz().length_squared() > motion.xz().length_squared() {
+            motion = stepped;
+        }
+    }
+
+    Collision {
+        motion,
+        grounded: velocity.y < 0.0 && motion.y > velocity.y,
+        collided: motion.cmpne(velocity),
+    }
+}
+
+///clip the motion on each axis in the y, x, z order, the AABB is moved after each axis
+fn clip(colliders: &[FloatAABB], mut aabb: FloatAABB, velocity: Vec3) -> Vec3 {
+    let mut motion = Vec3::ZERO;
+    for axis in [1, 0, 2] {
+        motion[axis] = clip_axis(colliders, aabb, axis, velocity[axis]);
+        let mut offset = Vec3::ZERO;
+        offset[axis] = motion[axis];
+        aabb = aabb.offset(offset);
+    }
+    motion
+}
+
+///the part of the motion along the axis the AABB can do before touching a collider
+fn clip_axis(colliders: &[FloatAABB], aabb: FloatAABB, axis: usize, mut motion: f32) -> f32 {
+    let (min, max) = (aabb.min(), aabb.max());
+    for collider in colliders {
+        let (collider_min, collider_max) = (collider.min(), collider.max());
+        //the collider must overlap the AABB on the two other axes to be on its way
+        let on_the_way = (0..3)
+            .filter(|other| *other != axis)
+            .all(|other| min[other] < collider_max[other] && max[other] > collider_min[other]);
+        if !on_the_way {
+            continue;
+        }
+        if motion > 0.0 && max[axis] <= collider_min[axis] + EPSILON {
+            motion = motion.min(collider_min[axis] - max[axis]).max(0.0);
+        } else if motion < 0.0 && min[axis] >= collider_max[axis] - EPSILON {
+            motion = motion.max(collider_max[axis] - min[axis]).min(0.0);
+        }
+    }
+    motion
+}
@@ -0,0 +1,373 @@
+mod region_file;
+
+use crate::chunk::{ChunkCompression, ChunkDecodeError, ChunkEncodeError};
+use crate::{Chunk, ChunkManager};
+use math::positions::ChunkPos;
+use math::IVec3;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use utils::spare_set::Id;
+
+///the number of chunks in a side of a region, a region covers the same chunks as a leaf node of the ChunkManager octree
+pub const REGION_SIDE: i32 = 8;
+pub const REGION_CHUNK_COUNT: usize = (REGION_SIDE * REGION_SIDE * REGION_SIDE) as usize;
+
+#[derive(Debug, Error)]
+pub enum RegionError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("the region file {0} is corrupted")]
+    InvalidRegion(PathBuf),
+    #[error("the region file {path} has the unsupported version {version}")]
+    UnsupportedVersion { path: PathBuf, version: u8 },
+    #[error("the chunk {pos} can't be decoded: {source}")]
+    InvalidChunk {
+        pos: ChunkPos,
+        source: ChunkDecodeError,
+    },
+    ///the other chunks were saved, only the listed ones are missing from the region files
+    #[error("{} chunks can't be encoded: {0:?}", .0.len())]
+    UnencodableChunks(Vec<(ChunkPos, ChunkEncodeError)>),
+    #[error("the region file stores the chunk {found} where {expected} was expected")]
+    UnexpectedChunkPosition { found: ChunkPos, expected: ChunkPos },
+}
+
+///store the chunks of a world in a directory, one file per region of 8^3 chunks
+///nothing is cached, the chunks are read on demand and a region file is rewritten each time one of its chunks is saved
+pub struct RegionStorage {
+    directory: PathBuf,
+    compression: ChunkCompression,
+}
+
+impl RegionStorage {
+    ///use the given directory to store the regions, it's created if it doesn't exist
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, RegionError> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            compression: ChunkCompression::Zlib,
+        })
+    }
+
+    ///change the compression used to save the chunks, the chunks already saved keep their compression
+    pub fn with_compression(mut self, compression: ChunkCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    ///get the position of the region that contains the given chunk
+    pub fn region_pos(chunk_pos: ChunkPos) -> IVec3 {
+        chunk_pos.div_euclid(IVec3::splat(REGION_SIDE)) //euclid division is important here, else the sign of the number will be wrong
+    }
+
+    fn region_path(&self, region_pos: IVec3) -> PathBuf {
+        let file_name = format!(
+            "r.{}.{}.{}.region",
+            region_pos.x, region_pos.y, region_pos.z
+        );
+        self.directory.join(file_name)
+    }
+
+    ///read a chunk from the disk, return None if the chunk was never saved
+    ///the scheduled ticks of the chunk are due at their saved delay after the current tick, see ChunkManager::current_tick
+    pub fn load_chunk(
+        &self,
+        pos: ChunkPos,
+        current_tick: u64,
+    ) -> Result<Option<Chunk>, RegionError> {
+        let path = self.region_path(Self::region_pos(pos));
+        let Some(data) = region_file::read_chunk(&path, pos)? else {
+            return Ok(None);
+        };
+        let chunk = Chunk::deserialize(&data, current_tick)
+            .map_err(|source| RegionError::InvalidChunk { pos, source })?;
+        if chunk.position() != pos {
+            return Err(RegionError::UnexpectedChunkPosition {
+                found: chunk.position(),
+                expected: pos,
+            });
+        }
+        Ok(Some(chunk))
+    }
+
+    ///get a chunk from the ChunkManager, or load it from the disk and insert it if it isn't loaded yet
+    ///return None if the chunk is neither loaded nor saved, a chunk loaded from the disk isn't marked as modified
+    pub fn get_or_load_chunk<'a>(
+        &self,
+        chunk_manager: &'a mut ChunkManager,
+        pos: ChunkPos,
+    ) -> Result<Option<&'a Chunk>, RegionError> {
+        if chunk_manager.get_chunk_untracked(pos).is_none() {
+            match self.load_chunk(pos, chunk_manager.current_tick())? {
+                Some(chunk) => chunk_manager.insert_chunk_untracked(chunk),
+                None => return Ok(None),
+            }
+        }
+        Ok(chunk_manager.get_chunk(pos))
+    }
+
+    ///save the given chunks, the ids that don't belong to a loaded chunk anymore are ignored
+    ///each region file touched is rewritten once, return the number of saved chunks
+    ///a chunk that can't be encoded is skipped, the others are still saved and the skipped ones are listed by RegionError::UnencodableChunks
+    pub fn save_chunks(
+        &self,
+        chunk_manager: &ChunkManager,
+        ids: &[Id],
+    ) -> Result<usize, RegionError> {
+        let mut saved = 0;
+        let mut unencodable = Vec::new();
+        for (region_pos, ids) in Self::group_by_region(chunk_manager, ids) {
+            saved += self.save_region(chunk_manager, region_pos, &ids, &mut unencodable)?;
+        }
+        Self::check_unencodable(saved, unencodable)
+    }
+
+    ///save the chunks modified since the last call to ChunkManager::on_process_modified_chunks, the list is consumed
+    ///if the list is also needed for something else, copy it from on_process_modified_chunks and give it to save_chunks
+    ///on error, the chunks that weren't saved are marked as modified again so the next call retries them
+    ///like with save_chunks, a chunk that can't be encoded doesn't prevent the others from being saved
+    pub fn save_modified_chunks(
+        &self,
+        chunk_manager: &mut ChunkManager,
+    ) -> Result<usize, RegionError> {
+        let mut modified = Vec::new();
+        chunk_manager.on_process_modified_chunks(|ids, _| modified.extend_from_slice(ids));
+
+        let mut regions = Self::group_by_region(chunk_manager, &modified).into_iter();
+        let mut saved = 0;
+        let mut unencodable = Vec::new();
+        while let Some((region_pos, ids)) = regions.next() {
+            match self.save_region(chunk_manager, region_pos, &ids, &mut unencodable) {
+                Ok(count) => saved += count,
+                Err(error) => {
+                    let skipped = unencodable.iter().map(|(id, _, _)| *id);
+                    for id in skipped.chain(ids).chain(regions.flat_map(|(_, ids)| ids)) {
+                        chunk_manager.mark_modified(id);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        for (id, _, _) in &unencodable {
+            chunk_manager.mark_modified(*id);
+        }
+        Self::check_unencodable(saved, unencodable)
+    }
+
+    ///sort the ids of the loaded chunks by region, the other ids are dropped
+    fn group_by_region(chunk_manager: &ChunkManager, ids: &[Id]) -> HashMap<IVec3, Vec<Id>> {
+        let mut regions: HashMap<IVec3, Vec<Id>> = HashMap::new();
+        for id in ids {
+            if let Some(pos) = chunk_manager.get_chunk_position(*id) {
+                regions.entry(Self::region_pos(pos)).or_default().push(*id);
+            }
+        }
+        regions
+    }
+
+    ///the error of the chunks skipped by save_region, if there are some
+    fn check_unencodable(
+        saved: usize,
+        unencodable: Vec<(Id, ChunkPos, ChunkEncodeError)>,
+    ) -> Result<usize, RegionError> {
+        if unencodable.is_empty() {
+            return Ok(saved);
+        }
+        Err(RegionError::UnencodableChunks(
+            unencodable
+                .into_iter()
+                .map(|(_, pos, error)| (pos, error))
+                .collect(),
+        ))
+    }
+
+    ///rewrite a region file with the given chunks, the other chunks of the file are kept
+    ///the chunks that can't be encoded are skipped and pushed in unencodable, the previous save of these chunks is kept
+    fn save_region(
+        &self,
+        chunk_manager: &ChunkManager,
+        region_pos: IVec3,
+        ids: &[Id],
+        unencodable: &mut Vec<(Id, ChunkPos, ChunkEncodeError)>,
+    ) -> Result<usize, RegionError> {
+        let path = self.region_path(region_pos);
+        let mut region = region_file::read_all_chunks(&path)?;
+        let mut saved = 0;
+        for id in ids {
+            let Some(chunk) = chunk_manager
+                .get_chunk_position(*id)
+                .and_then(|pos| chunk_manager.get_chunk_untracked(pos))
+            else {
+                continue;
+            };
+            match chunk.serialize(self.compression, chunk_manager.current_tick()) {
+                Ok(data) => {
+                    region[region_file::chunk_index(chunk.position())] = Some(data);
+                    saved += 1;
+                }
+                Err(error) => unencodable.push((*id, chunk.position(), error)),
+            }
+        }
+        region_file::write_all_chunks(&path, &region)?;
+        Ok(saved)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_entity::BlockEntity;
+    use crate::block_state::BlockState;
+    use crate::chunk::MAX_BLOCK_ENTITIES_SIZE;
+    use ident::ident;
+    use math::positions::BlockPos;
+    use nbt::compound;
+
+    ///a fresh directory in the temporary directory of the system, removed when dropped
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("world_core_{name}_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    pub fn save_and_load() {
+        let directory = TestDirectory::new("save_and_load");
+        let storage = RegionStorage::new(&directory.0).unwrap();
+        let mut chunk_manager = ChunkManager::new();
+
+        for x in -10..10 {
+            let mut chunk = Chunk::new(ChunkPos::new(x, -1, 3));
+            chunk.set_block_at(1, 2, 3, BlockState::from_raw((x + 20) as u16));
+            chunk_manager.insert_chunk(chunk);
+        }
+        assert_eq!(
+            storage.save_modified_chunks(&mut chunk_manager).unwrap(),
+            20
+        );
+        assert_eq!(storage.save_modified_chunks(&mut chunk_manager).unwrap(), 0); //nothing changed
+        assert_eq!(std::fs::read_dir(&directory.0).unwrap().count(), 4); //the regions x = -2, -1, 0 and 1
+
+        //only the modified chunk is saved, the others of the region are kept
+        let pos = ChunkPos::new(2, -1, 3);
+        let mut chunk = chunk_manager.get_chunk_mut(pos).unwrap();
+        chunk.set_block_at(0, 0, 0, BlockState::from_raw(7));
+        assert_eq!(storage.save_modified_chunks(&mut chunk_manager).unwrap(), 1);
+
+        let mut chunk_manager = ChunkManager::new();
+        assert!(storage
+            .get_or_load_chunk(&mut chunk_manager, ChunkPos::new(10, -1, 3))
+            .unwrap()
+            .is_none());
+        for x in -10..10 {
+            let chunk = storage
+                .get_or_load_chunk(&mut chunk_manager, ChunkPos::new(x, -1, 3))
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                chunk.get_block_at(1, 2, 3),
+                BlockState::from_raw((x + 20) as u16)
+            );
+        }
+        let chunk = chunk_manager.get_chunk(pos).unwrap();
+        assert_eq!(chunk.get_block_at(0, 0, 0), BlockState::from_raw(7));
+
+        //the loaded chunks are the same as the saved ones
+        assert_eq!(storage.save_modified_chunks(&mut chunk_manager).unwrap(), 0);
+    }
+
+    #[test]
+    pub fn retry_failed_save() {
+        let directory = TestDirectory::new("failed_save");
+        let storage = RegionStorage::new(&directory.0).unwrap();
+        let mut chunk_manager = ChunkManager::new();
+        for x in 0..3 {
+            chunk_manager.insert_chunk(Chunk::new(ChunkPos::new(x * REGION_SIDE, 0, 0)));
+        }
+
+        std::fs::remove_dir(&directory.0).unwrap(); //the region files can't be written anymore
+        assert!(matches!(
+            storage.save_modified_chunks(&mut chunk_manager),
+            Err(RegionError::Io(_))
+        ));
+
+        std::fs::create_dir(&directory.0).unwrap();
+        assert_eq!(storage.save_modified_chunks(&mut chunk_manager).unwrap(), 3);
+        assert_eq!(std::fs::read_dir(&directory.0).unwrap().count(), 3);
+    }
+
+    #[test]
+    pub fn skip_unencodable_chunk() {
+        let directory = TestDirectory::new("unencodable_chunk");
+        let storage = RegionStorage::new(&directory.0).unwrap();
+        let mut chunk_manager = ChunkManager::new();
+        let bad = ChunkPos::ZERO;
+        let good = ChunkPos::new(1, 0, 0); //in the same region
+        chunk_manager.insert_chunk(Chunk::new(bad));
+        chunk_manager.insert_chunk(Chunk::new(good));
+        let huge = BlockEntity::new(
+            ident!("minecraft:chest").into(),
+            compound! { "items" => vec![0i8; MAX_BLOCK_ENTITIES_SIZE] },
+        );
+        chunk_manager.set_block_entity(BlockPos::ZERO, huge);
+
+        match storage.save_modified_chunks(&mut chunk_manager) {
+            Err(RegionError::UnencodableChunks(chunks)) => {
+                assert_eq!(chunks.len(), 1);
+                assert_eq!(chunks[0].0, bad);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(storage.load_chunk(good, 0).unwrap().is_some());
+        assert!(storage.load_chunk(bad, 0).unwrap().is_none());
+
+        //only the chunk that couldn't be encoded is retried
+        let mut retried = Vec::new();
+        chunk_manager.on_process_modified_chunks(|ids, _| retried.extend_from_slice(ids));
+        assert_eq!(retried.len(), 1);
+        assert_eq!(chunk_manager.get_chunk_position(retried[0]), Some(bad));
+    }
+
+    #[test]
+    pub fn reject_corrupted_region() {
+        let directory = TestDirectory::new("corrupted_region");
+        let storage = RegionStorage::new(&directory.0).unwrap();
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new_uniform(ChunkPos::ZERO, BlockState::from_raw(1)));
+        storage.save_modified_chunks(&mut chunk_manager).unwrap();
+
+        let path = storage.region_path(IVec3::ZERO);
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(matches!(
+            storage.load_chunk(ChunkPos::ZERO, 0),
+            Err(RegionError::InvalidRegion(_))
+        ));
+
+        std::fs::write(&path, &data[..100]).unwrap();
+        assert!(matches!(
+            storage.load_chunk(ChunkPos::ZERO, 0),
+            Err(RegionError::InvalidRegion(_))
+        ));
+
+        //no temporary file is left behind
+        assert_eq!(std::fs::read_dir(&directory.0).unwrap().count(), 1);
+    }
+}
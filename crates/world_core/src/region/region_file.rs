@@ -0,0 +1,168 @@
+use crate::region::{RegionError, REGION_CHUNK_COUNT, REGION_SIDE};
+use math::positions::ChunkPos;
+use math::IVec3;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+///layout of a region file, all the numbers are little endian:
+///- magic "ARCR" then the version (u8)
+///- the location table: an (offset, length) pair of u32 for each chunk of the region, (0, 0) for a chunk that was never saved
+///- the chunks, encoded with Chunk::serialize, in any order
+const MAGIC: &[u8; 4] = b"ARCR";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = MAGIC.len() + 1 + REGION_CHUNK_COUNT * 8;
+
+///the location of a chunk in a region file
+#[derive(Clone, Copy, Default)]
+struct Location {
+    offset: u32,
+    length: u32,
+}
+
+impl Location {
+    fn is_present(&self) -> bool {
+        self.length != 0
+    }
+}
+
+///the index of a chunk in the location table, the chunk position can be anywhere in the world
+pub(super) fn chunk_index(pos: ChunkPos) -> usize {
+    let local_pos = pos.rem_euclid(IVec3::splat(REGION_SIDE));
+    (local_pos.x + local_pos.y * REGION_SIDE + local_pos.z * REGION_SIDE * REGION_SIDE) as usize
+}
+
+fn parse_header(path: &Path, header: &[u8]) -> Result<Vec<Location>, RegionError> {
+    let invalid = || RegionError::InvalidRegion(path.to_path_buf());
+    if header.len() < HEADER_SIZE || &header[..MAGIC.len()] != MAGIC {
+        return Err(invalid());
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(RegionError::UnsupportedVersion {
+            path: path.to_path_buf(),
+            version: header[MAGIC.len()],
+        });
+    }
+
+    let table = &header[MAGIC.len() + 1..HEADER_SIZE];
+    let locations = table
+        .chunks_exact(8)
+        .map(|entry| Location {
+            offset: u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+            length: u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+        })
+        .collect::<Vec<_>>();
+
+    //a chunk can't overlap the header, the end of the file is checked when the chunk is read
+    let overlap_header =
+        |location: &Location| location.is_present() && (location.offset as usize) < HEADER_SIZE;
+    if locations.iter().any(overlap_header) {
+        return Err(invalid());
+    }
+    Ok(locations)
+}
+
+///read the encoded chunk at the given position without reading the rest of the region
+///return None if the region file doesn't exist or if the chunk was never saved
+pub(super) fn read_chunk(path: &Path, pos: ChunkPos) -> Result<Option<Vec<u8>>, RegionError> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+
+    let mut header = vec![0; HEADER_SIZE];
+    file.read_exact(&mut header)
+        .map_err(|error| match error.kind() {
+            ErrorKind::UnexpectedEof => RegionError::InvalidRegion(path.to_path_buf()),
+            _ => error.into(),
+        })?;
+    let location = parse_header(path, &header)?[chunk_index(pos)];
+    if !location.is_present() {
+        return Ok(None);
+    }
+
+    let mut data = vec![0; location.length as usize];
+    file.seek(SeekFrom::Start(location.offset as u64))?;
+    file.read_exact(&mut data)
+        .map_err(|error| match error.kind() {
+            ErrorKind::UnexpectedEof => RegionError::InvalidRegion(path.to_path_buf()),
+            _ => error.into(),
+        })?;
+    Ok(Some(data))
+}
+
+///read all the encoded chunks of a region, the missing ones are None
+pub(super) fn read_all_chunks(path: &Path) -> Result<Vec<Option<Vec<u8>>>, RegionError> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            return Ok(vec![None; REGION_CHUNK_COUNT])
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let locations = parse_header(path, &data)?;
+    locations
+        .iter()
+        .map(|location| {
+            if !location.is_present() {
+                return Ok(None);
+            }
+            let start = location.offset as usize;
+            let chunk = data
+                .get(start..start + location.length as usize)
+                .ok_or_else(|| RegionError::InvalidRegion(path.to_path_buf()))?;
+            Ok(Some(chunk.to_vec()))
+        })
+        .collect()
+}
+
+///replace the region file with the given chunks
+///the region is written in a temporary file which is renamed over the old one, so a crash leaves either the old or the new region, never a mix of both
+pub(super) fn write_all_chunks(path: &Path, chunks: &[Option<Vec<u8>>]) -> Result<(), RegionError> {
+    debug_assert_eq!(chunks.len(), REGION_CHUNK_COUNT);
+    let mut data = Vec::with_capacity(HEADER_SIZE);
+    data.extend_from_slice(MAGIC);
+    data.push(VERSION);
+
+    let mut offset = HEADER_SIZE;
+    for chunk in chunks {
+        let location = match chunk {
+            Some(chunk) => {
+                let location = Location {
+                    offset: offset as u32,
+                    length: chunk.len() as u32,
+                };
+                offset += chunk.len();
+                location
+            }
+            None => Location::default(),
+        };
+        data.extend_from_slice(&location.offset.to_le_bytes());
+        data.extend_from_slice(&location.length.to_le_bytes());
+    }
+    for chunk in chunks.iter().flatten() {
+        data.extend_from_slice(chunk);
+    }
+
+    let temporary_path = temporary_path(path);
+    let mut file = File::create(&temporary_path)?;
+    file.write_all(&data)?;
+    file.sync_all()?; //the data must be on the disk before the rename, else a crash could leave an empty region
+    drop(file);
+    std::fs::rename(&temporary_path, path)?;
+
+    //make the rename itself durable, windows doesn't allow to open a directory
+    #[cfg(unix)]
+    if let Some(directory) = path.parent() {
+        File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
@@ -0,0 +1,132 @@
+use crate::Chunk;
+use math::positions::BlockPos;
+use std::collections::{BTreeSet, HashMap};
+
+///the random ticks per chunk and per tick used by vanilla
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 3;
+
+///the ticks scheduled in a chunk, a block has at most one scheduled tick
+///they belong to the chunk, so they are saved, loaded and unloaded with it, see ChunkManager::schedule_tick
+#[derive(Clone, Default)]
+pub struct ChunkTicks {
+    queue: BTreeSet<(u64, u64, u16)>, //(due tick, scheduling order, block index), the ticks due the same tick run in the order they were scheduled
+    by_block: HashMap<u16, (u64, u64)>, //the due tick and the scheduling order of each block with a scheduled tick
+}
+
+impl ChunkTicks {
+    pub fn len(&self) -> usize {
+        self.by_block.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_block.is_empty()
+    }
+
+    ///an estimation of the memory used by the ticks in bytes
+    pub fn memory_size(&self) -> usize {
+        //a tick is stored in both collections
+        self.len()
+            * (std::mem::size_of::<(u64, u64, u16)>() + std::mem::size_of::<(u16, (u64, u64))>())
+    }
+
+    ///get the due tick of the block at the given position in the chunk
+    pub fn get(&self, pos: BlockPos) -> Option<u64> {
+        self.by_block
+            .get(&block_index(pos))
+            .map(|(due_tick, _)| *due_tick)
+    }
+
+    ///the earliest due tick of the chunk
+    pub fn next_due_tick(&self) -> Option<u64> {
+        self.queue.first().map(|(due_tick, _, _)| *due_tick)
+    }
+
+    ///iterate over the position in the chunk and the due tick of every scheduled tick, in the order they will run
+    pub fn iter(&self) -> impl Iterator<Item = (BlockPos, u64)> + '_ {
+        self.queue
+            .iter()
+            .map(|(due_tick, _, index)| (block_pos_of(*index), *due_tick))
+    }
+
+    ///schedule a tick for the block, the earliest tick is kept if the block already has one
+    ///return false if the block already has a tick due earlier or at the same tick
+    pub(crate) fn schedule(&mut self, pos: BlockPos, due_tick: u64, order: u64) -> bool {
+        let index = block_index(pos);
+        if let Some((scheduled, scheduled_order)) = self.by_block.get(&index).copied() {
+            if scheduled <= due_tick {
+                return false;
+            }
+            self.queue.remove(&(scheduled, scheduled_order, index));
+        }
+        self.queue.insert((due_tick, order, index));
+        self.by_block.insert(index, (due_tick, order));
+        true
+    }
+
+    ///return false if the block doesn't have a scheduled tick
+    pub(crate) fn cancel(&mut self, pos: BlockPos) -> bool {
+        let index = block_index(pos);
+        match self.by_block.remove(&index) {
+            Some((due_tick, order)) => self.queue.remove(&(due_tick, order, index)),
+            None => false,
+        }
+    }
+
+    ///remove the ticks due at or before the given tick, return their due tick, their scheduling order and their position in the chunk
+    pub(crate) fn take_due(&mut self, tick: u64) -> Vec<(u64, u64, BlockPos)> {
+        let mut due = Vec::new();
+        while let Some((due_tick, order, index)) = self.queue.first().copied() {
+            if due_tick > tick {
+                break;
+            }
+            self.queue.pop_first();
+            self.by_block.remove(&index);
+            due.push((due_tick, order, block_pos_of(index)));
+        }
+        due
+    }
+}
+
+///the index of a block in the chunk, in the x, y, z order of Chunk::blocks
+fn block_index(pos: BlockPos) -> u16 {
+    debug_assert!(pos.cmpge(BlockPos::ZERO).all() && pos.cmplt(BlockPos::splat(Chunk::SIZE)).all());
+    (pos.x + pos.y * Chunk::SIZE + pos.z * Chunk::SIZE * Chunk::SIZE) as u16
+}
+
+fn block_pos_of(index: u16) -> BlockPos {
+    crate::chunk::block_pos_of(index as usize)
+}
+
+///a fast hash to derive deterministic random numbers from a seed, the same input always gives the same number
+pub(crate) fn splitmix64(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9e3779b97f4a7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn deduplicate() {
+        let mut ticks = ChunkTicks::default();
+        let pos = BlockPos::new(1, 2, 3);
+        assert!(ticks.schedule(pos, 10, 0));
+        assert!(!ticks.schedule(pos, 12, 1));
+        assert!(ticks.schedule(pos, 5, 2));
+        assert!(ticks.schedule(BlockPos::new(4, 5, 6), 5, 3));
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks.get(pos), Some(5));
+        assert_eq!(ticks.next_due_tick(), Some(5));
+
+        assert!(ticks.take_due(4).is_empty());
+        assert_eq!(
+            ticks.take_due(5),
+            vec![(5, 2, pos), (5, 3, BlockPos::new(4, 5, 6))]
+        );
+        assert!(ticks.is_empty());
+        assert!(!ticks.cancel(pos));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::block_state::{BlockState, AIR};
+use math::positions::BlockPos;
+use math::IVec3;
+
+///the axis flipped by Clipboard::mirrored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MirrorAxis {
+    X,
+    Y,
+    Z,
+}
+
+///a box of blocks copied from the world, the position of a block is relative to the smallest corner of the box
+///the transformations only move the blocks, the orientation of the states (stairs, logs...) can be fixed with map_states and a BlockStateRegistry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clipboard {
+    size: IVec3,
+    blocks: Vec<BlockState>, //the index of a block is x + y * size.x + z * size.x * size.y
+}
+
+impl Clipboard {
+    ///create a clipboard full of air, panic if the volume doesn't fit in an usize
+    pub fn new(size: IVec3) -> Self {
+        let size = size.max(IVec3::ZERO);
+        let volume = Self::volume(size).expect("the clipboard is too big");
+        Self {
+            size,
+            blocks: vec![AIR; volume],
+        }
+    }
+
+    ///the number of blocks of a clipboard of the given size, None if it overflows
+    pub fn volume(size: IVec3) -> Option<usize> {
+        let size = size.max(IVec3::ZERO).as_uvec3();
+        (size.x as usize)
+            .checked_mul(size.y as usize)?
+            .checked_mul(size.z as usize)
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    fn index(&self, pos: BlockPos) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.size).any() {
+            return None;
+        }
+        let (pos, size) = (pos.as_uvec3(), self.size.as_uvec3());
+        Some(pos.x as usize + (pos.y as usize + pos.z as usize * size.y as usize) * size.x as usize)
+    }
+
+    ///get the block at the given position in the clipboard, air outside of the clipboard
+    pub fn get_block(&self, pos: BlockPos) -> BlockState {
+        self.index(pos).map_or(AIR, |index| self.blocks[index])
+    }
+
+    ///set the block at the given position in the clipboard, nothing happens outside of the clipboard
+    pub fn set_block(&mut self, pos: BlockPos, state: BlockState) {
+        if let Some(index) = self.index(pos) {
+            self.blocks[index] = state;
+        }
+    }
+
+    ///replace every state of the clipboard, useful to rotate the orientation of the blocks with the properties of the registry
+    pub fn map_states(&mut self, mut func: impl FnMut(BlockState) -> BlockState) {
+        for state in self.blocks.iter_mut() {
+            *state = func(*state);
+        }
+    }
+
+    ///move every block, the new position is given from the old one, in the new size
+    fn transformed(&self, size: IVec3, new_pos: impl Fn(BlockPos) -> BlockPos) -> Self {
+        let mut clipboard = Self::new(size);
+        for z in 0..self.size.z {
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    let pos = BlockPos::new(x, y, z);
+                    clipboard.set_block(new_pos(pos), self.get_block(pos));
+                }
+            }
+        }
+        clipboard
+    }
+
+    ///rotate the clipboard around the y axis by quarter turns, clockwise when seen from above
+    pub fn rotated(&self, quarter_turns: i32) -> Self {
+        let size = self.size;
+        match quarter_turns.rem_euclid(4) {
+            1 => self.transformed(IVec3::new(size.z, size.y, size.x), |pos| {
+                BlockPos::new(size.z - 1 - pos.z, pos.y, pos.x)
+            }),
+            2 => self.transformed(size, |pos| {
+                BlockPos::new(size.x - 1 - pos.x, pos.y, size.z - 1 - pos.z)
+            }),
+            3 => self.transformed(IVec3::new(size.z, size.y, size.x), |pos| {
+                BlockPos::new(pos.z, pos.y, size.x - 1 - pos.x)
+            }),
+            _ => self.clone(),
+        }
+    }
+
+    ///flip the clipboard along the given axis
+    pub fn mirrored(&self, axis: MirrorAxis) -> Self {
+        let size = self.size;
+        self.transformed(size, |mut pos| {
+            match axis {
+                MirrorAxis::X => pos.x = size.x - 1 - pos.x,
+                MirrorAxis::Y => pos.y = size.y - 1 - pos.y,
+                MirrorAxis::Z => pos.z = size.z - 1 - pos.z,
+            }
+            pos
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn rotate_and_mirror() {
+        let stone = BlockState::from_raw(1);
+        let mut clipboard = Clipboard::new(IVec3::new(3, 1, 2));
+        clipboard.set_block(BlockPos::new(2, 0, 0), stone);
+
+        let rotated = clipboard.rotated(1);
+        assert_eq!(rotated.size(), IVec3::new(2, 1, 3));
+        assert_eq!(rotated.get_block(BlockPos::new(1, 0, 2)), stone);
+        assert_eq!(
+            clipboard.rotated(2).get_block(BlockPos::new(0, 0, 1)),
+            stone
+        );
+        assert_eq!(clipboard.rotated(-1), clipboard.rotated(3));
+        assert_eq!(rotated.rotated(3), clipboard);
+
+        let mirrored = clipboard.mirrored(MirrorAxis::X);
+        assert_eq!(mirrored.get_block(BlockPos::new(0, 0, 0)), stone);
+        assert_eq!(mirrored.mirrored(MirrorAxis::X), clipboard);
+    }
+}
@@ -0,0 +1,299 @@
+mod clipboard;
+mod schematic;
+
+use crate::block_state::{BlockState, AIR};
+use crate::{Chunk, ChunkManager};
+use math::aabb::AABB;
+use math::positions::{BlockPos, ChunkPos};
+use math::IVec3;
+use std::collections::HashMap;
+
+pub use clipboard::{Clipboard, MirrorAxis};
+pub use schematic::{
+    BlockMapping, Schematic, SchematicBlockEntity, SchematicError, SchematicVersion,
+};
+
+///give the state to place at each position of an edit
+pub trait Pattern {
+    fn state_at(&self, pos: BlockPos) -> BlockState;
+}
+
+impl Pattern for BlockState {
+    fn state_at(&self, _: BlockPos) -> BlockState {
+        *self
+    }
+}
+
+impl<F: Fn(BlockPos) -> BlockState> Pattern for F {
+    fn state_at(&self, pos: BlockPos) -> BlockState {
+        self(pos)
+    }
+}
+
+///a random mix of states, each state is chosen with a probability proportional to its weight
+///the choice only depends on the position and the seed, so the same edit always gives the same result
+#[derive(Clone, Debug)]
+pub struct RandomPattern {
+    states: Vec<(BlockState, u32)>,
+    total_weight: u32,
+    seed: u64,
+}
+
+impl RandomPattern {
+    pub fn new(states: Vec<(BlockState, u32)>, seed: u64) -> Self {
+        let total_weight = states.iter().map(|(_, weight)| weight).sum();
+        Self {
+            states,
+            total_weight,
+            seed,
+        }
+    }
+}
+
+impl Pattern for RandomPattern {
+    fn state_at(&self, pos: BlockPos) -> BlockState {
+        if self.total_weight == 0 {
+            return AIR;
+        }
+        //splitmix64 of the position, good enough to not see any pattern
+        let mut hash = self.seed
+            ^ (pos.x as u32 as u64)
+            ^ ((pos.y as u32 as u64) << 21)
+            ^ ((pos.z as u32 as u64) << 42);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^= hash >> 31;
+
+        let mut roll = (hash % self.total_weight as u64) as u32;
+        for (state, weight) in &self.states {
+            if roll < *weight {
+                return *state;
+            }
+            roll -= weight;
+        }
+        AIR
+    }
+}
+
+///the chunks that intersect the AABB, with the part of the AABB inside each chunk in local coordinates
+fn chunks_in(aabb: AABB) -> impl Iterator<Item = (ChunkPos, IVec3, IVec3)> {
+    let size = IVec3::splat(Chunk::SIZE);
+    let min_chunk = aabb.min().div_euclid(size);
+    let max_chunk = (aabb.max() - IVec3::ONE).div_euclid(size);
+    (min_chunk.z..=max_chunk.z).flat_map(move |z| {
+        (min_chunk.y..=max_chunk.y).flat_map(move |y| {
+            (min_chunk.x..=max_chunk.x).map(move |x| {
+                let chunk_pos = ChunkPos::new(x, y, z);
+                let origin = chunk_pos * Chunk::SIZE;
+                let local_min = (aabb.min() - origin).max(IVec3::ZERO);
+                let local_max = (aabb.max() - origin).min(size);
+                (chunk_pos, local_min, local_max)
+            })
+        })
+    })
+}
+
+fn block_index(pos: BlockPos) -> usize {
+    (pos.x + pos.y * Chunk::SIZE + pos.z * Chunk::SIZE * Chunk::SIZE) as usize
+}
+
+///bulk edits, the blocks of each chunk are edited in a buffer and the chunk is rebuilt once in the right format, see Chunk::set_blocks
+///the edited chunks are marked as modified like with make_dirty, so they are entirely resent instead of being recorded block by block
+impl ChunkManager {
+    ///call the function for each block of the AABB and place the returned state, return the number of changed blocks
+    ///the missing chunks are seen as air and are only created if a non-air block is placed in them
+    pub fn edit_blocks(
+        &mut self,
+        aabb: AABB,
+        mut func: impl FnMut(BlockPos, BlockState) -> BlockState,
+    ) -> usize {
+        let mut changed = 0;
+        for (chunk_pos, min, max) in chunks_in(aabb) {
+            let mut blocks = match self.get_chunk_untracked(chunk_pos) {
+                Some(chunk) => chunk.blocks(),
+                None => vec![AIR; Chunk::BLOCK_COUNT],
+            };
+
+            let origin = chunk_pos * Chunk::SIZE;
+            let mut chunk_changed = 0;
+            for z in min.z..max.z {
+                for y in min.y..max.y {
+                    for x in min.x..max.x {
+                        let local_pos = BlockPos::new(x, y, z);
+                        let block = &mut blocks[block_index(local_pos)];
+                        let state = func(origin + local_pos, *block);
+                        if state != *block {
+                            *block = state;
+                            chunk_changed += 1;
+                        }
+                    }
+                }
+            }
+            if chunk_changed == 0 {
+                continue;
+            }
+
+            changed += chunk_changed;
+            match self.get_chunk_mut(chunk_pos) {
+                Some(mut chunk) => chunk.set_blocks(&blocks),
+                None => {
+                    let mut chunk = Chunk::new(chunk_pos);
+                    chunk.set_blocks(&blocks);
+                    self.insert_chunk(chunk);
+                }
+            }
+        }
+        changed
+    }
+
+    ///place the pattern in the whole AABB, return the number of changed blocks
+    pub fn fill(&mut self, aabb: AABB, pattern: &impl Pattern) -> usize {
+        self.edit_blocks(aabb, |pos, _| pattern.state_at(pos))
+    }
+
+    ///place the pattern where the mask matches the current state, return the number of changed blocks
+    ///the mask is only evaluated once per different state
+    pub fn replace(
+        &mut self,
+        aabb: AABB,
+        mask: impl Fn(BlockState) -> bool,
+        pattern: &impl Pattern,
+    ) -> usize {
+        let mut matches = HashMap::new();
+        self.edit_blocks(aabb, |pos, state| {
+            match *matches.entry(state).or_insert_with(|| mask(state)) {
+                true => pattern.state_at(pos),
+                false => state,
+            }
+        })
+    }
+
+    ///copy the blocks of the AABB, the missing chunks are copied as air
+    pub fn copy(&self, aabb: AABB) -> Clipboard {
+        let mut clipboard = Clipboard::new(aabb.size());
+        for (chunk_pos, min, max) in chunks_in(aabb) {
+            let Some(chunk) = self.get_chunk_untracked(chunk_pos) else {
+                continue;
+            };
+            let blocks = chunk.blocks();
+            let offset = chunk_pos * Chunk::SIZE - aabb.min(); //from the chunk to the clipboard
+            for z in min.z..max.z {
+                for y in min.y..max.y {
+                    for x in min.x..max.x {
+                        let local_pos = BlockPos::new(x, y, z);
+                        clipboard.set_block(local_pos + offset, blocks[block_index(local_pos)]);
+                    }
+                }
+            }
+        }
+        clipboard
+    }
+
+    ///place the clipboard with its smallest corner at the given position, the chunks are created as needed
+    ///with skip_air the air of the clipboard doesn't replace the blocks of the world, return the number of changed blocks
+    pub fn paste(&mut self, clipboard: &Clipboard, pos: BlockPos, skip_air: bool) -> usize {
+        if clipboard.size().cmple(IVec3::ZERO).any() {
+            return 0;
+        }
+        let aabb = AABB::new(pos, pos + clipboard.size());
+        self.edit_blocks(aabb, |block_pos, state| {
+            match clipboard.get_block(block_pos - pos) {
+                AIR if skip_air => state,
+                new_state => new_state,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn fill_and_replace() {
+        let stone = BlockState::from_raw(1);
+        let dirt = BlockState::from_raw(2);
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::ZERO));
+        chunk_manager.on_process_modified_chunks(|_, _| {});
+
+        //from the middle of a loaded chunk to a missing one
+        let aabb = AABB::new(BlockPos::new(8, 0, 0), BlockPos::new(24, 4, 16));
+        assert_eq!(chunk_manager.fill(aabb, &stone), 16 * 4 * 16);
+        let chunk = chunk_manager.get_chunk(ChunkPos::X).unwrap();
+        assert_eq!(chunk.get_block_at(7, 3, 15), stone);
+        assert_eq!(chunk.get_block_at(8, 3, 15), AIR);
+        assert_eq!(
+            chunk_manager
+                .get_chunk(ChunkPos::ZERO)
+                .unwrap()
+                .get_block_at(7, 0, 0),
+            AIR
+        );
+        chunk_manager.on_process_modified_chunks(|ids, journal| {
+            assert_eq!(ids.len(), 2);
+            assert!(journal
+                .chunks()
+                .iter()
+                .all(|chunk| chunk.needs_full_resend()));
+        });
+
+        //filling with air doesn't create chunks
+        let far = AABB::new(BlockPos::new(100, 0, 0), BlockPos::new(110, 10, 10));
+        assert_eq!(chunk_manager.fill(far, &AIR), 0);
+        assert!(chunk_manager.get_chunk(ChunkPos::new(6, 0, 0)).is_none());
+
+        //half of the stone is kept
+        let pattern = RandomPattern::new(vec![(dirt, 1), (stone, 1)], 42);
+        let replaced = chunk_manager.replace(aabb, |state| state == stone, &pattern);
+        assert!(replaced > 0 && replaced < 16 * 4 * 16);
+        let copy = chunk_manager.copy(aabb);
+        assert_eq!(
+            copy.get_block(BlockPos::new(1, 1, 1)),
+            pattern.state_at(BlockPos::new(9, 1, 1))
+        );
+    }
+
+    #[test]
+    pub fn copy_and_paste() {
+        let stone = BlockState::from_raw(1);
+        let dirt = BlockState::from_raw(2);
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.fill(
+            AABB::new(BlockPos::new(-2, 0, 0), BlockPos::new(2, 1, 1)),
+            &stone,
+        );
+        chunk_manager.fill(
+            AABB::new(BlockPos::new(1, 0, 0), BlockPos::new(2, 1, 1)),
+            &dirt,
+        );
+
+        let clipboard =
+            chunk_manager.copy(AABB::new(BlockPos::new(-2, 0, 0), BlockPos::new(2, 2, 1)));
+        assert_eq!(clipboard.size(), IVec3::new(4, 2, 1));
+        assert_eq!(clipboard.get_block(BlockPos::new(3, 0, 0)), dirt);
+
+        //the rotated line goes along z, the dirt is at the end
+        let pasted = chunk_manager.paste(&clipboard.rotated(1), BlockPos::new(30, 0, 30), true);
+        assert_eq!(pasted, 4);
+        let block = |chunk_manager: &ChunkManager, x, y, z| {
+            let pos = BlockPos::new(x, y, z);
+            let chunk_pos = pos.div_euclid(IVec3::splat(Chunk::SIZE));
+            let chunk = chunk_manager.get_chunk(chunk_pos).unwrap();
+            chunk.get_block(pos.rem_euclid(IVec3::splat(Chunk::SIZE)))
+        };
+        assert_eq!(block(&chunk_manager, 30, 0, 30), stone);
+        assert_eq!(block(&chunk_manager, 30, 0, 33), dirt);
+        assert_eq!(block(&chunk_manager, 31, 0, 31), AIR);
+
+        //the air of the clipboard is skipped
+        chunk_manager.fill(
+            AABB::new(BlockPos::new(30, 1, 30), BlockPos::new(31, 2, 31)),
+            &dirt,
+        );
+        chunk_manager.paste(&clipboard.rotated(1), BlockPos::new(30, 0, 30), true);
+        assert_eq!(block(&chunk_manager, 30, 1, 30), dirt);
+        chunk_manager.paste(&clipboard.rotated(1), BlockPos::new(30, 0, 30), false);
+        assert_eq!(block(&chunk_manager, 30, 1, 30), AIR);
+    }
+}
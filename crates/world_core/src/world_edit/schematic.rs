@@ -0,0 +1,585 @@
+use crate::block_entity::BlockEntity;
+use crate::block_state::{BlockState, BlockStateRegistry};
+use crate::world_edit::Clipboard;
+use crate::{Chunk, ChunkManager};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use ident::Ident;
+use math::aabb::AABB;
+use math::positions::BlockPos;
+use math::IVec3;
+use nbt::{Compound, List, Value};
+use std::collections::HashMap;
+use std::io::Read;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SchematicError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("invalid schematic nbt: {0}")]
+    Nbt(#[from] nbt::binary::Error),
+    #[error("unsupported schematic version {0}, only the versions 2 and 3 are supported")]
+    UnsupportedVersion(i32),
+    #[error("the schematic doesn't have a valid {0} field")]
+    InvalidField(&'static str),
+    #[error("the palette index {0} is out of the palette")]
+    InvalidPaletteIndex(u32),
+    #[error("the block {0} can't be mapped to a state")]
+    UnknownBlock(String),
+    #[error("the state {0} doesn't have a name in the mapping")]
+    UnnamedState(u16),
+    #[error("the schematic is too big, each side must fit in an u16")]
+    TooBig,
+}
+
+///the translation between the names of a schematic palette, like "minecraft:stone[axis=y]", and our states
+pub trait BlockMapping {
+    fn state_of(&self, name: &str) -> Option<BlockState>;
+    fn name_of(&self, state: BlockState) -> Option<String>;
+}
+
+impl BlockMapping for BlockStateRegistry {
+    fn state_of(&self, name: &str) -> Option<BlockState> {
+        self.parse_state(name)
+    }
+
+    fn name_of(&self, state: BlockState) -> Option<String> {
+        self.format_state(state)
+    }
+}
+
+///the two versions of the Sponge schematic format, v3 moves the blocks and their block entities in a "Blocks" compound
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchematicVersion {
+    V2,
+    V3,
+}
+
+///a block entity of a schematic, the position is relative to the smallest corner of the schematic
+///data holds every field but the position and the id, it's written back untouched
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchematicBlockEntity {
+    pub pos: BlockPos,
+    pub id: String,
+    pub data: Compound,
+}
+
+///a build stored in the Sponge schematic format (.schem), used to exchange builds with the other tools
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    pub blocks: Clipboard,
+    ///where the smallest corner is placed relative to the paste position
+    pub offset: IVec3,
+    ///the minecraft data version of the names of the palette
+    pub data_version: i32,
+    pub metadata: Compound,
+    pub block_entities: Vec<SchematicBlockEntity>,
+}
+
+impl Schematic {
+    pub fn new(blocks: Clipboard) -> Self {
+        Self {
+            blocks,
+            offset: IVec3::ZERO,
+            data_version: 0,
+            metadata: Compound::new(),
+            block_entities: Vec::new(),
+        }
+    }
+
+    ///copy the blocks and the block entities of the AABB, the missing chunks are copied as air
+    pub fn copy(chunk_manager: &ChunkManager, aabb: AABB) -> Self {
+        let mut schematic = Self::new(chunk_manager.copy(aabb));
+        let size = IVec3::splat(Chunk::SIZE);
+        let chunk_aabb = AABB::new(
+            aabb.min().div_euclid(size),
+            (aabb.max() - IVec3::ONE).div_euclid(size) + IVec3::ONE,
+        );
+        chunk_manager.foreach_chunk_in(chunk_aabb, &mut |_, chunk| {
+            for (pos, block_entity) in chunk.block_entities().iter() {
+                let pos = chunk.position() * Chunk::SIZE + pos;
+                //AABB::contains includes the max corner, but it's out of the copied volume
+                if pos.cmpge(aabb.min()).all() && pos.cmplt(aabb.max()).all() {
+                    schematic.block_entities.push(SchematicBlockEntity {
+                        pos: pos - aabb.min(),
+                        id: block_entity.kind.to_string(),
+                        data: block_entity.data.clone(),
+                    });
+                }
+            }
+        });
+        //the chunks are visited in no particular order
+        schematic
+            .block_entities
+            .sort_unstable_by_key(|block_entity| block_entity.pos.to_array());
+        schematic
+    }
+
+    ///place the blocks and the block entities at the given position moved by the offset, see ChunkManager::paste
+    ///the block entities with an invalid id are skipped, return the number of changed blocks
+    pub fn paste(&self, chunk_manager: &mut ChunkManager, pos: BlockPos, skip_air: bool) -> usize {
+        let origin = pos + self.offset;
+        let changed = chunk_manager.paste(&self.blocks, origin, skip_air);
+        for block_entity in &self.block_entities {
+            if let Ok(kind) = Ident::try_from(block_entity.id.as_str()) {
+                chunk_manager.set_block_entity(
+                    origin + block_entity.pos,
+                    BlockEntity::new(kind, block_entity.data.clone()),
+                );
+            }
+        }
+        changed
+    }
+
+    ///read a schematic file, compressed with gzip or not
+    ///the names missing from the mapping are replaced by unknown_state or make the read fail
+    pub fn read(
+        data: &[u8],
+        mapping: &impl BlockMapping,
+        unknown_state: Option<BlockState>,
+    ) -> Result<Self, SchematicError> {
+        let mut decompressed = Vec::new();
+        let mut data = data;
+        if data.starts_with(&[0x1f, 0x8b]) {
+            GzDecoder::new(data).read_to_end(&mut decompressed)?;
+            data = &decompressed;
+        }
+        let (nbt, _) = nbt::from_binary::<String>(&mut &*data)?;
+        Self::from_nbt(&nbt, mapping, unknown_state)
+    }
+
+    ///convert the root compound of a schematic file
+    pub fn from_nbt(
+        nbt: &Compound,
+        mapping: &impl BlockMapping,
+        unknown_state: Option<BlockState>,
+    ) -> Result<Self, SchematicError> {
+        //the v3 fields are wrapped in a "Schematic" compound
+        let schematic = match nbt.get("Schematic") {
+            Some(Value::Compound(schematic)) => schematic,
+            _ => nbt,
+        };
+        let version = schematic
+            .get("Version")
+            .and_then(Value::as_i32)
+            .ok_or(SchematicError::InvalidField("Version"))?;
+        let blocks = match version {
+            2 => schematic,
+            3 => match schematic.get("Blocks") {
+                Some(Value::Compound(blocks)) => blocks,
+                _ => return Err(SchematicError::InvalidField("Blocks")),
+            },
+            _ => return Err(SchematicError::UnsupportedVersion(version)),
+        };
+
+        let size = IVec3::new(
+            read_side(schematic, "Width")?,
+            read_side(schematic, "Height")?,
+            read_side(schematic, "Length")?,
+        );
+        let offset = match schematic.get("Offset") {
+            Some(Value::IntArray(offset)) if offset.len() == 3 => {
+                IVec3::new(offset[0], offset[1], offset[2])
+            }
+            None => IVec3::ZERO,
+            _ => return Err(SchematicError::InvalidField("Offset")),
+        };
+
+        let data_field = match version {
+            2 => "BlockData",
+            _ => "Data",
+        };
+        let palette = match blocks.get("Palette") {
+            Some(Value::Compound(palette)) => read_palette(palette, mapping, unknown_state)?,
+            _ => return Err(SchematicError::InvalidField("Palette")),
+        };
+        let data = match blocks.get(data_field) {
+            Some(Value::ByteArray(data)) => data,
+            _ => return Err(SchematicError::InvalidField(data_field)),
+        };
+
+        //each palette index takes at least a byte, so a bigger volume can only come from a broken header
+        let volume = Clipboard::volume(size).ok_or(SchematicError::TooBig)?;
+        if volume > data.len() {
+            return Err(SchematicError::InvalidField(data_field));
+        }
+
+        //the index of a block is x + z * width + y * width * length, and each palette index is a varint
+        let mut clipboard = Clipboard::new(size);
+        let mut bytes = data.iter().map(|byte| *byte as u8);
+        for y in 0..size.y {
+            for z in 0..size.z {
+                for x in 0..size.x {
+                    let index =
+                        read_varint(&mut bytes).ok_or(SchematicError::InvalidField(data_field))?;
+                    let state = palette
+                        .get(&index)
+                        .ok_or(SchematicError::InvalidPaletteIndex(index))?;
+                    clipboard.set_block(BlockPos::new(x, y, z), *state);
+                }
+            }
+        }
+
+        let block_entities = match blocks.get("BlockEntities") {
+            Some(Value::List(List::Compound(block_entities))) => block_entities
+                .iter()
+                .map(|block_entity| read_block_entity(block_entity, version))
+                .collect::<Result<_, _>>()?,
+            Some(Value::List(List::End)) | None => Vec::new(),
+            _ => return Err(SchematicError::InvalidField("BlockEntities")),
+        };
+
+        Ok(Self {
+            blocks: clipboard,
+            offset,
+            data_version: schematic
+                .get("DataVersion")
+                .and_then(Value::as_i32)
+                .unwrap_or(0),
+            metadata: match schematic.get("Metadata") {
+                Some(Value::Compound(metadata)) => metadata.clone(),
+                _ => Compound::new(),
+            },
+            block_entities,
+        })
+    }
+
+    ///build the root compound of a schematic file
+    pub fn to_nbt(
+        &self,
+        version: SchematicVersion,
+        mapping: &impl BlockMapping,
+    ) -> Result<Compound, SchematicError> {
+        let size = self.blocks.size();
+        if size.cmpgt(IVec3::splat(u16::MAX as i32)).any() {
+            return Err(SchematicError::TooBig);
+        }
+
+        //the palette is built in the order of appearance
+        let mut indices = HashMap::new();
+        let mut palette = Compound::new();
+        let mut data = Vec::new();
+        for y in 0..size.y {
+            for z in 0..size.z {
+                for x in 0..size.x {
+                    let state = self.blocks.get_block(BlockPos::new(x, y, z));
+                    let index = match indices.get(&state) {
+                        Some(index) => *index,
+                        None => {
+                            let name = mapping
+                                .name_of(state)
+                                .ok_or(SchematicError::UnnamedState(state.raw()))?;
+                            let index = indices.len() as i32;
+                            palette.insert(name, index);
+                            indices.insert(state, index);
+                            index
+                        }
+                    };
+                    write_varint(&mut data, index as u32);
+                }
+            }
+        }
+
+        let block_entities = self
+            .block_entities
+            .iter()
+            .map(|block_entity| write_block_entity(block_entity, version))
+            .collect::<Vec<_>>();
+        let mut schematic = Compound::new();
+        schematic.insert(
+            "Version",
+            match version {
+                SchematicVersion::V2 => 2,
+                SchematicVersion::V3 => 3,
+            },
+        );
+        schematic.insert("DataVersion", self.data_version);
+        schematic.insert("Width", size.x as u16 as i16);
+        schematic.insert("Height", size.y as u16 as i16);
+        schematic.insert("Length", size.z as u16 as i16);
+        schematic.insert("Offset", self.offset.to_array().to_vec());
+        if !self.metadata.is_empty() {
+            schematic.insert("Metadata", self.metadata.clone());
+        }
+        let data = data.into_iter().map(|byte| byte as i8).collect::<Vec<_>>();
+
+        Ok(match version {
+            SchematicVersion::V2 => {
+                schematic.insert("PaletteMax", indices.len() as i32);
+                schematic.insert("Palette", palette);
+                schematic.insert("BlockData", data);
+                schematic.insert("BlockEntities", List::Compound(block_entities));
+                schematic
+            }
+            SchematicVersion::V3 => {
+                let mut blocks = Compound::new();
+                blocks.insert("Palette", palette);
+                blocks.insert("Data", data);
+                blocks.insert("BlockEntities", List::Compound(block_entities));
+                schematic.insert("Blocks", blocks);
+                let mut root = Compound::new();
+                root.insert("Schematic", schematic);
+                root
+            }
+        })
+    }
+
+    ///write a schematic file compressed with gzip, like the other tools do
+    pub fn write(
+        &self,
+        version: SchematicVersion,
+        mapping: &impl BlockMapping,
+    ) -> Result<Vec<u8>, SchematicError> {
+        let nbt = self.to_nbt(version, mapping)?;
+        let root_name = match version {
+            SchematicVersion::V2 => "Schematic",
+            SchematicVersion::V3 => "",
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        nbt::to_binary(&nbt, &mut encoder, root_name)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+///the sides are unsigned shorts
+fn read_side(schematic: &Compound, field: &'static str) -> Result<i32, SchematicError> {
+    match schematic.get(field) {
+        Some(Value::Short(side)) => Ok(*side as u16 as i32),
+        Some(value) => value
+            .as_i32()
+            .filter(|side| *side >= 0)
+            .ok_or(SchematicError::InvalidField(field)),
+        None => Err(SchematicError::InvalidField(field)),
+    }
+}
+
+fn read_palette(
+    palette: &Compound,
+    mapping: &impl BlockMapping,
+    unknown_state: Option<BlockState>,
+) -> Result<HashMap<u32, BlockState>, SchematicError> {
+    palette
+        .iter()
+        .map(|(name, index)| {
+            let index = index
+                .as_i32()
+                .ok_or(SchematicError::InvalidField("Palette"))?;
+            let state = match mapping.state_of(name) {
+                Some(state) => state,
+                None => unknown_state.ok_or_else(|| SchematicError::UnknownBlock(name.clone()))?,
+            };
+            Ok((index as u32, state))
+        })
+        .collect()
+}
+
+///return None if the data ends in the middle of the varint or if it's longer than 5 bytes
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u32> {
+    let mut value = 0u32;
+    for shift in (0..32).step_by(7) {
+        let byte = bytes.next()?;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        data.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+///the v2 block entities hold their fields next to the position and the id, the v3 ones hold them in a "Data" compound
+fn read_block_entity(
+    block_entity: &Compound,
+    version: i32,
+) -> Result<SchematicBlockEntity, SchematicError> {
+    let pos = match block_entity.get("Pos") {
+        Some(Value::IntArray(pos)) if pos.len() == 3 => BlockPos::new(pos[0], pos[1], pos[2]),
+        _ => return Err(SchematicError::InvalidField("Pos")),
+    };
+    let id = match block_entity.get("Id") {
+        Some(Value::String(id)) => id.clone(),
+        _ => return Err(SchematicError::InvalidField("Id")),
+    };
+    let data = match version {
+        2 => {
+            let mut data = block_entity.clone();
+            data.remove("Pos");
+            data.remove("Id");
+            data
+        }
+        _ => match block_entity.get("Data") {
+            Some(Value::Compound(data)) => data.clone(),
+            None => Compound::new(),
+            _ => return Err(SchematicError::InvalidField("Data")),
+        },
+    };
+    Ok(SchematicBlockEntity { pos, id, data })
+}
+
+fn write_block_entity(block_entity: &SchematicBlockEntity, version: SchematicVersion) -> Compound {
+    let mut compound = Compound::new();
+    compound.insert("Pos", block_entity.pos.to_array().to_vec());
+    compound.insert("Id", block_entity.id.as_str());
+    match version {
+        SchematicVersion::V2 => compound.merge(block_entity.data.clone()),
+        SchematicVersion::V3 => {
+            compound.insert("Data", block_entity.data.clone());
+        }
+    }
+    compound
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::block_state::{Property, AIR};
+    use ident::ident;
+    use math::positions::ChunkPos;
+    use nbt::compound;
+
+    fn registry() -> BlockStateRegistry {
+        let mut registry = BlockStateRegistry::new();
+        registry
+            .register_block(
+                ident!("minecraft:oak_log").into(),
+                vec![Property::enumeration("axis", &["x", "y", "z"])],
+            )
+            .unwrap();
+        registry
+            .register_block(
+                ident!("minecraft:test").into(),
+                vec![Property::int("level", 0, 199)],
+            )
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let registry = registry();
+        let log = registry.parse_state("minecraft:oak_log[axis=z]").unwrap();
+
+        //more than 128 states, so some indices take two bytes
+        let mut clipboard = Clipboard::new(IVec3::new(20, 3, 10));
+        for i in 0..200 {
+            let state = registry
+                .state_of("test", &[("level", &i.to_string())])
+                .unwrap();
+            clipboard.set_block(BlockPos::new(i % 20, 1, i / 20), state);
+        }
+        clipboard.set_block(BlockPos::new(19, 2, 9), log);
+        let mut schematic = Schematic::new(clipboard);
+        schematic.offset = IVec3::new(-1, 0, 2);
+        schematic.data_version = 3465;
+        schematic.metadata = compound! { "Name" => "house" };
+        schematic.block_entities.push(SchematicBlockEntity {
+            pos: BlockPos::new(19, 2, 9),
+            id: "minecraft:chest".to_string(),
+            data: compound! { "Items" => List::Compound(vec![compound! { "Slot" => 3i8 }]) },
+        });
+
+        for version in [SchematicVersion::V2, SchematicVersion::V3] {
+            let data = schematic.write(version, &registry).unwrap();
+            let read = Schematic::read(&data, &registry, None).unwrap();
+            assert_eq!(read, schematic);
+        }
+
+        let mut chunk_manager = ChunkManager::new();
+        schematic.paste(&mut chunk_manager, BlockPos::new(0, 0, 0), true);
+        let chunk = chunk_manager.get_chunk(ChunkPos::new(1, 0, 0)).unwrap();
+        assert_eq!(chunk.get_block_at(2, 2, 11), log);
+        let copy = Schematic::copy(
+            &chunk_manager,
+            AABB::new(BlockPos::new(-1, 0, 2), BlockPos::new(19, 3, 12)),
+        );
+        assert_eq!(copy.blocks, schematic.blocks);
+        assert_eq!(copy.block_entities, schematic.block_entities);
+    }
+
+    #[test]
+    pub fn copy_block_entities_in_the_volume() {
+        let mut chunk_manager = ChunkManager::new();
+        chunk_manager.insert_chunk(Chunk::new(ChunkPos::ZERO));
+        let chest = BlockEntity::new(ident!("minecraft:chest").into(), Compound::new());
+        let aabb = AABB::new(BlockPos::new(1, 1, 1), BlockPos::new(4, 4, 4));
+        chunk_manager.set_block_entity(BlockPos::new(3, 3, 3), chest.clone());
+        chunk_manager.set_block_entity(aabb.max(), chest);
+
+        let copy = Schematic::copy(&chunk_manager, aabb);
+        assert_eq!(copy.blocks.size(), IVec3::splat(3));
+        assert_eq!(copy.block_entities.len(), 1);
+        assert_eq!(copy.block_entities[0].pos, BlockPos::new(2, 2, 2));
+    }
+
+    #[test]
+    pub fn read_v2() {
+        let registry = registry();
+        let nbt = compound! {
+            "Version" => 2,
+            "DataVersion" => 3465,
+            "Width" => 2i16,
+            "Height" => 1i16,
+            "Length" => 1i16,
+            "Palette" => compound! {
+                "minecraft:air" => 0,
+                "minecraft:oak_log[axis=x]" => 1,
+            },
+            "BlockData" => vec![1i8, 0],
+            "BlockEntities" => List::Compound(vec![compound! {
+                "Pos" => vec![0, 0, 0],
+                "Id" => "minecraft:sign",
+                "Text1" => "hello",
+            }]),
+        };
+        let schematic = Schematic::from_nbt(&nbt, &registry, None).unwrap();
+        assert_eq!(
+            schematic.blocks.get_block(BlockPos::ZERO),
+            registry.parse_state("oak_log[axis=x]").unwrap()
+        );
+        assert_eq!(schematic.blocks.get_block(BlockPos::X), AIR);
+        assert_eq!(
+            schematic.block_entities[0].data,
+            compound! { "Text1" => "hello" }
+        );
+
+        let mut unknown = nbt.clone();
+        unknown.insert("Palette", compound! { "minecraft:dirt" => 0 });
+        unknown.insert("BlockData", vec![0i8, 0]);
+        assert!(matches!(
+            Schematic::from_nbt(&unknown, &registry, None),
+            Err(SchematicError::UnknownBlock(_))
+        ));
+        let schematic = Schematic::from_nbt(&unknown, &registry, Some(AIR)).unwrap();
+        assert_eq!(schematic.blocks.get_block(BlockPos::ZERO), AIR);
+
+        let mut truncated = nbt.clone();
+        truncated.insert("BlockData", vec![1i8]);
+        assert!(Schematic::from_nbt(&truncated, &registry, None).is_err());
+
+        //the size is checked against the data before anything is allocated
+        let mut huge = nbt.clone();
+        for side in ["Width", "Height", "Length"] {
+            huge.insert(side, -1i16); //65535
+        }
+        assert!(matches!(
+            Schematic::from_nbt(&huge, &registry, None),
+            Err(SchematicError::InvalidField("BlockData"))
+        ));
+        let mut overflow = nbt;
+        for side in ["Width", "Height", "Length"] {
+            overflow.insert(side, i32::MAX);
+        }
+        assert!(matches!(
+            Schematic::from_nbt(&overflow, &registry, None),
+            Err(SchematicError::TooBig)
+        ));
+    }
+}
@@ -6,22 +6,28 @@ use egui_winit::winit::event_loop::{EventLoop, EventLoopWindowTarget};
 use egui_winit::winit::keyboard::{KeyCode, PhysicalKey};
 use egui_winit::winit::window::WindowBuilder;
 use gen::Generator;
+use ident::Ident;
+use math::aabb::FloatAABB;
 use math::positions::{BlockPos, ChunkPos, EntityPos};
 use math::{DVec3, Vec3};
 use std::f32::consts::{FRAC_PI_2, PI};
 use std::time::{Duration, Instant};
-use world_core::{Chunk, ChunkManager, MEMORY_MANAGER};
+use world_core::biome::ChunkBiomes;
+use world_core::block_state::{BlockState, BlockStateRegistry};
+use world_core::physics::CollisionShapes;
+use world_core::{Chunk, ChunkManager, ConcurrentChunkManager, MissingChunks, MEMORY_MANAGER};
 use rand::random;
 
 fn main_menu(gui_wrapper: &mut GUIWrapper<GUIData>, ctx: &egui::Context, data: &mut GUIData) {
     egui::Window::new("Tool box").show(ctx, |ui| {
         let fps = 1.0 / data.second_per_frame;
 
-        let (used_memory, pre_allocated_memory) = MEMORY_MANAGER.stats();
+        let stats = MEMORY_MANAGER.stats();
         ui.label(format!("fps: {:.2}", fps));
-        ui.label(format!("used memory: {}", used_memory));
+        ui.label(format!("used memory: {}", stats.used));
 
-        ui.label(format!("pre-allocated memory: {}", pre_allocated_memory));
+        ui.label(format!("pre-allocated memory: {}", stats.pre_allocated));
+        ui.label(format!("uniform chunks: {}", stats.uniform_chunks));
         if ui.button("more options").clicked() {
             gui_wrapper.set_gui(other_gui);
         }
@@ -130,7 +136,13 @@ impl CameraController {
         self.mouse_y += delta.1;
     }
 
-    fn update_camera(&mut self, camera: &mut graphic::camera::Camera, delta_time: Duration) {
+    fn update_camera(
+        &mut self,
+        camera: &mut graphic::camera::Camera,
+        chunk_manager: &ChunkManager,
+        collision_shapes: &CollisionShapes,
+        delta_time: Duration,
+    ) {
         //update camera yaw and pitch
         camera.yaw += self.mouse_x as f32 * 0.0025;
 
@@ -169,7 +181,18 @@ impl CameraController {
         if self.is_down_pressed {
             direction -= Vec3::Y;
         }
-        camera.position += direction.normalize_or_zero() * self.speed * delta_time;
+        //the camera has a hitbox around the eyes so it can't go through the terrain, the ungenerated chunks are empty
+        let hitbox = FloatAABB::new(Vec3::new(-0.3, -1.5, -0.3), Vec3::new(0.3, 0.3, 0.3));
+        let velocity = direction.normalize_or_zero() * self.speed * delta_time;
+        let collision = chunk_manager.sweep_aabb(
+            camera.position,
+            hitbox,
+            velocity,
+            0.0,
+            collision_shapes,
+            MissingChunks::Empty,
+        );
+        camera.position += collision.motion;
         camera.position.try_shrink();
     }
 }
@@ -183,35 +206,134 @@ pub struct App {
     terrain_renderer: graphic::terrain::TerrainRenderer,
     camera_controller: CameraController,
     chunk_manager: ChunkManager,
+    collision_shapes: CollisionShapes,
     seed: i64,
 }
 
-impl App {
-    fn regenerate_cube(chunk_manager: &mut ChunkManager, generator: &mut Generator) {
-        //make a platform
-        let mut build_chunk = |x: i32, z: i32, y: i32| {
-            let mut chunk = Chunk::new(ChunkPos::new(x, y, z));
-
-            for ix in 0..16 {
-                for iz in 0..16 {
-                    for iy in 0..16 {
-                        let block =
-                            generator.get_block(ix + x * 16, iy + y * 16, iz + z * 16) as u16;
-                        chunk.set_block(BlockPos::new(ix, iy, iz), block);
-                    }
-                }
-            }
-            chunk_manager.insert_chunk(chunk);
-        };
+///the blocks of the client in the order of the terrain texture atlas, the mesher takes the texture of a
+///state from its raw id so each block must be registered with the id of its texture plus one
+const TERRAIN_BLOCKS: [&str; 11] = [
+    "minecraft:stone",
+    "minecraft:diamond_block",
+    "minecraft:emerald_block",
+    "minecraft:lapis_block",
+    "minecraft:gold_block",
+    "minecraft:iron_block",
+    "minecraft:coal_block",
+    "minecraft:red_wool",
+    "minecraft:hay_block",
+    "archipel:hay_block_side",
+    "minecraft:grass_block",
+];
+
+///the blocks returned by Generator.getBlock for each of its ids
+const GENERATOR_BLOCKS: [(usize, &str); 6] = [
+    (0, "minecraft:air"),
+    (1, "minecraft:stone"),
+    (3, "minecraft:emerald_block"),
+    (4, "minecraft:lapis_block"),
+    (5, "minecraft:gold_block"),
+    (11, "minecraft:grass_block"),
+];
+
+///register the terrain blocks, checking that their states match the texture atlas
+fn block_registry() -> anyhow::Result<BlockStateRegistry> {
+    let mut registry = BlockStateRegistry::new();
+    for (texture, block) in TERRAIN_BLOCKS.into_iter().enumerate() {
+        let state = registry
+            .register_block(Ident::new(block)?.into(), Vec::new())?
+            .default_state();
+        anyhow::ensure!(
+            state.raw() as usize == texture + 1,
+            "{block} doesn't have the state of its texture"
+        );
+    }
+    Ok(registry)
+}
+
+///resolve the ids of the generator through the registry, indexed by generator id
+fn generator_states(registry: &BlockStateRegistry) -> anyhow::Result<Vec<Option<BlockState>>> {
+    let len = GENERATOR_BLOCKS
+        .iter()
+        .map(|(id, _)| id + 1)
+        .max()
+        .unwrap_or(0);
+    let mut states = vec![None; len];
+    for (id, block) in GENERATOR_BLOCKS {
+        let state = registry
+            .default_state(block)
+            .ok_or_else(|| anyhow::anyhow!("the generator block {block} isn't registered"))?;
+        states[id] = Some(state);
+    }
+    Ok(states)
+}
 
-        for x in -20..20 {
-            for z in -20..20 {
-                for y in -5..5 {
-                    build_chunk(x, z, y);
+impl App {
+    fn build_chunk(
+        generator: &mut Generator,
+        states: &[Option<BlockState>],
+        pos: ChunkPos,
+    ) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        let origin = pos * Chunk::SIZE;
+        for ix in 0..16 {
+            for iz in 0..16 {
+                for iy in 0..16 {
+                    let block = generator.get_block(ix + origin.x, iy + origin.y, iz + origin.z);
+                    let state = usize::try_from(block)
+                        .ok()
+                        .and_then(|block| states.get(block).copied().flatten())
+                        .expect("the generator gave an unknown block");
+                    chunk.set_block(BlockPos::new(ix, iy, iz), state);
                 }
             }
         }
+        let biomes = generator.get_biomes(pos.x, pos.y, pos.z);
+        chunk.biomes_mut().fill_with(|pos| {
+            biomes[ChunkBiomes::cell_index(ChunkBiomes::cell_of(pos))]
+                .parse()
+                .expect("the generator gave an invalid biome")
+        });
+        chunk.compact();
+        chunk
+    }
+
+    ///make a platform, the columns are spread between worker threads that each have their own generator
+    fn regenerate_cube(jar_path: &str, seed: i64) -> anyhow::Result<ChunkManager> {
+        let states = generator_states(&block_registry()?)?;
+        let chunk_manager = ConcurrentChunkManager::new();
+        let threads =
+            std::thread::available_parallelism().map_or(1, |threads| threads.get()) as i32;
+        std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|worker| {
+                    let chunk_manager = &chunk_manager;
+                    let states = &states;
+                    scope.spawn(move || -> anyhow::Result<()> {
+                        let mut generator = Generator::new(jar_path, seed)?;
+                        for x in (-20..20).filter(|x: &i32| x.rem_euclid(threads) == worker) {
+                            for z in -20..20 {
+                                for y in -5..5 {
+                                    let chunk = Self::build_chunk(
+                                        &mut generator,
+                                        states,
+                                        ChunkPos::new(x, y, z),
+                                    );
+                                    chunk_manager.insert_chunk(chunk);
+                                }
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .try_for_each(|worker| worker.join().expect("a generation thread panicked"))
+        })?;
+        Ok(chunk_manager.into_chunk_manager())
     }
+
     pub fn new() -> anyhow::Result<(Self, EventLoop<()>)> {
         let event_loop = EventLoop::new()?;
         let window = WindowBuilder::new()
@@ -236,12 +358,9 @@ impl App {
         );
 
         //todo: move this to a better place, when the network will be implemented
-        let mut chunk_manager = ChunkManager::new();
-
         let seed = random();
-        let mut generator = Generator::new("crates/gen/build/libs/generator-1.0.0.jar", seed)?;
-
-        Self::regenerate_cube(&mut chunk_manager, &mut generator);
+        let chunk_manager =
+            Self::regenerate_cube("crates/gen/build/libs/generator-1.0.0.jar", seed)?;
 
         let terrain_renderer =
             graphic::terrain::TerrainRenderer::new(&camera, 16, &chunk_manager, &graphic_context);
@@ -256,6 +375,7 @@ impl App {
                 terrain_renderer,
                 camera_controller: CameraController::new(),
                 chunk_manager,
+                collision_shapes: CollisionShapes::new(),
                 seed,
             },
             event_loop,
@@ -317,8 +437,12 @@ impl App {
             world_seed: self.seed,
         };
 
-        self.camera_controller
-            .update_camera(&mut self.camera, delta_time);
+        self.camera_controller.update_camera(
+            &mut self.camera,
+            &self.chunk_manager,
+            &self.collision_shapes,
+            delta_time,
+        );
         self.gui_handler
             .update_gui(&self.window, &self.graphic_context, &mut gui_data);
 
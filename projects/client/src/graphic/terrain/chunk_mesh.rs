@@ -248,38 +248,31 @@ impl ChunkMesh {
                 }
             };
 
-        for y in 0..CHUNK_SIZE {
-            for z in 0..CHUNK_SIZE {
-                for x in 0..CHUNK_SIZE {
-                    let blockstate = chunk.get_block_at(x, y, z);
-                    if blockstate == AIR {
-                        continue;
-                    }
-                    let blockstate = (blockstate - 1) as u32;
+        for (pos, blockstate) in chunk.iter_non_air_blocks() {
+            let (x, y, z) = (pos.x, pos.y, pos.z);
+            let blockstate = (blockstate.raw() - 1) as u32;
 
-                    let texture_coordinates = texture_atlas.get_texture_coordinates();
-                    let fx = x as f32;
-                    let fy = y as f32;
-                    let fz = z as f32;
-                    if get_block_at(x, y + 1, z) == AIR {
-                        add_face(fx, fy, fz, Face::Top, texture_coordinates, blockstate);
-                    }
-                    if get_block_at(x, y - 1, z) == AIR {
-                        add_face(fx, fy, fz, Face::Bottom, texture_coordinates, blockstate);
-                    }
-                    if get_block_at(x - 1, y, z) == AIR {
-                        add_face(fx, fy, fz, Face::West, texture_coordinates, blockstate);
-                    }
-                    if get_block_at(x + 1, y, z) == AIR {
-                        add_face(fx, fy, fz, Face::East, texture_coordinates, blockstate);
-                    }
-                    if get_block_at(x, y, z - 1) == AIR {
-                        add_face(fx, fy, fz, Face::North, texture_coordinates, blockstate);
-                    }
-                    if get_block_at(x, y, z + 1) == AIR {
-                        add_face(fx, fy, fz, Face::South, texture_coordinates, blockstate);
-                    }
-                }
+            let texture_coordinates = texture_atlas.get_texture_coordinates();
+            let fx = x as f32;
+            let fy = y as f32;
+            let fz = z as f32;
+            if get_block_at(x, y + 1, z) == AIR {
+                add_face(fx, fy, fz, Face::Top, texture_coordinates, blockstate);
+            }
+            if get_block_at(x, y - 1, z) == AIR {
+                add_face(fx, fy, fz, Face::Bottom, texture_coordinates, blockstate);
+            }
+            if get_block_at(x - 1, y, z) == AIR {
+                add_face(fx, fy, fz, Face::West, texture_coordinates, blockstate);
+            }
+            if get_block_at(x + 1, y, z) == AIR {
+                add_face(fx, fy, fz, Face::East, texture_coordinates, blockstate);
+            }
+            if get_block_at(x, y, z - 1) == AIR {
+                add_face(fx, fy, fz, Face::North, texture_coordinates, blockstate);
+            }
+            if get_block_at(x, y, z + 1) == AIR {
+                add_face(fx, fy, fz, Face::South, texture_coordinates, blockstate);
             }
         }
 
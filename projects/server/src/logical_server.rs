@@ -1,7 +1,10 @@
 use flume::Receiver;
-use tracing::info;
+use tracing::{info, warn};
 
 use networking::client::PrimitiveClientComponents;
+use world_core::block_state::BlockStateRegistry;
+use world_core::fluid::{Fluid, Fluids};
+use world_core::ChunkManager;
 
 /**
  * This module contains the logical server implementation.
@@ -11,20 +14,50 @@ use networking::client::PrimitiveClientComponents;
 
 pub(crate) struct GameServer {
     new_connections: Receiver<PrimitiveClientComponents>,
+    world: ChunkManager,
+    fluids: Fluids,
+    seed: u64,
+    random_tick_speed: u32,
 }
 
 impl GameServer {
-    pub fn new(new_connections: Receiver<PrimitiveClientComponents>) -> Self {
-        Self { new_connections }
+    pub fn new(new_connections: Receiver<PrimitiveClientComponents>, seed: u64, random_tick_speed: u32, registry: Option<&BlockStateRegistry>) -> Self {
+        // the fluids need the states of their levels, without a registry nothing flows
+        let mut fluids = Fluids::new();
+        match registry {
+            Some(registry) => {
+                for fluid in [Fluid::water(registry), Fluid::lava(registry)].into_iter().flatten() {
+                    fluids.register(fluid);
+                }
+            }
+            None => warn!("no block report in the world config, the water and the lava won't flow"),
+        }
+
+        Self {
+            new_connections,
+            world: ChunkManager::new(),
+            fluids,
+            seed,
+            random_tick_speed,
+        }
     }
 
-    pub fn tick(&mut self, _tick_date: u64) -> anyhow::Result<()> {
+    pub fn tick(&mut self, tick_date: u64) -> anyhow::Result<()> {
         for client in self.new_connections.try_iter() {
             info!(
                 "New connection: {:?}, {:?}, {:?}",
                 client.ip, client.username, client.uuid
             );
         }
+
+        // the block updates scheduled for this tick, then the random ticks like vanilla
+        // todo: dispatch to the block behaviours once they exist
+        let fluids = &self.fluids;
+        self.world.run_scheduled_ticks(tick_date, |world, pos, state| {
+            world.tick_fluid(pos, state, tick_date, fluids);
+        });
+        self.world.run_random_ticks(tick_date, self.random_tick_speed, self.seed, |_world, _pos, _state| {});
+
         // todo: implement game logic
         Ok(())
     }
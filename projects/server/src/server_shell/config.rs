@@ -1,11 +1,15 @@
 use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use networking::NetworkConfig;
+use world_core::tick::DEFAULT_RANDOM_TICK_SPEED;
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Config {
     pub runtime_config: RuntimeConfig,
     pub network_config: NetworkConfig,
+    #[serde(default)]
+    pub world_config: WorldConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,3 +26,23 @@ impl Default for RuntimeConfig {
     }
 }
 
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorldConfig {
+    /// The seed of the random block ticks.
+    pub seed: u64,
+    /// The number of blocks randomly ticked per chunk and per tick.
+    pub random_tick_speed: u32,
+    /// The vanilla blocks.json report, the water and the lava only flow when it's given.
+    pub block_report: Option<PathBuf>,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            block_report: None,
+        }
+    }
+}
@@ -2,6 +2,7 @@ mod config;
 
 use std::sync::Arc;
 use tokio::signal;
+use world_core::block_state::BlockStateRegistry;
 use crate::logical_server::GameServer;
 
 /**
@@ -72,7 +73,12 @@ impl ServerShell {
         self.starting()?;
 
         let new_connections = networking::build_plugin(self.config.network_config.clone(), self.tokio.clone())?;
-        let logical_server = GameServer::new(new_connections);
+        let world_config = &self.config.world_config;
+        let registry = match &world_config.block_report {
+            Some(path) => Some(BlockStateRegistry::from_vanilla_report(&std::fs::read_to_string(path)?)?),
+            None => None,
+        };
+        let logical_server = GameServer::new(new_connections, world_config.seed, world_config.random_tick_speed, registry.as_ref());
 
         let task = async {
 
@@ -98,6 +104,7 @@ impl ServerShell {
         let duration = std::time::Duration::from_millis(50); // 20 Hz
         let mut interval = tokio::time::interval(duration);
 
+        // the scheduled ticks are saved as delays, so the counter doesn't need to be persisted between two launches
         let mut tick_date = 0u64;
         loop {
             interval.tick().await;